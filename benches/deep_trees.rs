@@ -0,0 +1,55 @@
+//! Times building the CST of documents nested `N` levels deep. Run with
+//! `cargo bench --bench deep_trees`; the CST is built on the main thread's
+//! default stack, which only works because the conversion does not recurse.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use typst_ast::{ParseMode, ParseOptions, build_cst};
+
+const N: usize = 10_000;
+
+fn main() {
+    let cases = [
+        (
+            "parentheses",
+            format!("#{}1{}", "(".repeat(N), ")".repeat(N)),
+        ),
+        (
+            "content blocks",
+            format!("#{}x{}", "[".repeat(N), "]".repeat(N)),
+        ),
+    ];
+    let options = ParseOptions {
+        max_depth: Some(usize::MAX),
+        ..Default::default()
+    };
+
+    for (name, text) in cases {
+        // typst-syntax's parser and the trees' destructors recurse, so both
+        // run on a thread with a large stack.
+        let (root, parsed) = std::thread::Builder::new()
+            .stack_size(1 << 30)
+            .spawn(move || {
+                let start = Instant::now();
+                let root = ParseMode::Markup.parse(&text);
+                (root, start.elapsed())
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let start = Instant::now();
+        let cst = black_box(build_cst(&root, &options));
+        let built = start.elapsed();
+
+        std::thread::Builder::new()
+            .stack_size(1 << 30)
+            .spawn(move || drop((root, cst)))
+            .unwrap()
+            .join()
+            .unwrap();
+
+        println!("{name:>16}: parse {parsed:>10.2?}  cst {built:>10.2?}");
+    }
+}
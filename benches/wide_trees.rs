@@ -0,0 +1,46 @@
+//! Times parsing of documents whose root has a very large number of siblings.
+//! Run with `cargo bench --bench wide_trees`; every case should scale
+//! linearly with `N`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use typst_ast::ast::make_ast_result;
+use typst_ast::{ParseMode, ParseOptions, build_cst};
+
+const N: usize = 100_000;
+
+fn main() {
+    let cases = [
+        (
+            "table cells",
+            format!("#table(columns: 4, {})", "[cell], ".repeat(N)),
+        ),
+        ("embedded expressions", "#x ".repeat(N)),
+        ("equations", "$x$ ".repeat(N)),
+        ("set rules", "#set text(red)\n".repeat(N)),
+        ("documented bindings", "// Doc.\n#let x = 1\n".repeat(N)),
+        ("headings", "= Title\n".repeat(N)),
+        ("dictionary entries", format!("#(:{})", "a: 1, ".repeat(N))),
+    ];
+    let options = ParseOptions {
+        include_comments_in_ranges: true,
+        ..Default::default()
+    };
+
+    for (name, text) in &cases {
+        let start = Instant::now();
+        let root = ParseMode::Markup.parse(text);
+        let parsed = start.elapsed();
+
+        let start = Instant::now();
+        black_box(build_cst(&root, &options));
+        let cst = start.elapsed();
+
+        let start = Instant::now();
+        black_box(make_ast_result(&root, &ParseMode::Markup, &options).unwrap());
+        let ast = start.elapsed();
+
+        println!("{name:>22}: parse {parsed:>10.2?}  cst {cst:>10.2?}  ast {ast:>10.2?}");
+    }
+}
@@ -0,0 +1,128 @@
+use serde::Serialize;
+
+use crate::ast::{AstExpr, make_ast_result, walk};
+use crate::parse_options::ParseOptions;
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CallEntry {
+    pub range: [usize; 2],
+    /// Positional, named, and spread arguments, trailing content blocks
+    /// included.
+    pub arg_count: usize,
+}
+
+/// Parses `text` and lists, in document order, the calls whose callee is
+/// `name` itself or a field access ending in it, so `figure` matches
+/// `figure(..)`, `std.figure(..)`, and `x.figure()` alike. `link` and
+/// `heading` calls are found even with `ParseOptions::element_calls`.
+///
+/// ```
+/// use typst_ast::{ParseOptions, find_calls};
+///
+/// let calls = find_calls("#figure[A] #image(\"a.png\")", "figure", &ParseOptions::default())
+///     .unwrap();
+/// assert_eq!(calls.len(), 1);
+/// assert_eq!(calls[0].arg_count, 1);
+/// ```
+pub fn find_calls(
+    text: &str,
+    name: &str,
+    options: &ParseOptions,
+) -> Result<Vec<CallEntry>, String> {
+    let mode = options.mode.unwrap_or_default();
+    let root = mode.parse(text);
+    let options = ParseOptions {
+        element_calls: false,
+        ..options.clone()
+    };
+    let result = make_ast_result(&root, &mode, &options)?;
+    let mut calls = vec![];
+    for expr in &result.root {
+        walk(expr, &mut |expr| {
+            if let AstExpr::FuncCall {
+                range: Some(range),
+                callee,
+                args,
+                ..
+            } = expr
+                && callee_name(callee) == Some(name)
+            {
+                calls.push(CallEntry {
+                    range: *range,
+                    arg_count: args.len(),
+                });
+            }
+        });
+    }
+    Ok(calls)
+}
+
+pub(super) fn callee_name(callee: &AstExpr) -> Option<&str> {
+    match callee {
+        AstExpr::Ident { name, .. } | AstExpr::MathIdent { name, .. } => Some(name),
+        AstExpr::FieldAccess { field, .. } => Some(field),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_calls_by_callee_name() {
+        let text = "#figure(image(\"a.png\"), caption: [A])\n\
+                    #table(columns: 2)[x][y]\n\
+                    #block(std.figure[B])";
+        let calls = find_calls(text, "figure", &ParseOptions::default()).unwrap();
+        let found: Vec<_> = calls
+            .iter()
+            .map(|call| (&text[call.range[0]..call.range[1]], call.arg_count))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("figure(image(\"a.png\"), caption: [A])", 2),
+                ("std.figure[B]", 1)
+            ]
+        );
+
+        let tables = find_calls(text, "table", &ParseOptions::default()).unwrap();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].arg_count, 3);
+        assert!(
+            find_calls(text, "grid", &ParseOptions::default())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn matches_methods_by_their_field() {
+        let text = "#let x = (1, 2).map(v => v).len()";
+        let calls = find_calls(text, "map", &ParseOptions::default()).unwrap();
+        let [start, end] = calls[0].range;
+        assert_eq!(&text[start..end], "(1, 2).map(v => v)");
+    }
+
+    #[test]
+    fn finds_link_and_heading_calls() {
+        let text = "#link(\"https://typst.app\")[Typst] #heading(level: 2)[Intro]";
+        for element_calls in [false, true] {
+            let options = ParseOptions {
+                element_calls,
+                ..Default::default()
+            };
+            let links = find_calls(text, "link", &options).unwrap();
+            let [start, end] = links[0].range;
+            assert_eq!(&text[start..end], "link(\"https://typst.app\")[Typst]");
+            assert_eq!(links[0].arg_count, 2);
+
+            let headings = find_calls(text, "heading", &options).unwrap();
+            let [start, end] = headings[0].range;
+            assert_eq!(&text[start..end], "heading(level: 2)[Intro]");
+            assert_eq!(headings[0].arg_count, 2);
+        }
+    }
+}
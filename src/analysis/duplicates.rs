@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use typst_syntax::ast;
+use typst_syntax::{LinkedNode, SyntaxKind};
+
+use crate::parse_mode::ParseMode;
+
+struct Subtree {
+    hash: u64,
+    size: usize,
+    range: [usize; 2],
+}
+
+/// Groups structurally identical expressions that span at least `min_size`
+/// syntax nodes, returning the ranges of each group's occurrences.
+///
+/// Structure is compared by node kinds and token text only, so whitespace,
+/// comments, and positions are ignored. Groups whose occurrences all lie
+/// inside a larger reported duplicate are omitted. Larger groups come first.
+pub fn duplicate_subtrees(text: &str, mode: ParseMode, min_size: usize) -> Vec<Vec<[usize; 2]>> {
+    let root = mode.parse(text);
+    let mut subtrees = vec![];
+    fingerprint(&LinkedNode::new(&root), &mut subtrees);
+
+    let mut groups: HashMap<u64, Vec<&Subtree>> = HashMap::new();
+    for subtree in subtrees.iter().filter(|s| s.size >= min_size.max(1)) {
+        groups.entry(subtree.hash).or_default().push(subtree);
+    }
+    let mut groups: Vec<_> = groups.into_values().filter(|g| g.len() > 1).collect();
+    groups.sort_by_key(|g| (std::cmp::Reverse(g[0].size), g[0].range));
+
+    let mut reported: Vec<[usize; 2]> = vec![];
+    let mut out = vec![];
+    for group in groups {
+        let ranges: Vec<_> = group.iter().map(|s| s.range).collect();
+        let nested = ranges.iter().all(|&[start, end]| {
+            reported
+                .iter()
+                .any(|&[outer_start, outer_end]| outer_start <= start && end <= outer_end)
+        });
+        if !nested {
+            reported.extend(&ranges);
+            out.push(ranges);
+        }
+    }
+    out
+}
+
+/// Returns the structural hash and non-trivia node count of `node`, recording
+/// every expression subtree along the way.
+fn fingerprint(node: &LinkedNode, out: &mut Vec<Subtree>) -> (u64, usize) {
+    let mut hasher = DefaultHasher::new();
+    node.kind().hash(&mut hasher);
+    let mut size = 1;
+    if node.children().len() == 0 {
+        node.text().hash(&mut hasher);
+    }
+    for child in node.children().filter(|c| !c.kind().is_trivia()) {
+        let (hash, child_size) = fingerprint(&child, out);
+        hash.hash(&mut hasher);
+        size += child_size;
+    }
+    let hash = hasher.finish();
+    if node.cast::<ast::Expr>().is_some() && node.kind() != SyntaxKind::Space {
+        out.push(Subtree {
+            hash,
+            size,
+            range: [node.offset(), node.range().end],
+        });
+    }
+    (hash, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_identical_subtrees_ignoring_whitespace() {
+        let text = "#figure(image(\"a.png\"), caption: [A])\n#figure(image( \"a.png\" ),caption:[A])\n#figure(image(\"b.png\"), caption: [A])";
+        let groups = duplicate_subtrees(text, ParseMode::Markup, 8);
+        let snippets: Vec<Vec<&str>> = groups
+            .iter()
+            .map(|g| g.iter().map(|&[s, e]| &text[s..e]).collect())
+            .collect();
+        assert_eq!(
+            snippets,
+            [[
+                "figure(image(\"a.png\"), caption: [A])",
+                "figure(image( \"a.png\" ),caption:[A])"
+            ]]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use std::hash::Hasher;
+
+use siphasher::sip::SipHasher13;
+
+use super::parse_exprs;
+use crate::ast::AstExpr;
+use crate::parse_mode::ParseMode;
+
+/// Hashes the structure of the AST of `text`, for finding documents that
+/// differ only in their prose.
+///
+/// The hash covers the kind and number of children of every node, heading
+/// depths, the operators of unary and binary expressions, and the names of
+/// identifiers, math identifiers, accessed fields, labels, and references.
+/// Ranges, `space` nodes, and the content of text, strings, raw blocks, and
+/// all other literals are ignored.
+///
+/// Fingerprints can be stored: they are hashed with fixed-key SipHash over
+/// names and fixed-width integers, so they are the same across builds and
+/// platforms.
+pub fn fingerprint(text: &str, mode: ParseMode) -> u64 {
+    let mut hasher = SipHasher13::new_with_keys(0, 0);
+    hash_exprs(&parse_exprs(text, mode), &mut hasher);
+    hasher.finish()
+}
+
+fn hash_exprs<'a>(exprs: impl IntoIterator<Item = &'a AstExpr>, hasher: &mut SipHasher13) {
+    let exprs: Vec<_> = exprs
+        .into_iter()
+        .filter(|e| !matches!(e, AstExpr::Space { .. }))
+        .collect();
+    hasher.write_u64(exprs.len() as u64);
+    for expr in exprs {
+        write_str(hasher, expr.kind());
+        match expr {
+            AstExpr::Ident { name, .. }
+            | AstExpr::MathIdent { name, .. }
+            | AstExpr::Label { name, .. } => write_str(hasher, name),
+            AstExpr::FieldAccess { field, .. } => write_str(hasher, field),
+            AstExpr::Ref { target, .. } => write_str(hasher, target),
+            AstExpr::Heading { depth, .. } => hasher.write_u64(*depth as u64),
+            AstExpr::Unary { op, .. } => write_str(hasher, op.name()),
+            AstExpr::Binary { op, .. } => write_str(hasher, op.name()),
+            _ => {}
+        }
+        hash_exprs(expr.children(), hasher);
+    }
+}
+
+/// Writes the bytes of `text` followed by a terminator that no UTF-8 text
+/// contains, so that adjacent strings cannot run into each other.
+fn write_str(hasher: &mut SipHasher13, text: &str) {
+    hasher.write(text.as_bytes());
+    hasher.write_u8(0xff);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markup(text: &str) -> u64 {
+        fingerprint(text, ParseMode::Markup)
+    }
+
+    #[test]
+    fn ignores_prose_and_literals() {
+        let base = markup("= Intro\nSome *bold* words. #f(1, \"a\") @ref");
+        assert_eq!(
+            base,
+            markup("= Overview\nOther *strong*   text. #f(2, \"b\") @ref")
+        );
+    }
+
+    #[test]
+    fn tracks_structure_names_and_operators() {
+        let base = markup("= Intro\n*a* #f(x + 1)");
+        for changed in [
+            "== Intro\n*a* #f(x + 1)",
+            "= Intro\n_a_ #f(x + 1)",
+            "= Intro\n*a* #g(x + 1)",
+            "= Intro\n*a* #f(x - 1)",
+            "= Intro\n*a* #f(x + 1, y)",
+        ] {
+            assert_ne!(base, markup(changed), "{changed}");
+        }
+    }
+
+    #[test]
+    fn is_pinned_across_builds() {
+        assert_eq!(markup("= Intro\n*a* #f(x + 1)"), 1270589977376559841);
+    }
+}
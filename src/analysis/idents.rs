@@ -0,0 +1,84 @@
+use std::collections::BTreeSet;
+
+use super::{for_each_expr, parse_exprs};
+use crate::ast::AstExpr;
+use crate::ast::types::{DestructuringItem, LetBindingKind, Param, Pattern};
+use crate::parse_mode::ParseMode;
+
+/// Collects the distinct identifiers that `text` references but does not bind
+/// itself.
+///
+/// This is an approximation of the document's free variables: a name bound by
+/// a `let`, closure parameter, or `for` pattern *anywhere* in the document is
+/// excluded everywhere, without regard to scoping. Shadowing, names used
+/// before their binding, and names bound by imports are therefore not
+/// distinguished.
+pub fn referenced_idents(text: &str, mode: ParseMode) -> BTreeSet<String> {
+    let exprs = parse_exprs(text, mode);
+    let mut used = BTreeSet::new();
+    let mut bound = BTreeSet::new();
+
+    for_each_expr(&exprs, &mut |expr| match expr {
+        AstExpr::Ident { name, .. } | AstExpr::MathIdent { name, .. } => {
+            used.insert(name.clone());
+        }
+        AstExpr::LetBinding { binding_kind, .. } => match binding_kind {
+            LetBindingKind::Normal { pattern, .. } => bind_pattern(pattern, &mut bound),
+            LetBindingKind::Closure { name } => {
+                bound.insert(name.clone());
+            }
+        },
+        AstExpr::Closure { name, params, .. } => {
+            bound.extend(name.clone());
+            for param in params {
+                match param {
+                    Param::Pos { pattern } => bind_pattern(pattern, &mut bound),
+                    Param::Named { name, .. } => {
+                        bound.insert(name.clone());
+                    }
+                    Param::Spread { sink_ident, .. } => bound.extend(sink_ident.clone()),
+                }
+            }
+        }
+        AstExpr::ForLoop { pattern, .. } => bind_pattern(pattern, &mut bound),
+        _ => {}
+    });
+
+    used.retain(|name| !bound.contains(name));
+    used
+}
+
+fn bind_pattern(pattern: &Pattern, bound: &mut BTreeSet<String>) {
+    match pattern {
+        Pattern::Normal { expr } | Pattern::Parenthesized { expr } => {
+            if let AstExpr::Ident { name, .. } = expr.as_ref() {
+                bound.insert(name.clone());
+            }
+        }
+        Pattern::Placeholder { .. } => {}
+        Pattern::Destructuring { items, .. } => {
+            for item in items {
+                match item {
+                    DestructuringItem::Pattern { pattern }
+                    | DestructuringItem::Named { pattern, .. } => bind_pattern(pattern, bound),
+                    DestructuringItem::Spread { sink_ident } => bound.extend(sink_ident.clone()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excludes_locally_bound_names() {
+        let text = "#let (a, ..rest) = f(x)\n#let g(p, q: 1) = p + q + y\n#for i in range(n) [#i #a]\n$ alpha + beta $";
+        let idents = referenced_idents(text, ParseMode::Markup);
+        assert_eq!(
+            idents.into_iter().collect::<Vec<_>>(),
+            ["alpha", "beta", "f", "n", "range", "x", "y"]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use serde::Serialize;
+
+use crate::ast::types::{ImportItem, Imports};
+use crate::ast::{AstExpr, make_ast_result, walk};
+use crate::parse_options::ParseOptions;
+
+/// An `import` or `include` of another file.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ImportEntry {
+    Import {
+        range: [usize; 2],
+        source: ImportSource,
+        new_name: Option<String>,
+        /// `None` for a plain `import "a.typ"` without a colon.
+        imports: Option<ImportedNames>,
+    },
+    Include {
+        range: [usize; 2],
+        source: ImportSource,
+    },
+}
+
+/// What a file is imported from.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ImportSource {
+    /// A string literal, usually a path.
+    Str { value: String },
+    /// Any other expression, given by its `kind`, e.g. `ident` for a module.
+    Expr { expr_kind: &'static str },
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ImportedNames {
+    Wildcard,
+    Items { items: Vec<ImportedName> },
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedName {
+    /// The path of the imported item, e.g. `["b"]` for `b as c`.
+    pub path: Vec<String>,
+    pub new_name: Option<String>,
+}
+
+/// Parses `text` and lists its imports and includes in document order, e.g.
+/// to build a dependency graph.
+///
+/// ```
+/// use typst_ast::{ImportEntry, ImportSource, ParseOptions, collect_imports};
+///
+/// let imports = collect_imports("#include \"a.typ\"", &ParseOptions::default()).unwrap();
+/// let ImportEntry::Include { source, .. } = &imports[0] else { panic!() };
+/// assert_eq!(source, &ImportSource::Str { value: "a.typ".into() });
+/// ```
+pub fn collect_imports(text: &str, options: &ParseOptions) -> Result<Vec<ImportEntry>, String> {
+    let mode = options.mode.unwrap_or_default();
+    let root = mode.parse(text);
+    let result = make_ast_result(&root, &mode, options)?;
+    let mut out = vec![];
+    for expr in &result.root {
+        walk(expr, &mut |expr| match expr {
+            AstExpr::ModuleImport {
+                range: Some(range),
+                source,
+                new_name,
+                imports,
+                ..
+            } => out.push(ImportEntry::Import {
+                range: *range,
+                source: import_source(source),
+                new_name: new_name.clone(),
+                imports: imports.as_ref().map(imported_names),
+            }),
+            AstExpr::ModuleInclude {
+                range: Some(range),
+                source,
+                ..
+            } => out.push(ImportEntry::Include {
+                range: *range,
+                source: import_source(source),
+            }),
+            _ => {}
+        });
+    }
+    Ok(out)
+}
+
+fn import_source(source: &AstExpr) -> ImportSource {
+    match source {
+        AstExpr::Str { value, .. } => ImportSource::Str {
+            value: value.clone(),
+        },
+        _ => ImportSource::Expr {
+            expr_kind: source.kind(),
+        },
+    }
+}
+
+fn imported_names(imports: &Imports) -> ImportedNames {
+    match imports {
+        Imports::Wildcard => ImportedNames::Wildcard,
+        Imports::Items { items } => ImportedNames::Items {
+            items: items
+                .iter()
+                .map(|item| match item {
+                    ImportItem::Simple { path, .. } => ImportedName {
+                        path: path.clone(),
+                        new_name: None,
+                    },
+                    ImportItem::Renamed { path, new_name, .. } => ImportedName {
+                        path: path.clone(),
+                        new_name: Some(new_name.clone()),
+                    },
+                })
+                .collect(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_imports_and_includes() {
+        let text = "#import \"foo.typ\": a, b as c\n#include \"bar.typ\"\n#import mod: *";
+        let imports = collect_imports(text, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            imports,
+            [
+                ImportEntry::Import {
+                    range: [1, 28],
+                    source: ImportSource::Str {
+                        value: "foo.typ".into()
+                    },
+                    new_name: None,
+                    imports: Some(ImportedNames::Items {
+                        items: vec![
+                            ImportedName {
+                                path: vec!["a".into()],
+                                new_name: None,
+                            },
+                            ImportedName {
+                                path: vec!["b".into()],
+                                new_name: Some("c".into()),
+                            },
+                        ]
+                    }),
+                },
+                ImportEntry::Include {
+                    range: [30, 47],
+                    source: ImportSource::Str {
+                        value: "bar.typ".into()
+                    },
+                },
+                ImportEntry::Import {
+                    range: [49, 62],
+                    source: ImportSource::Expr { expr_kind: "ident" },
+                    new_name: None,
+                    imports: Some(ImportedNames::Wildcard),
+                },
+            ]
+        );
+
+        let json = serde_json::to_value(&imports[0]).unwrap();
+        assert_eq!(json["type"], "import");
+        assert_eq!(json["source"]["kind"], "str");
+        assert_eq!(json["imports"]["items"][1]["newName"], "c");
+    }
+}
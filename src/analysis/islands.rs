@@ -0,0 +1,96 @@
+use serde::Serialize;
+use typst_syntax::{LinkedNode, SyntaxKind};
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum IslandKind {
+    Markup,
+    Code,
+    Math,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Island {
+    pub kind: IslandKind,
+    pub range: [usize; 2],
+}
+
+/// Splits a markup document into consecutive markup, code, and math regions
+/// covering the whole source.
+///
+/// Embedded code (`#...`) and equations (`$...$`) inside markup elements such
+/// as headings or strong text are found too, but an island is never split
+/// further: `#[text #code]` is reported as a single code island.
+pub fn islands(text: &str) -> Vec<Island> {
+    let root = typst_syntax::parse(text);
+    let mut embedded = vec![];
+    collect_embedded(&LinkedNode::new(&root), &mut embedded);
+
+    let mut out = vec![];
+    let mut cursor = 0;
+    for island in embedded {
+        if cursor < island.range[0] {
+            out.push(Island {
+                kind: IslandKind::Markup,
+                range: [cursor, island.range[0]],
+            });
+        }
+        cursor = island.range[1];
+        out.push(island);
+    }
+    if cursor < text.len() {
+        out.push(Island {
+            kind: IslandKind::Markup,
+            range: [cursor, text.len()],
+        });
+    }
+    out
+}
+
+fn collect_embedded(node: &LinkedNode, out: &mut Vec<Island>) {
+    let mut children = node.children();
+    while let Some(child) = children.next() {
+        match child.kind() {
+            SyntaxKind::Hash => {
+                let end = children
+                    .next()
+                    .map_or(child.range().end, |expr| expr.range().end);
+                out.push(Island {
+                    kind: IslandKind::Code,
+                    range: [child.offset(), end],
+                });
+            }
+            SyntaxKind::Equation => out.push(Island {
+                kind: IslandKind::Math,
+                range: [child.offset(), child.range().end],
+            }),
+            _ => collect_embedded(&child, out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_markup_code_and_math() {
+        let text = "= A #x\n*b $y$* #[c #z] d";
+        let segments: Vec<_> = islands(text)
+            .into_iter()
+            .map(|island| (island.kind, &text[island.range[0]..island.range[1]]))
+            .collect();
+        assert_eq!(
+            segments,
+            [
+                (IslandKind::Markup, "= A "),
+                (IslandKind::Code, "#x"),
+                (IslandKind::Markup, "\n*b "),
+                (IslandKind::Math, "$y$"),
+                (IslandKind::Markup, "* "),
+                (IslandKind::Code, "#[c #z]"),
+                (IslandKind::Markup, " d"),
+            ]
+        );
+    }
+}
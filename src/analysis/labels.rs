@@ -0,0 +1,87 @@
+use serde::Serialize;
+
+use crate::ast::{AstExpr, make_ast_result, walk};
+use crate::parse_options::ParseOptions;
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LabelEntry {
+    pub name: String,
+    pub range: [usize; 2],
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct RefEntry {
+    pub target: String,
+    pub range: [usize; 2],
+}
+
+/// The labels and references of a document, each in document order.
+#[derive(Serialize, Debug, Default)]
+pub struct LabelsAndRefs {
+    pub labels: Vec<LabelEntry>,
+    pub refs: Vec<RefEntry>,
+}
+
+/// Parses `text` and collects every `<label>` and `@ref`, including those in
+/// headings, content blocks, and reference supplements. Whether a reference
+/// resolves is left to the caller.
+///
+/// ```
+/// use typst_ast::{ParseOptions, collect_labels_and_refs};
+///
+/// let found = collect_labels_and_refs("= Intro <intro>\nSee @intro.", &ParseOptions::default())
+///     .unwrap();
+/// assert_eq!(found.labels[0].name, "intro");
+/// assert_eq!(found.refs[0].target, "intro");
+/// ```
+pub fn collect_labels_and_refs(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<LabelsAndRefs, String> {
+    let mode = options.mode.unwrap_or_default();
+    let root = mode.parse(text);
+    let result = make_ast_result(&root, &mode, options)?;
+    let mut found = LabelsAndRefs::default();
+    for expr in &result.root {
+        walk(expr, &mut |expr| match expr {
+            AstExpr::Label {
+                range: Some(range),
+                name,
+                ..
+            } => found.labels.push(LabelEntry {
+                name: name.clone(),
+                range: *range,
+            }),
+            AstExpr::Ref {
+                range: Some(range),
+                target,
+                ..
+            } => found.refs.push(RefEntry {
+                target: target.clone(),
+                range: *range,
+            }),
+            _ => {}
+        });
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_nested_labels_and_refs() {
+        let text = "= Intro @a <a>\nSee @a[and @b].\n#block[*Table* <b>]";
+        let found = collect_labels_and_refs(text, &ParseOptions::default()).unwrap();
+        let names: Vec<_> = found.labels.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["a", "b"]);
+        let targets: Vec<_> = found.refs.iter().map(|r| r.target.as_str()).collect();
+        assert_eq!(targets, ["a", "a", "b"]);
+
+        let [start, end] = found.refs[2].range;
+        assert_eq!(&text[start..end], "@b");
+        let [start, end] = found.labels[1].range;
+        assert_eq!(&text[start..end], "<b>");
+    }
+}
@@ -0,0 +1,44 @@
+mod calls;
+mod duplicates;
+mod fingerprint;
+mod idents;
+mod imports;
+mod islands;
+mod labels;
+mod outline;
+mod position;
+mod query;
+mod raw;
+mod rules;
+mod words;
+
+use crate::ast::{AstExpr, make_ast_result, walk};
+use crate::parse_mode::ParseMode;
+use crate::parse_options::ParseOptions;
+
+pub use calls::{CallEntry, find_calls};
+pub use duplicates::duplicate_subtrees;
+pub use fingerprint::fingerprint;
+pub use idents::referenced_idents;
+pub use imports::{ImportEntry, ImportSource, ImportedName, ImportedNames, collect_imports};
+pub use islands::{Island, IslandKind, islands};
+pub use labels::{LabelEntry, LabelsAndRefs, RefEntry, collect_labels_and_refs};
+pub use outline::{OutlineEntry, outline};
+pub use position::{Ancestor, NodeAtOffset, ancestors_at_offset, node_at_offset};
+pub use query::query;
+pub use raw::raw_languages;
+pub use rules::{RuleEntry, SelectorKind, collect_rules};
+pub use words::{WordCount, word_count};
+
+fn parse_exprs(text: &str, mode: ParseMode) -> Vec<AstExpr> {
+    let root = mode.parse(text);
+    make_ast_result(&root, &mode, &ParseOptions::default())
+        .map(|result| result.root)
+        .unwrap_or_default()
+}
+
+pub(crate) fn for_each_expr<'a>(exprs: &'a [AstExpr], f: &mut impl FnMut(&'a AstExpr)) {
+    for expr in exprs {
+        walk(expr, f);
+    }
+}
@@ -0,0 +1,115 @@
+use serde::Serialize;
+
+use crate::ast::{AstExpr, extract_text, make_ast_result, walk};
+use crate::parse_mode::ParseMode;
+use crate::parse_options::ParseOptions;
+
+/// A heading and the deeper headings that follow it.
+#[derive(Serialize, Debug)]
+pub struct OutlineEntry {
+    pub level: usize,
+    pub title: String,
+    pub range: [usize; 2],
+    pub children: Vec<OutlineEntry>,
+}
+
+/// Parses `text` as markup and returns its headings as a tree. Each heading
+/// becomes a child of the closest preceding heading with a smaller level, so
+/// a skipped level nests under the nearest shallower ancestor. Headings inside
+/// content blocks count too.
+///
+/// ```
+/// use typst_ast::{ParseOptions, outline};
+///
+/// let outline = outline("= Intro\n=== Details", &ParseOptions::default()).unwrap();
+/// assert_eq!(outline[0].title, "Intro");
+/// assert_eq!(outline[0].children[0].title, "Details");
+/// ```
+pub fn outline(text: &str, options: &ParseOptions) -> Result<Vec<OutlineEntry>, String> {
+    let root = ParseMode::Markup.parse(text);
+    let result = make_ast_result(&root, &ParseMode::Markup, options)?;
+    let mut headings = vec![];
+    for expr in &result.root {
+        walk(expr, &mut |expr| {
+            if let AstExpr::Heading {
+                range: Some(range),
+                depth,
+                body,
+                ..
+            } = expr
+            {
+                headings.push(OutlineEntry {
+                    level: *depth,
+                    title: extract_text(body).trim().to_string(),
+                    range: *range,
+                    children: vec![],
+                });
+            }
+        });
+    }
+    Ok(nest(&mut headings.into_iter().peekable(), 0))
+}
+
+/// Takes the headings deeper than `level` off the front of `headings`,
+/// nesting each one's deeper successors below it.
+fn nest(
+    headings: &mut std::iter::Peekable<impl Iterator<Item = OutlineEntry>>,
+    level: usize,
+) -> Vec<OutlineEntry> {
+    let mut out = vec![];
+    while let Some(mut heading) = headings.next_if(|heading| heading.level > level) {
+        heading.children = nest(headings, heading.level);
+        out.push(heading);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(entries: &[OutlineEntry]) -> Vec<(usize, String, usize)> {
+        entries
+            .iter()
+            .map(|e| (e.level, e.title.clone(), e.children.len()))
+            .collect()
+    }
+
+    #[test]
+    fn nests_deeper_headings() {
+        let text = "= Intro\nText\n== *Bold* start\n== Second _part_\n=== Details\n= End";
+        let outline = outline(text, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            shape(&outline),
+            [(1, "Intro".into(), 2), (1, "End".into(), 0)]
+        );
+        assert_eq!(
+            shape(&outline[0].children),
+            [(2, "Bold start".into(), 0), (2, "Second part".into(), 1)]
+        );
+        assert_eq!(
+            shape(&outline[0].children[1].children),
+            [(3, "Details".into(), 0)]
+        );
+        let [start, end] = outline[0].children[1].children[0].range;
+        assert_eq!(&text[start..end], "=== Details");
+    }
+
+    #[test]
+    fn attaches_skipped_levels_to_the_nearest_shallower_heading() {
+        let text = "== Orphan\n= A\n=== Deep\n== B\n#block[= Nested]";
+        let outline = outline(text, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            shape(&outline),
+            [
+                (2, "Orphan".into(), 0),
+                (1, "A".into(), 2),
+                (1, "Nested".into(), 0)
+            ]
+        );
+        assert_eq!(
+            shape(&outline[1].children),
+            [(3, "Deep".into(), 0), (2, "B".into(), 0)]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use serde::Serialize;
+
+use crate::ast::{AstExpr, make_ast_result};
+use crate::parse_options::ParseOptions;
+
+/// The deepest AST node covering an offset.
+#[derive(Serialize)]
+pub struct NodeAtOffset {
+    pub node: AstExpr,
+    /// The kinds of the nodes enclosing `node`, outermost first.
+    pub ancestors: Vec<&'static str>,
+}
+
+/// A node on the path from the root to an offset.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Ancestor {
+    pub kind: &'static str,
+    pub range: [usize; 2],
+}
+
+/// Parses `text` and returns the smallest expression whose range contains
+/// `offset`, in the same units as the ranges `options` produce. Ranges are
+/// half-open, so an offset right after a node belongs to whatever follows it.
+/// `None` if no node with a range contains `offset`.
+///
+/// ```
+/// use typst_ast::{ParseOptions, node_at_offset};
+///
+/// let found = node_at_offset("#f(x + 1)", 3, &ParseOptions::default()).unwrap();
+/// let found = found.unwrap();
+/// assert_eq!(found.node.kind(), "ident");
+/// assert_eq!(found.ancestors, ["funcCall", "binary"]);
+/// ```
+pub fn node_at_offset(
+    text: &str,
+    offset: usize,
+    options: &ParseOptions,
+) -> Result<Option<NodeAtOffset>, String> {
+    with_path_at(text, offset, options, |mut path| {
+        let node = path.pop()?;
+        Some(NodeAtOffset {
+            node: node.clone(),
+            ancestors: path.iter().map(|expr| expr.kind()).collect(),
+        })
+    })
+}
+
+/// Parses `text` and returns the nodes from the top level down to the one
+/// [`node_at_offset`] finds, e.g. for breadcrumbs. Empty if there is none.
+///
+/// ```
+/// use typst_ast::{ParseOptions, ancestors_at_offset};
+///
+/// let path = ancestors_at_offset("= Hi *there*", 7, &ParseOptions::default()).unwrap();
+/// let kinds: Vec<_> = path.iter().map(|node| node.kind).collect();
+/// assert_eq!(kinds, ["heading", "strong", "text"]);
+/// ```
+pub fn ancestors_at_offset(
+    text: &str,
+    offset: usize,
+    options: &ParseOptions,
+) -> Result<Vec<Ancestor>, String> {
+    with_path_at(text, offset, options, |path| {
+        path.iter()
+            .filter_map(|expr| {
+                Some(Ancestor {
+                    kind: expr.kind(),
+                    range: expr.range()?,
+                })
+            })
+            .collect()
+    })
+}
+
+fn with_path_at<T>(
+    text: &str,
+    offset: usize,
+    options: &ParseOptions,
+    f: impl FnOnce(Vec<&AstExpr>) -> T,
+) -> Result<T, String> {
+    let mode = options.mode.unwrap_or_default();
+    let root = mode.parse(text);
+    let result = make_ast_result(&root, &mode, options)?;
+    Ok(f(path_at(&result.root, offset)))
+}
+
+/// The chain of expressions from a top-level one down to the deepest one
+/// containing `offset`. Nodes without a range, like placeholders, contain no
+/// offset, so the descent stops at them.
+fn path_at(exprs: &[AstExpr], offset: usize) -> Vec<&AstExpr> {
+    let mut path = vec![];
+    let mut candidates: Vec<&AstExpr> = exprs.iter().collect();
+    while let Some(expr) = candidates.iter().copied().find(|expr| {
+        expr.range()
+            .is_some_and(|[start, end]| start <= offset && offset < end)
+    }) {
+        path.push(expr);
+        candidates = expr.children();
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_mode::ParseMode;
+
+    fn lookup(
+        text: &str,
+        mode: ParseMode,
+        offset: usize,
+    ) -> Option<(&'static str, Vec<&'static str>)> {
+        let options = ParseOptions {
+            mode: Some(mode),
+            ..Default::default()
+        };
+        let found = node_at_offset(text, offset, &options).unwrap()?;
+        Some((found.node.kind(), found.ancestors))
+    }
+
+    #[test]
+    fn finds_the_innermost_node() {
+        let text = "= Hi *there*\n#f(x, y: 1)";
+        assert_eq!(
+            lookup(text, ParseMode::Markup, text.find("here").unwrap()),
+            Some(("text", vec!["heading", "strong"]))
+        );
+        assert_eq!(
+            lookup(text, ParseMode::Markup, text.find('x').unwrap()),
+            Some(("ident", vec!["funcCall"]))
+        );
+        // Argument names are not expressions.
+        assert_eq!(
+            lookup(text, ParseMode::Markup, text.find('y').unwrap()),
+            Some(("funcCall", vec![]))
+        );
+        assert_eq!(
+            lookup(text, ParseMode::Markup, text.find('1').unwrap()),
+            Some(("int", vec!["funcCall"]))
+        );
+        assert_eq!(
+            lookup("x^2", ParseMode::Math, 2),
+            Some(("mathText", vec!["mathAttach"]))
+        );
+    }
+
+    #[test]
+    fn finds_nothing_outside_of_nodes() {
+        assert_eq!(lookup("1 + 2  ", ParseMode::Code, 6), None);
+        assert_eq!(lookup("1 + 2", ParseMode::Code, 5), None);
+        assert_eq!(lookup("", ParseMode::Markup, 0), None);
+    }
+
+    #[test]
+    fn uses_the_units_of_the_ranges() {
+        let options = ParseOptions {
+            offset_encoding: crate::OffsetEncoding::Utf16,
+            base_offset: 10,
+            ..Default::default()
+        };
+        // `ä` is two bytes but one UTF-16 unit, so `x` is at unit 13, not 14.
+        let found = node_at_offset("ä #x", 13, &options).unwrap().unwrap();
+        assert_eq!(found.node.kind(), "ident");
+    }
+
+    #[test]
+    fn records_the_path_through_calls() {
+        let text = "#emph[*x*]";
+        let path = ancestors_at_offset(text, text.find('x').unwrap(), &ParseOptions::default());
+        assert_eq!(
+            path.unwrap(),
+            [
+                Ancestor {
+                    kind: "funcCall",
+                    range: [1, 10],
+                },
+                Ancestor {
+                    kind: "contentBlock",
+                    range: [5, 10],
+                },
+                Ancestor {
+                    kind: "strong",
+                    range: [6, 9],
+                },
+                Ancestor {
+                    kind: "text",
+                    range: [7, 8],
+                },
+            ]
+        );
+
+        let path = ancestors_at_offset(text, 2, &ParseOptions::default()).unwrap();
+        let kinds: Vec<_> = path.iter().map(|node| node.kind).collect();
+        assert_eq!(kinds, ["funcCall", "ident"]);
+        assert!(
+            ancestors_at_offset(text, 0, &ParseOptions::default())
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use super::parse_exprs;
+use crate::ast::AstExpr;
+use crate::output::kind_id;
+use crate::parse_mode::ParseMode;
+
+/// Returns the nodes matching a CSS-like `selector`, in document order.
+///
+/// Supported syntax:
+///
+/// - kind selectors using the `kind` names of the JSON output (`heading`,
+///   `funcCall`, ...) or `*` for any node,
+/// - attribute equality on `name`, `field`, and `callee`, e.g.
+///   `funcCall[callee=figure]` or `ident[name="x"]`,
+/// - the descendant (`a b`) and child (`a > b`) combinators.
+///
+/// Malformed selectors, including ones naming an unknown kind, are
+/// rejected with an error.
+///
+/// ```
+/// use typst_ast::{ParseMode, query};
+///
+/// let nodes = query("= Hi *there*", ParseMode::Markup, "heading > text").unwrap();
+/// assert_eq!(nodes.len(), 1);
+/// ```
+pub fn query(text: &str, mode: ParseMode, selector: &str) -> Result<Vec<AstExpr>, String> {
+    let steps = parse_selector(selector)?;
+    let exprs = parse_exprs(text, mode);
+    let mut out = vec![];
+    let mut ancestors = vec![];
+    for expr in &exprs {
+        collect(expr, &steps, &mut ancestors, &mut out);
+    }
+    Ok(out)
+}
+
+#[derive(Debug, PartialEq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Debug, PartialEq)]
+enum Attr {
+    Name,
+    Field,
+    Callee,
+}
+
+#[derive(Debug, PartialEq)]
+struct Compound {
+    kind: Option<String>,
+    attrs: Vec<(Attr, String)>,
+}
+
+/// A compound selector and the combinator linking it to the previous one.
+type Step = (Combinator, Compound);
+
+fn collect<'a>(
+    expr: &'a AstExpr,
+    steps: &[Step],
+    ancestors: &mut Vec<&'a AstExpr>,
+    out: &mut Vec<AstExpr>,
+) {
+    if matches(steps, expr, ancestors) {
+        out.push(expr.clone());
+    }
+    ancestors.push(expr);
+    for child in expr.children() {
+        collect(child, steps, ancestors, out);
+    }
+    ancestors.pop();
+}
+
+fn matches(steps: &[Step], expr: &AstExpr, ancestors: &[&AstExpr]) -> bool {
+    let Some(((combinator, compound), rest)) = steps.split_last() else {
+        return true;
+    };
+    if !compound.matches(expr) {
+        return false;
+    }
+    if rest.is_empty() {
+        return true;
+    }
+    match combinator {
+        Combinator::Child => ancestors
+            .split_last()
+            .is_some_and(|(parent, above)| matches(rest, parent, above)),
+        Combinator::Descendant => {
+            (0..ancestors.len()).any(|i| matches(rest, ancestors[i], &ancestors[..i]))
+        }
+    }
+}
+
+impl Compound {
+    fn matches(&self, expr: &AstExpr) -> bool {
+        if self.kind.as_deref().is_some_and(|kind| kind != expr.kind()) {
+            return false;
+        }
+        self.attrs
+            .iter()
+            .all(|(attr, value)| attr_value(expr, attr).as_deref() == Some(value.as_str()))
+    }
+}
+
+fn attr_value(expr: &AstExpr, attr: &Attr) -> Option<String> {
+    match (attr, expr) {
+        (Attr::Name, AstExpr::Ident { name, .. } | AstExpr::MathIdent { name, .. }) => {
+            Some(name.clone())
+        }
+        (Attr::Name, AstExpr::Label { name, .. }) => Some(name.clone()),
+        (Attr::Name, AstExpr::Closure { name, .. }) => name.clone(),
+        (Attr::Field, AstExpr::FieldAccess { field, .. }) => Some(field.clone()),
+        (Attr::Callee, AstExpr::FuncCall { callee, .. }) => dotted_path(callee),
+        _ => None,
+    }
+}
+
+/// Renders `a` or `a.b.c` callees; anything else has no name.
+fn dotted_path(expr: &AstExpr) -> Option<String> {
+    match expr {
+        AstExpr::Ident { name, .. } => Some(name.clone()),
+        AstExpr::FieldAccess { target, field, .. } => {
+            Some(format!("{}.{field}", dotted_path(target)?))
+        }
+        _ => None,
+    }
+}
+
+fn parse_selector(selector: &str) -> Result<Vec<Step>, String> {
+    let mut parser = SelectorParser {
+        chars: selector.char_indices().peekable(),
+    };
+    let mut steps = vec![];
+    let mut combinator = Combinator::Descendant;
+    loop {
+        let had_space = parser.skip_whitespace();
+        match parser.peek() {
+            None if steps.is_empty() => return Err("Empty selector".into()),
+            None if combinator == Combinator::Child => {
+                return Err("Expected a selector after `>`".into());
+            }
+            None => return Ok(steps),
+            Some('>') => {
+                if steps.is_empty() || combinator == Combinator::Child {
+                    return Err(parser.unexpected());
+                }
+                parser.chars.next();
+                combinator = Combinator::Child;
+            }
+            Some(_) if !steps.is_empty() && !had_space && combinator == Combinator::Descendant => {
+                return Err(parser.unexpected());
+            }
+            Some(_) => {
+                steps.push((combinator, parser.compound()?));
+                combinator = Combinator::Descendant;
+            }
+        }
+    }
+}
+
+struct SelectorParser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl SelectorParser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn skip_whitespace(&mut self) -> bool {
+        let mut skipped = false;
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.chars.next();
+            skipped = true;
+        }
+        skipped
+    }
+
+    fn unexpected(&mut self) -> String {
+        match self.chars.peek() {
+            Some(&(i, c)) => format!("Unexpected `{c}` at offset {i} in selector"),
+            None => "Unexpected end of selector".into(),
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek().filter(|c| c.is_alphanumeric() || *c == '_') {
+            word.push(c);
+            self.chars.next();
+        }
+        word
+    }
+
+    fn compound(&mut self) -> Result<Compound, String> {
+        let kind = if self.peek() == Some('*') {
+            self.chars.next();
+            None
+        } else {
+            let kind = self.word();
+            if kind.is_empty() && self.peek() != Some('[') {
+                return Err(self.unexpected());
+            }
+            if !kind.is_empty() && kind_id(&kind).is_none() {
+                return Err(format!("Unknown kind `{kind}`"));
+            }
+            (!kind.is_empty()).then_some(kind)
+        };
+
+        let mut attrs = vec![];
+        while self.peek() == Some('[') {
+            self.chars.next();
+            let attr = match self.word().as_str() {
+                "name" => Attr::Name,
+                "field" => Attr::Field,
+                "callee" => Attr::Callee,
+                "" => return Err(self.unexpected()),
+                other => {
+                    return Err(format!(
+                        "Unknown attribute `{other}`, expected `name`, `field`, or `callee`"
+                    ));
+                }
+            };
+            if self.peek() != Some('=') {
+                return Err(self.unexpected());
+            }
+            self.chars.next();
+            let value = self.value()?;
+            if self.peek() != Some(']') {
+                return Err(self.unexpected());
+            }
+            self.chars.next();
+            attrs.push((attr, value));
+        }
+        Ok(Compound { kind, attrs })
+    }
+
+    fn value(&mut self) -> Result<String, String> {
+        if self.peek() != Some('"') {
+            let mut value = String::new();
+            while let Some(c) = self.peek().filter(|&c| c != ']' && !c.is_whitespace()) {
+                value.push(c);
+                self.chars.next();
+            }
+            return Ok(value);
+        }
+        self.chars.next();
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(value),
+                Some((_, c)) => value.push(c),
+                None => return Err("Unterminated string in selector".into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(text: &str, mode: ParseMode, selector: &str) -> Vec<String> {
+        query(text, mode, selector)
+            .unwrap()
+            .iter()
+            .map(|expr| {
+                let [start, end] = expr.range().unwrap();
+                text[start..end].to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_kinds_and_combinators() {
+        let text = "= Hello *world*\nplain";
+        assert_eq!(ranges(text, ParseMode::Markup, "heading > text"), ["Hello"]);
+        assert_eq!(
+            ranges(text, ParseMode::Markup, "heading text"),
+            ["Hello", "world"]
+        );
+        assert_eq!(ranges(text, ParseMode::Markup, "strong>*"), ["world"]);
+    }
+
+    #[test]
+    fn matches_attributes() {
+        let text = "#figure(image(\"a.png\"))\n#std.figure[b]\n#x.y";
+        assert_eq!(
+            ranges(text, ParseMode::Markup, "funcCall[callee=figure]"),
+            ["figure(image(\"a.png\"))"]
+        );
+        assert_eq!(
+            ranges(text, ParseMode::Markup, "funcCall[callee=\"std.figure\"]"),
+            ["std.figure[b]"]
+        );
+        assert_eq!(ranges(text, ParseMode::Markup, "[field=y]"), ["x.y"]);
+        assert_eq!(
+            ranges(text, ParseMode::Markup, "funcCall ident[name=image]"),
+            ["image"]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_selectors() {
+        for selector in [
+            "",
+            "heading >",
+            "> text",
+            "ident[size=1]",
+            "ident[name=x",
+            "ident[name=\"x]",
+            "ident$b",
+            "ident > > text",
+            "headng > text",
+            "funccall",
+        ] {
+            assert!(
+                query("x", ParseMode::Markup, selector).is_err(),
+                "expected {selector:?} to be rejected"
+            );
+        }
+        assert_eq!(
+            query("x", ParseMode::Markup, "headng > text")
+                .err()
+                .as_deref(),
+            Some("Unknown kind `headng`")
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use std::collections::BTreeMap;
+
+use super::{for_each_expr, parse_exprs};
+use crate::ast::AstExpr;
+use crate::parse_mode::ParseMode;
+
+/// Counts the raw blocks and inline raw spans of a markup document by
+/// language tag. Untagged raw is counted under `"<none>"`.
+pub fn raw_languages(text: &str) -> BTreeMap<String, usize> {
+    let exprs = parse_exprs(text, ParseMode::Markup);
+    let mut counts = BTreeMap::new();
+    for_each_expr(&exprs, &mut |expr| {
+        if let AstExpr::Raw { lang, .. } = expr {
+            let lang = lang.as_deref().unwrap_or("<none>");
+            *counts.entry(lang.to_string()).or_insert(0) += 1;
+        }
+    });
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_languages_including_untagged() {
+        let text = "```rust\nfn main() {}\n```\n`x` and ```py y```\n#[```rust\nlet a;\n```]";
+        let counts = raw_languages(text);
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            [
+                ("<none>".to_string(), 1),
+                ("py".to_string(), 1),
+                ("rust".to_string(), 2)
+            ]
+        );
+    }
+}
@@ -0,0 +1,189 @@
+use serde::Serialize;
+
+use crate::ast::{AstExpr, make_ast_result, walk};
+use crate::parse_options::ParseOptions;
+
+/// A `set` or `show` rule. Both variants have the same fields, so that every
+/// entry can be read alike; those that only apply to the other kind of rule
+/// hold `false` or `None`.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum RuleEntry {
+    Set {
+        range: [usize; 2],
+        /// The name of the function set, if it is a plain identifier like
+        /// `text`.
+        target: Option<String>,
+        /// Whether the rule only applies `if` a condition holds.
+        has_condition: bool,
+        /// Always `None`.
+        selector_kind: Option<SelectorKind>,
+    },
+    Show {
+        range: [usize; 2],
+        /// The selector, if it is a plain identifier like `heading`.
+        target: Option<String>,
+        /// Always `false`.
+        has_condition: bool,
+        /// Always `Some`.
+        selector_kind: Option<SelectorKind>,
+    },
+}
+
+/// What a show rule selects.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SelectorKind {
+    /// An element function, like `heading`, `math.equation`, or
+    /// `heading.where(level: 1)`.
+    Function,
+    /// A `<label>`.
+    Label,
+    /// A string, matching text.
+    Text,
+    /// A `regex(..)`.
+    Regex,
+    /// Any other expression, like `selector(..)` or a variable.
+    Other,
+    /// No selector: `show: ..` applies to everything after it.
+    Absent,
+}
+
+/// Parses `text` and lists its `set` and `show` rules in document order,
+/// including those nested in blocks and function bodies.
+///
+/// ```
+/// use typst_ast::{ParseOptions, RuleEntry, collect_rules};
+///
+/// let rules = collect_rules("#set text(red)", &ParseOptions::default()).unwrap();
+/// let RuleEntry::Set { target, .. } = &rules[0] else { panic!() };
+/// assert_eq!(target.as_deref(), Some("text"));
+/// ```
+pub fn collect_rules(text: &str, options: &ParseOptions) -> Result<Vec<RuleEntry>, String> {
+    let mode = options.mode.unwrap_or_default();
+    let root = mode.parse(text);
+    let result = make_ast_result(&root, &mode, options)?;
+    let mut out = vec![];
+    for expr in &result.root {
+        walk(expr, &mut |expr| match expr {
+            AstExpr::SetRule {
+                range: Some(range),
+                target,
+                condition,
+                ..
+            } => out.push(RuleEntry::Set {
+                range: *range,
+                target: ident_name(target),
+                has_condition: condition.is_some(),
+                selector_kind: None,
+            }),
+            AstExpr::ShowRule {
+                range: Some(range),
+                selector,
+                ..
+            } => out.push(RuleEntry::Show {
+                range: *range,
+                target: selector.as_deref().and_then(ident_name),
+                has_condition: false,
+                selector_kind: Some(
+                    selector
+                        .as_deref()
+                        .map_or(SelectorKind::Absent, selector_kind),
+                ),
+            }),
+            _ => {}
+        });
+    }
+    Ok(out)
+}
+
+fn ident_name(expr: &AstExpr) -> Option<String> {
+    match expr {
+        AstExpr::Ident { name, .. } => Some(name.clone()),
+        _ => None,
+    }
+}
+
+fn selector_kind(selector: &AstExpr) -> SelectorKind {
+    match selector {
+        AstExpr::Ident { .. } | AstExpr::FieldAccess { .. } => SelectorKind::Function,
+        AstExpr::Label { .. } => SelectorKind::Label,
+        AstExpr::Str { .. } => SelectorKind::Text,
+        AstExpr::FuncCall { callee, .. } => match callee.as_ref() {
+            AstExpr::Ident { name, .. } if name == "regex" => SelectorKind::Regex,
+            AstExpr::FieldAccess { target, field, .. }
+                if field == "where" && selector_kind(target) == SelectorKind::Function =>
+            {
+                SelectorKind::Function
+            }
+            _ => SelectorKind::Other,
+        },
+        _ => SelectorKind::Other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_set_and_show_rules() {
+        let text = "#set text(size: 12pt)\n\
+                    #show heading: it => it\n\
+                    #{\n  set par(justify: true) if true\n  show <a>: none\n}\n\
+                    #show heading.where(level: 1): strong\n\
+                    #show \"Typst\": smallcaps\n\
+                    #show regex(\"\\d+\"): underline\n\
+                    #show: rest => rest";
+        let rules = collect_rules(text, &ParseOptions::default()).unwrap();
+        let set = |target: &str, has_condition| RuleEntry::Set {
+            range: [0, 0],
+            target: Some(target.into()),
+            has_condition,
+            selector_kind: None,
+        };
+        let show = |target: Option<&str>, selector_kind| RuleEntry::Show {
+            range: [0, 0],
+            target: target.map(Into::into),
+            has_condition: false,
+            selector_kind: Some(selector_kind),
+        };
+        let without_ranges: Vec<_> = rules
+            .into_iter()
+            .map(|mut rule| {
+                match &mut rule {
+                    RuleEntry::Set { range, .. } | RuleEntry::Show { range, .. } => *range = [0, 0],
+                }
+                rule
+            })
+            .collect();
+        assert_eq!(
+            without_ranges,
+            [
+                set("text", false),
+                show(Some("heading"), SelectorKind::Function),
+                set("par", true),
+                show(None, SelectorKind::Label),
+                show(None, SelectorKind::Function),
+                show(None, SelectorKind::Text),
+                show(None, SelectorKind::Regex),
+                show(None, SelectorKind::Absent),
+            ]
+        );
+
+        let rules = collect_rules(text, &ParseOptions::default()).unwrap();
+        let RuleEntry::Show { range, .. } = &rules[1] else {
+            panic!("expected a show rule");
+        };
+        assert_eq!(&text[range[0]..range[1]], "show heading: it => it");
+
+        let json = serde_json::to_value(&rules).unwrap();
+        assert_eq!(json[0]["selectorKind"], serde_json::Value::Null);
+        assert_eq!(json[1]["hasCondition"], false);
+        assert_eq!(json[1]["selectorKind"], "function");
+    }
+}
@@ -0,0 +1,79 @@
+use serde::Serialize;
+
+use super::calls::callee_name;
+use crate::ast::{AstExpr, make_ast_result, markup_text, walk};
+use crate::parse_mode::ParseMode;
+use crate::parse_options::ParseOptions;
+
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct WordCount {
+    /// Runs of non-whitespace text holding a letter or digit, so that a
+    /// lone `--` is not a word.
+    pub words: usize,
+    /// Characters of the words, not counting whitespace.
+    pub characters: usize,
+    pub headings: usize,
+    /// Calls of `figure`.
+    pub figures: usize,
+    pub equations: usize,
+}
+
+/// Parses `text` as markup and counts the words of its prose, leaving out
+/// code, raw text, and math, along with its headings, figures, and
+/// equations. Words in headings and other nested markup count too.
+///
+/// ```
+/// use typst_ast::{ParseOptions, word_count};
+///
+/// let count = word_count("= Intro\nSome *bold* text $x$.", &ParseOptions::default()).unwrap();
+/// assert_eq!(count.words, 4);
+/// assert_eq!(count.headings, 1);
+/// assert_eq!(count.equations, 1);
+/// ```
+pub fn word_count(text: &str, options: &ParseOptions) -> Result<WordCount, String> {
+    let root = ParseMode::Markup.parse(text);
+    let result = make_ast_result(&root, &ParseMode::Markup, options)?;
+    let mut count = WordCount::default();
+    for word in markup_text(&result.root).split_whitespace() {
+        if word.chars().any(char::is_alphanumeric) {
+            count.words += 1;
+            count.characters += word.chars().count();
+        }
+    }
+    for expr in &result.root {
+        walk(expr, &mut |expr| match expr {
+            AstExpr::Heading { .. } => count.headings += 1,
+            AstExpr::Equation { .. } => count.equations += 1,
+            AstExpr::FuncCall { callee, .. } if callee_name(callee) == Some("figure") => {
+                count.figures += 1
+            }
+            _ => {}
+        });
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_prose_and_structure() {
+        let text = "= A _short_ intro\n\
+                    Don't count `raw text` or #let x = 1 code -- only prose.\n\n\
+                    #figure(image(\"a.png\"), caption: [A cat])\n\
+                    $ e = m c^2 $ and $x$\n\
+                    == Done";
+        let count = word_count(text, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            count,
+            WordCount {
+                words: 11,
+                characters: 44,
+                headings: 2,
+                figures: 1,
+                equations: 2,
+            }
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use super::types::MathIdentRole;
+
+/// Element functions that Typst's standard library exposes at the top level,
+/// i.e. the names that are valid as plain-identifier `set`/`show` targets.
+/// Kept sorted so lookups can binary search.
+pub const KNOWN_ELEMENTS: &[&str] = &[
+    "align",
+    "bibliography",
+    "block",
+    "box",
+    "circle",
+    "cite",
+    "colbreak",
+    "columns",
+    "curve",
+    "document",
+    "ellipse",
+    "emph",
+    "enum",
+    "figure",
+    "footnote",
+    "grid",
+    "h",
+    "heading",
+    "hide",
+    "highlight",
+    "image",
+    "line",
+    "linebreak",
+    "link",
+    "list",
+    "metadata",
+    "move",
+    "outline",
+    "overline",
+    "pad",
+    "page",
+    "pagebreak",
+    "par",
+    "parbreak",
+    "path",
+    "place",
+    "polygon",
+    "quote",
+    "raw",
+    "rect",
+    "ref",
+    "repeat",
+    "rotate",
+    "scale",
+    "skew",
+    "smallcaps",
+    "smartquote",
+    "square",
+    "stack",
+    "strike",
+    "strong",
+    "sub",
+    "super",
+    "table",
+    "terms",
+    "text",
+    "title",
+    "underline",
+    "v",
+];
+
+pub fn is_known_element(name: &str) -> bool {
+    KNOWN_ELEMENTS.binary_search(&name).is_ok()
+}
+
+/// Math identifiers that act as functions: the text operators Typst renders
+/// upright (`sin`, `lim`) and the functions of the `math` module. Sorted.
+pub const MATH_FUNCTIONS: &[&str] = &[
+    "Pr",
+    "abs",
+    "accent",
+    "arccos",
+    "arcsin",
+    "arctan",
+    "arg",
+    "attach",
+    "bb",
+    "binom",
+    "bold",
+    "cal",
+    "cancel",
+    "cases",
+    "ceil",
+    "class",
+    "cos",
+    "cosh",
+    "cot",
+    "coth",
+    "csc",
+    "csch",
+    "ctg",
+    "deg",
+    "det",
+    "dim",
+    "display",
+    "exp",
+    "floor",
+    "frac",
+    "frak",
+    "gcd",
+    "hom",
+    "id",
+    "im",
+    "inf",
+    "inline",
+    "italic",
+    "ker",
+    "lcm",
+    "lg",
+    "lim",
+    "liminf",
+    "limits",
+    "limsup",
+    "ln",
+    "log",
+    "lr",
+    "mat",
+    "max",
+    "mid",
+    "min",
+    "mod",
+    "mono",
+    "norm",
+    "op",
+    "overbrace",
+    "overbracket",
+    "overline",
+    "overparen",
+    "overshell",
+    "primes",
+    "root",
+    "round",
+    "sans",
+    "scr",
+    "script",
+    "scripts",
+    "sec",
+    "sech",
+    "serif",
+    "sin",
+    "sinc",
+    "sinh",
+    "sqrt",
+    "sscript",
+    "stretch",
+    "sup",
+    "tan",
+    "tanh",
+    "tg",
+    "tr",
+    "underbrace",
+    "underbracket",
+    "underline",
+    "underparen",
+    "undershell",
+    "upright",
+    "vec",
+];
+
+/// Math identifiers that name common symbols, such as Greek letters and
+/// operators, as opposed to user variables. Far from every symbol Typst
+/// knows. Sorted.
+pub const MATH_SYMBOLS: &[&str] = &[
+    "Alpha", "Beta", "Chi", "Delta", "Epsilon", "Eta", "Gamma", "Iota", "Kappa", "Lambda", "Mu",
+    "Nu", "Omega", "Omicron", "Phi", "Pi", "Psi", "Rho", "Sigma", "Tau", "Theta", "Upsilon", "Xi",
+    "Zeta", "aleph", "alpha", "and", "angle", "approx", "arrow", "ast", "bar", "beta", "beth",
+    "bot", "chi", "circle", "colon", "comma", "compose", "convolve", "degree", "delta", "diff",
+    "div", "dot", "dots", "ell", "emptyset", "epsilon", "eq", "equiv", "eta", "exists", "forall",
+    "gamma", "gimel", "gt", "harpoon", "in", "infinity", "integral", "iota", "kappa", "lambda",
+    "lt", "minus", "mu", "nabla", "not", "nothing", "nu", "omega", "omicron", "oo", "or",
+    "parallel", "partial", "perp", "phi", "pi", "planck", "plus", "prime", "product", "prop",
+    "psi", "qed", "rho", "sect", "sigma", "star", "subset", "sum", "supset", "tau", "theta",
+    "tilde", "times", "top", "union", "upsilon", "without", "xi", "zeta",
+];
+
+/// Best-effort classification of a math identifier by name.
+pub fn math_ident_role(name: &str) -> MathIdentRole {
+    if MATH_FUNCTIONS.binary_search(&name).is_ok() {
+        MathIdentRole::Function
+    } else if MATH_SYMBOLS.binary_search(&name).is_ok() {
+        MathIdentRole::Symbol
+    } else {
+        MathIdentRole::Variable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn element_list_is_sorted() {
+        assert!(KNOWN_ELEMENTS.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn math_lists_are_sorted() {
+        assert!(MATH_FUNCTIONS.windows(2).all(|w| w[0] < w[1]));
+        assert!(MATH_SYMBOLS.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn recognizes_known_elements() {
+        for name in ["heading", "figure", "table", "text", "v"] {
+            assert!(is_known_element(name), "{name} should be known");
+        }
+        for name in ["headings", "foo", "Heading", ""] {
+            assert!(!is_known_element(name), "{name} should be unknown");
+        }
+    }
+}
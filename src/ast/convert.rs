@@ -1,302 +1,1118 @@
+use std::cell::{Cell, RefCell};
 use std::num::NonZeroUsize;
 
 use typst_syntax::ast::{self, AstNode};
+use typst_syntax::{LinkedNode, SyntaxKind, SyntaxNode};
 
+use super::builtins;
 use super::expr::AstExpr;
-use super::offset::{OffsetMap, range_of};
+use super::offset::{Range, child, children};
+use super::stable_id::StableIds;
+use super::text::extract_text;
 use super::types::*;
+use super::{AstParseResult, convert_tree};
+use crate::cst;
+use crate::parse_mode::ParseMode;
+use crate::parse_options::{ParseOptions, TextMode};
+use crate::range_mapper::RangeMapper;
 
-pub fn convert_markup(markup: ast::Markup, offsets: &OffsetMap) -> Vec<AstExpr> {
-    markup.exprs().map(|e| convert_expr(e, offsets)).collect()
+pub struct Context<'a> {
+    pub options: &'a ParseOptions,
+    pub mapper: &'a RangeMapper,
+    pub stable_ids: &'a StableIds,
+    /// How many raw blocks deep the converted tree is nested.
+    pub nesting: usize,
+    /// See [`heading_offsets`].
+    pub heading_offsets: &'a [(usize, Option<usize>)],
+    /// How many expressions enclose the one being converted.
+    pub depth: Cell<usize>,
+    /// The parsed text, only with `ParseOptions::include_source`.
+    pub source: Option<&'a str>,
+    /// Set by [`ast_events`](super::ast_events) to convert one node at a time.
+    pub frontier: Option<&'a Frontier>,
 }
 
-pub fn convert_code(code: ast::Code, offsets: &OffsetMap) -> Vec<AstExpr> {
-    code.exprs().map(|e| convert_expr(e, offsets)).collect()
+/// Limits conversion to the first expression or pattern entered: those
+/// below it are left as `truncated` stand-ins and recorded, so that they can
+/// be converted in turn.
+#[derive(Default)]
+pub struct Frontier {
+    entered: Cell<bool>,
+    skipped: RefCell<Vec<Skipped>>,
 }
 
-pub fn convert_math(math: ast::Math, offsets: &OffsetMap) -> Vec<AstExpr> {
-    math.exprs().map(|e| convert_expr(e, offsets)).collect()
+impl Frontier {
+    pub fn into_skipped(self) -> Vec<Skipped> {
+        self.skipped.into_inner()
+    }
+}
+
+/// A node a [`Frontier`] left out.
+pub struct Skipped {
+    /// The range and syntax kind of its stand-in.
+    pub range: Range,
+    pub syntax_kind: String,
+    /// The child indices leading to the node from the root.
+    pub path: Vec<usize>,
+    /// Whether the node is to be converted as a pattern other than a plain
+    /// expression.
+    pub pattern: bool,
 }
 
-pub fn convert_expr(expr: ast::Expr, offsets: &OffsetMap) -> AstExpr {
+impl Context<'_> {
+    fn range(&self, node: &LinkedNode) -> Range {
+        self.map(self.source_range(node))
+    }
+
+    /// The byte range of `node` in the parsed text, before mapping. Detached
+    /// placeholders have none.
+    fn source_range(&self, node: &LinkedNode) -> Range {
+        node.parent()?;
+        Some([node.offset(), node.offset() + node.len()])
+    }
+
+    fn meta(&self, node: &LinkedNode) -> NodeMeta {
+        NodeMeta {
+            stable_id: self.stable_ids.get(node).map(str::to_string),
+            hash_prefixed: false,
+            node_depth: None,
+            source: self.source.and_then(|text| {
+                let [start, end] = self.source_range(node)?;
+                Some(text[start..end].to_string())
+            }),
+        }
+    }
+
+    fn map(&self, range: Range) -> Range {
+        range.map(|r| self.mapper.map(r))
+    }
+
+    /// Whether `node` is nested more than `max_depth` levels below the root,
+    /// where the CST leaves nodes out. Conversion stops there, so that it
+    /// cannot overflow the stack.
+    fn too_deep(&self, node: &LinkedNode) -> bool {
+        let ancestors = std::iter::successors(node.parent(), |parent| parent.parent());
+        ancestors.take(self.options.max_depth() + 1).count() > self.options.max_depth()
+    }
+
+    /// Whether `node` lies below the node the [`Frontier`] converts, in which
+    /// case it is recorded there. Leaves are converted right away, as they
+    /// cost no more than their stand-ins.
+    fn beyond_frontier(&self, node: &LinkedNode, pattern: bool) -> bool {
+        let Some(frontier) = self.frontier else {
+            return false;
+        };
+        if !frontier.entered.replace(true) || node.children().len() == 0 {
+            return false;
+        }
+        let mut path = vec![];
+        let mut current = node.clone();
+        while let Some(parent) = current.parent() {
+            path.push(current.index());
+            current = parent.clone();
+        }
+        path.reverse();
+        frontier.skipped.borrow_mut().push(Skipped {
+            range: self.range(node),
+            syntax_kind: format!("{:?}", node.kind()),
+            path,
+            pattern,
+        });
+        true
+    }
+
+    /// The node standing in for `node` if it is [too deep](Self::too_deep).
+    fn truncated(&self, node: &LinkedNode) -> AstExpr {
+        AstExpr::Truncated {
+            range: self.range(node),
+            meta: self.meta(node),
+            syntax_kind: format!("{:?}", node.kind()),
+        }
+    }
+}
+
+pub fn convert_markup<'a>(
+    markup: ast::Markup<'a>,
+    node: &LinkedNode<'a>,
+    ctx: &Context,
+) -> Vec<AstExpr> {
+    let exprs = convert_hash_prefixed(markup.exprs(), node, ctx);
+    match ctx.options.text_mode {
+        TextMode::Raw => exprs,
+        TextMode::Resolved => resolve_text(exprs),
+    }
+}
+
+/// Merges runs of text, escapes, shorthands, and smart quotes into single
+/// `Text` nodes holding the characters they stand for. Spaces inside or after
+/// such a run join it as a single space. A smart quote opens
+/// at the start of a run or after whitespace or an opening bracket, and
+/// closes anywhere else.
+fn resolve_text(exprs: Vec<AstExpr>) -> Vec<AstExpr> {
+    let mut out: Vec<AstExpr> = Vec::with_capacity(exprs.len());
+    for expr in exprs {
+        let piece = match &expr {
+            AstExpr::Text { text, .. } => text.clone(),
+            AstExpr::Escape { character, .. } | AstExpr::Shorthand { character, .. } => {
+                character.to_string()
+            }
+            AstExpr::Space { .. } if matches!(out.last(), Some(AstExpr::Text { .. })) => {
+                " ".to_string()
+            }
+            AstExpr::SmartQuote { double, .. } => {
+                let opens = match out.last() {
+                    Some(AstExpr::Text { text, .. }) => text
+                        .chars()
+                        .last()
+                        .is_none_or(|c| c.is_whitespace() || "([{".contains(c)),
+                    Some(AstExpr::Space { .. } | AstExpr::Parbreak { .. }) | None => true,
+                    Some(_) => false,
+                };
+                match (double, opens) {
+                    (true, true) => "“",
+                    (true, false) => "”",
+                    (false, true) => "‘",
+                    (false, false) => "’",
+                }
+                .to_string()
+            }
+            _ => {
+                out.push(expr);
+                continue;
+            }
+        };
+        match out.last_mut() {
+            Some(AstExpr::Text {
+                range, meta, text, ..
+            }) => {
+                text.push_str(&piece);
+                if let (Some([start, _]), Some([_, end])) = (*range, expr.range()) {
+                    *range = Some([start, end]);
+                }
+                // The merged nodes are adjacent, so their sources add up.
+                if let (Some(source), Some(next)) = (&mut meta.source, &expr.meta().source) {
+                    source.push_str(next);
+                }
+            }
+            _ => out.push(AstExpr::Text {
+                range: expr.range(),
+                meta: expr.meta().clone(),
+                text: piece,
+            }),
+        }
+    }
+    out
+}
+
+pub fn convert_code<'a>(code: ast::Code<'a>, node: &LinkedNode<'a>, ctx: &Context) -> Vec<AstExpr> {
+    children(node, code.exprs())
+        .map(|(e, node)| convert_expr(e, &node, ctx))
+        .collect()
+}
+
+pub fn convert_math<'a>(math: ast::Math<'a>, node: &LinkedNode<'a>, ctx: &Context) -> Vec<AstExpr> {
+    convert_hash_prefixed(math.exprs(), node, ctx)
+}
+
+/// Converts the expressions of a markup or math node, flagging the ones that
+/// follow a `#` to embed code.
+fn convert_hash_prefixed<'a>(
+    exprs: impl Iterator<Item = ast::Expr<'a>>,
+    parent: &LinkedNode<'a>,
+    ctx: &Context,
+) -> Vec<AstExpr> {
+    children(parent, exprs)
+        .map(|(e, node)| {
+            let mut expr = convert_expr(e, &node, ctx);
+            let siblings = parent.get().children().as_slice();
+            if node
+                .index()
+                .checked_sub(1)
+                .is_some_and(|i| siblings[i].kind() == SyntaxKind::Hash)
+            {
+                expr.meta_mut().hash_prefixed = true;
+            }
+            expr
+        })
+        .collect()
+}
+
+/// Converts `expr`, whose linked node is `node`.
+pub fn convert_expr<'a>(expr: ast::Expr<'a>, node: &LinkedNode<'a>, ctx: &Context) -> AstExpr {
+    let depth = ctx.depth.get();
+    ctx.depth.set(depth + 1);
+    let mut converted = convert_expr_at_depth(expr, node, ctx);
+    ctx.depth.set(depth);
+    if ctx.options.node_depth {
+        converted.meta_mut().node_depth = Some(depth);
+    }
+    converted
+}
+
+/// Converts `expr` while `ctx.depth` already counts it, so that its children
+/// end up one level deeper.
+fn convert_expr_at_depth<'a>(
+    expr: ast::Expr<'a>,
+    linked: &LinkedNode<'a>,
+    ctx: &Context,
+) -> AstExpr {
+    if ctx.too_deep(linked) || ctx.beyond_frontier(linked, false) {
+        return ctx.truncated(linked);
+    }
     let node = expr.to_untyped();
-    let range = range_of(node, offsets);
+    let range = ctx.range(linked);
+    let meta = ctx.meta(linked);
+    let sub = |e: ast::Expr<'a>| convert_expr(e, &child(linked, e.to_untyped()), ctx);
+    let boxed = |e: ast::Expr<'a>| Box::new(sub(e));
+    let markup = |m: ast::Markup<'a>| convert_markup(m, &child(linked, m.to_untyped()), ctx);
+    let math = |m: ast::Math<'a>| convert_math(m, &child(linked, m.to_untyped()), ctx);
 
     match expr {
+        ast::Expr::Text(v) if ctx.options.trim_text => {
+            let (text, range) = trim_text(v.get(), ctx.source_range(linked));
+            AstExpr::Text {
+                range: ctx.map(range),
+                meta,
+                text,
+            }
+        }
         ast::Expr::Text(v) => AstExpr::Text {
             range,
+            meta,
             text: v.get().to_string(),
         },
-        ast::Expr::Space(_) => AstExpr::Space { range },
-        ast::Expr::Linebreak(_) => AstExpr::Linebreak { range },
-        ast::Expr::Parbreak(_) => AstExpr::Parbreak { range },
+        ast::Expr::Space(_) => AstExpr::Space { range, meta },
+        ast::Expr::Linebreak(_) => AstExpr::Linebreak { range, meta },
+        ast::Expr::Parbreak(_) => AstExpr::Parbreak {
+            range,
+            meta,
+            blank_lines: node.text().matches('\n').count().saturating_sub(1),
+        },
         ast::Expr::Escape(v) => AstExpr::Escape {
             range,
+            meta,
             character: v.get(),
         },
         ast::Expr::Shorthand(v) => AstExpr::Shorthand {
             range,
+            meta,
             character: v.get(),
         },
         ast::Expr::SmartQuote(v) => AstExpr::SmartQuote {
             range,
+            meta,
             double: v.double(),
         },
         ast::Expr::Strong(v) => AstExpr::Strong {
             range,
-            body: convert_markup(v.body(), offsets),
+            meta,
+            body: markup(v.body()),
         },
         ast::Expr::Emph(v) => AstExpr::Emph {
             range,
-            body: convert_markup(v.body(), offsets),
-        },
-        ast::Expr::Raw(v) => AstExpr::Raw {
-            range,
-            lines: v.lines().map(|l| l.get().to_string()).collect(),
-            lang: v.lang().map(|l| l.get().to_string()),
-            block: v.block(),
+            meta,
+            body: markup(v.body()),
         },
+        ast::Expr::Raw(v) => {
+            let backticks = node
+                .children()
+                .find(|child| child.kind() == SyntaxKind::RawDelim)
+                .map_or(0, |delim| delim.len());
+            AstExpr::Raw {
+                range,
+                meta,
+                lines: v.lines().map(|l| l.get().to_string()).collect(),
+                lang: v.lang().map(|l| l.get().to_string()),
+                block: v.block(),
+                fenced: backticks >= 3,
+                backticks,
+                nested: nested_typst(v, linked, ctx).map(Box::new),
+            }
+        }
         ast::Expr::Link(v) => AstExpr::Link {
             range,
+            meta,
             url: v.get().to_string(),
+            body: None,
         },
         ast::Expr::Label(v) => AstExpr::Label {
             range,
+            meta,
             name: v.get().to_string(),
         },
         ast::Expr::Ref(v) => AstExpr::Ref {
             range,
+            meta,
             target: v.target().to_string(),
-            supplement: v.supplement().map(|s| convert_markup(s.body(), offsets)),
+            supplement: v.supplement().map(|s| content_body(s, linked, ctx)),
         },
         ast::Expr::Heading(v) => AstExpr::Heading {
             range,
+            meta,
             depth: NonZeroUsize::get(v.depth()),
-            body: convert_markup(v.body(), offsets),
+            effective_depth: effective_depth(linked, NonZeroUsize::get(v.depth()), ctx),
+            syntax: HeadingSyntax::Markup,
+            body: markup(v.body()),
         },
         ast::Expr::ListItem(v) => AstExpr::ListItem {
             range,
-            body: convert_markup(v.body(), offsets),
+            meta,
+            body: markup(v.body()),
         },
         ast::Expr::EnumItem(v) => AstExpr::EnumItem {
             range,
+            meta,
             number: v.number(),
-            body: convert_markup(v.body(), offsets),
-        },
-        ast::Expr::TermItem(v) => AstExpr::TermItem {
-            range,
-            term: convert_markup(v.term(), offsets),
-            description: convert_markup(v.description(), offsets),
+            body: markup(v.body()),
         },
+        ast::Expr::TermItem(v) => {
+            let term = markup(v.term());
+            let description = markup(v.description());
+            AstExpr::TermItem {
+                range,
+                meta,
+                term_text: extract_text(&term).trim().to_string(),
+                description_text: extract_text(&description).trim().to_string(),
+                term,
+                description,
+            }
+        }
         ast::Expr::Equation(v) => AstExpr::Equation {
             range,
-            body: convert_math(v.body(), offsets),
+            meta,
+            body: math(v.body()),
             block: v.block(),
+            label: trailing_label(linked),
         },
 
         // Math
         ast::Expr::MathText(v) => AstExpr::MathText {
             range,
+            meta,
             text: convert_math_text_kind(v.get()),
         },
         ast::Expr::MathIdent(v) => AstExpr::MathIdent {
             range,
+            meta,
             name: v.get().to_string(),
+            role: builtins::math_ident_role(v.get()),
         },
         ast::Expr::MathShorthand(v) => AstExpr::MathShorthand {
             range,
+            meta,
             character: v.get(),
         },
-        ast::Expr::MathAlignPoint(_) => AstExpr::MathAlignPoint { range },
+        ast::Expr::MathAlignPoint(_) => AstExpr::MathAlignPoint { range, meta },
         ast::Expr::MathDelimited(v) => AstExpr::MathDelimited {
             range,
-            open: Box::new(convert_expr(v.open(), offsets)),
-            body: convert_math(v.body(), offsets),
-            close: Box::new(convert_expr(v.close(), offsets)),
+            meta,
+            delimiter: math_delimiter(v),
+            sized: is_lr_argument(linked),
+            open: boxed(v.open()),
+            body: math(v.body()),
+            close: boxed(v.close()),
         },
         ast::Expr::MathAttach(v) => AstExpr::MathAttach {
             range,
-            base: Box::new(convert_expr(v.base(), offsets)),
-            bottom: v.bottom().map(|e| Box::new(convert_expr(e, offsets))),
-            top: v.top().map(|e| Box::new(convert_expr(e, offsets))),
+            meta,
+            base: boxed(v.base()),
+            bottom: v.bottom().map(boxed),
+            top: v.top().map(boxed),
             primes: v.primes().map(|p| p.count()),
         },
         ast::Expr::MathPrimes(v) => AstExpr::MathPrimes {
             range,
+            meta,
             count: v.count(),
         },
         ast::Expr::MathFrac(v) => AstExpr::MathFrac {
             range,
-            num: Box::new(convert_expr(v.num(), offsets)),
-            denom: Box::new(convert_expr(v.denom(), offsets)),
+            meta,
+            num: boxed(v.num()),
+            denom: boxed(v.denom()),
         },
         ast::Expr::MathRoot(v) => AstExpr::MathRoot {
             range,
+            meta,
             index: v.index(),
-            radicand: Box::new(convert_expr(v.radicand(), offsets)),
+            radicand: boxed(v.radicand()),
         },
         ast::Expr::Math(v) => AstExpr::Math {
             range,
-            body: convert_math(v, offsets),
+            meta,
+            body: convert_math(v, linked, ctx),
         },
 
         // Literals
         ast::Expr::Ident(v) => AstExpr::Ident {
             range,
+            meta,
             name: v.get().to_string(),
         },
-        ast::Expr::None(_) => AstExpr::None { range },
-        ast::Expr::Auto(_) => AstExpr::Auto { range },
+        ast::Expr::None(_) => AstExpr::None { range, meta },
+        ast::Expr::Auto(_) => AstExpr::Auto { range, meta },
         ast::Expr::Bool(v) => AstExpr::Bool {
             range,
+            meta,
             value: v.get(),
         },
         ast::Expr::Int(v) => AstExpr::Int {
             range,
+            meta,
             value: v.get(),
+            literal: literal_text(node, ctx),
         },
         ast::Expr::Float(v) => AstExpr::Float {
             range,
+            meta,
             value: v.get(),
+            literal: literal_text(node, ctx),
         },
         ast::Expr::Numeric(v) => {
             let (value, unit) = v.get();
             AstExpr::Numeric {
                 range,
+                meta,
                 value,
                 unit: convert_unit(unit),
+                literal: literal_text(node, ctx),
             }
         }
         ast::Expr::Str(v) => AstExpr::Str {
             range,
+            meta,
             value: v.get().to_string(),
+            raw: ctx.options.string_raw.then(|| node.text().to_string()),
         },
 
         // Code structures
         ast::Expr::CodeBlock(v) => AstExpr::CodeBlock {
             range,
-            body: convert_code(v.body(), offsets),
+            meta,
+            body: convert_code(v.body(), &child(linked, v.body().to_untyped()), ctx),
         },
         ast::Expr::ContentBlock(v) => AstExpr::ContentBlock {
             range,
-            body: convert_markup(v.body(), offsets),
+            meta,
+            body: markup(v.body()),
         },
         ast::Expr::Parenthesized(v) => AstExpr::Parenthesized {
             range,
-            expr: Box::new(convert_expr(v.expr(), offsets)),
+            meta,
+            expr: boxed(v.expr()),
         },
         ast::Expr::Array(v) => AstExpr::Array {
             range,
-            items: v.items().map(|i| convert_array_item(i, offsets)).collect(),
+            meta,
+            items: children(linked, v.items())
+                .map(|(i, node)| convert_array_item(i, &node, ctx))
+                .collect(),
+            trailing_comma: has_trailing_comma(node),
         },
         ast::Expr::Dict(v) => AstExpr::Dict {
             range,
-            items: v.items().map(|i| convert_dict_item(i, offsets)).collect(),
+            meta,
+            items: children(linked, v.items())
+                .map(|(i, node)| convert_dict_item(i, &node, ctx))
+                .collect(),
+            trailing_comma: has_trailing_comma(node),
         },
 
         // Operations
         ast::Expr::Unary(v) => AstExpr::Unary {
             range,
+            meta,
             op: convert_unop(v.op()),
-            expr: Box::new(convert_expr(v.expr(), offsets)),
-        },
-        ast::Expr::Binary(v) => AstExpr::Binary {
-            range,
-            op: convert_binop(v.op()),
-            lhs: Box::new(convert_expr(v.lhs(), offsets)),
-            rhs: Box::new(convert_expr(v.rhs(), offsets)),
-        },
+            expr: boxed(v.expr()),
+        },
+        ast::Expr::Binary(v) => {
+            let lhs = sub(v.lhs());
+            let rhs = sub(v.rhs());
+            let folded = ctx
+                .options
+                .fold_constants
+                .then(|| fold_binary(v.op(), &lhs, &rhs, range, &meta))
+                .flatten();
+            folded.unwrap_or_else(|| {
+                let (lhs_gap, rhs_gap) = ctx
+                    .options
+                    .operator_gaps
+                    .then(|| operator_gaps(linked, ctx))
+                    .flatten()
+                    .unzip();
+                AstExpr::Binary {
+                    range,
+                    meta,
+                    op: convert_binop(v.op()),
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                    lhs_gap,
+                    rhs_gap,
+                }
+            })
+        }
         ast::Expr::FieldAccess(v) => AstExpr::FieldAccess {
             range,
-            target: Box::new(convert_expr(v.target(), offsets)),
+            meta,
+            target: boxed(v.target()),
             field: v.field().get().to_string(),
         },
-        ast::Expr::FuncCall(v) => AstExpr::FuncCall {
-            range,
-            callee: Box::new(convert_expr(v.callee(), offsets)),
-            args: v.args().items().map(|a| convert_arg(a, offsets)).collect(),
-        },
+        ast::Expr::FuncCall(v) => {
+            if let Some((url, body)) = link_call(v).filter(|_| ctx.options.element_calls) {
+                AstExpr::Link {
+                    range,
+                    meta,
+                    url,
+                    body: Some(content_body(
+                        body,
+                        &child(linked, v.args().to_untyped()),
+                        ctx,
+                    )),
+                }
+            } else if let Some((depth, absolute, body)) =
+                heading_call(v).filter(|_| ctx.options.element_calls)
+            {
+                AstExpr::Heading {
+                    range,
+                    meta,
+                    depth,
+                    // An explicit `level` is not shifted by the offset.
+                    effective_depth: if absolute {
+                        Some(depth)
+                    } else {
+                        effective_depth(linked, depth, ctx)
+                    },
+                    syntax: HeadingSyntax::Func,
+                    body: content_body(body, &child(linked, v.args().to_untyped()), ctx),
+                }
+            } else {
+                AstExpr::FuncCall {
+                    range,
+                    meta,
+                    callee: boxed(v.callee()),
+                    args: convert_args(v.args(), linked, ctx),
+                }
+            }
+        }
         ast::Expr::Closure(v) => AstExpr::Closure {
             range,
+            meta,
             name: v.name().map(|n| n.get().to_string()),
-            params: v
-                .params()
-                .children()
-                .map(|p| convert_param(p, offsets))
-                .collect(),
-            body: Box::new(convert_expr(v.body(), offsets)),
+            params: children(
+                &child(linked, v.params().to_untyped()),
+                v.params().children(),
+            )
+            .map(|(p, node)| convert_param(p, &node, ctx))
+            .collect(),
+            body: boxed(v.body()),
         },
 
         // Bindings
         ast::Expr::LetBinding(v) => AstExpr::LetBinding {
-            range,
-            binding_kind: convert_let_binding_kind(v.kind(), offsets),
-            init: v.init().map(|e| Box::new(convert_expr(e, offsets))),
+            range: if ctx.options.include_comments_in_ranges {
+                ctx.map(with_leading_comments(linked, ctx))
+            } else {
+                range
+            },
+            meta,
+            binding_kind: convert_let_binding_kind(v.kind(), linked, ctx),
+            init: v.init().map(boxed),
         },
         ast::Expr::DestructAssignment(v) => AstExpr::DestructAssignment {
             range,
-            pattern: convert_pattern(v.pattern(), offsets),
-            value: Box::new(convert_expr(v.value(), offsets)),
+            meta,
+            pattern: convert_pattern(v.pattern(), &child(linked, v.pattern().to_untyped()), ctx),
+            pattern_kind: pattern_kind(v.pattern()),
+            value: boxed(v.value()),
         },
 
         // Rules
         ast::Expr::SetRule(v) => AstExpr::SetRule {
             range,
-            target: Box::new(convert_expr(v.target(), offsets)),
-            args: v.args().items().map(|a| convert_arg(a, offsets)).collect(),
-            condition: v.condition().map(|e| Box::new(convert_expr(e, offsets))),
+            meta,
+            target: boxed(v.target()),
+            args: convert_args(v.args(), linked, ctx),
+            condition: v.condition().map(boxed),
+            is_known_element: known_element(Some(v.target())),
+            scope: rule_scope(linked),
         },
         ast::Expr::ShowRule(v) => AstExpr::ShowRule {
             range,
-            selector: v.selector().map(|e| Box::new(convert_expr(e, offsets))),
-            transform: Box::new(convert_expr(v.transform(), offsets)),
+            meta,
+            selector: v.selector().map(boxed),
+            transform: boxed(v.transform()),
+            is_known_element: known_element(v.selector()),
+            scope: rule_scope(linked),
         },
         ast::Expr::Contextual(v) => AstExpr::Contextual {
             range,
-            body: Box::new(convert_expr(v.body(), offsets)),
+            meta,
+            body: boxed(v.body()),
         },
 
         // Control flow
         ast::Expr::Conditional(v) => AstExpr::Conditional {
             range,
-            condition: Box::new(convert_expr(v.condition(), offsets)),
-            if_body: Box::new(convert_expr(v.if_body(), offsets)),
-            else_body: v.else_body().map(|e| Box::new(convert_expr(e, offsets))),
+            meta,
+            condition: boxed(v.condition()),
+            if_body: boxed(v.if_body()),
+            else_body: v.else_body().map(boxed),
+            if_keyword_range: keyword_range(linked, SyntaxKind::If, ctx),
+            else_keyword_range: keyword_range(linked, SyntaxKind::Else, ctx),
+            if_body_kind: body_kind(v.if_body()),
+            else_body_kind: v.else_body().map(body_kind),
         },
         ast::Expr::WhileLoop(v) => AstExpr::WhileLoop {
             range,
-            condition: Box::new(convert_expr(v.condition(), offsets)),
-            body: Box::new(convert_expr(v.body(), offsets)),
+            meta,
+            condition: boxed(v.condition()),
+            body: boxed(v.body()),
+            body_kind: body_kind(v.body()),
         },
         ast::Expr::ForLoop(v) => AstExpr::ForLoop {
             range,
-            pattern: convert_pattern(v.pattern(), offsets),
-            iterable: Box::new(convert_expr(v.iterable(), offsets)),
-            body: Box::new(convert_expr(v.body(), offsets)),
+            meta,
+            pattern: convert_pattern(v.pattern(), &child(linked, v.pattern().to_untyped()), ctx),
+            pattern_kind: pattern_kind(v.pattern()),
+            iterable: boxed(v.iterable()),
+            body: boxed(v.body()),
+            body_kind: body_kind(v.body()),
         },
 
         // Module
         ast::Expr::ModuleImport(v) => AstExpr::ModuleImport {
             range,
-            source: Box::new(convert_expr(v.source(), offsets)),
+            meta,
+            source: boxed(v.source()),
             new_name: v.new_name().map(|n| n.get().to_string()),
-            imports: v.imports().map(|i| convert_imports(i)),
+            new_name_range: v
+                .new_name()
+                .and_then(|n| ctx.range(&child(linked, n.to_untyped()))),
+            imports: v.imports().map(|i| convert_imports(i, linked, ctx)),
         },
         ast::Expr::ModuleInclude(v) => AstExpr::ModuleInclude {
             range,
-            source: Box::new(convert_expr(v.source(), offsets)),
+            meta,
+            source: boxed(v.source()),
         },
 
         // Jump
-        ast::Expr::LoopBreak(_) => AstExpr::LoopBreak { range },
-        ast::Expr::LoopContinue(_) => AstExpr::LoopContinue { range },
+        ast::Expr::LoopBreak(_) => AstExpr::LoopBreak { range, meta },
+        ast::Expr::LoopContinue(_) => AstExpr::LoopContinue { range, meta },
         ast::Expr::FuncReturn(v) => AstExpr::FuncReturn {
             range,
-            body: v.body().map(|e| Box::new(convert_expr(e, offsets))),
+            meta,
+            keyword_range: keyword_range(linked, SyntaxKind::Return, ctx),
+            body: v.body().map(boxed),
+        },
+
+        // Only reachable once `typst-syntax` grows a variant we don't handle.
+        #[cfg(feature = "forward_compat")]
+        #[allow(unreachable_patterns)]
+        _ => AstExpr::Unknown {
+            range,
+            meta,
+            syntax_kind: format!("{:?}", node.kind()),
         },
     }
 }
 
+/// Folds arithmetic over two literals of the same type into a single literal
+/// spanning the whole binary expression. Integer overflow, int/float mixing,
+/// integer division, differing units, and non-finite results are left alone.
+fn fold_binary(
+    op: ast::BinOp,
+    lhs: &AstExpr,
+    rhs: &AstExpr,
+    range: Range,
+    meta: &NodeMeta,
+) -> Option<AstExpr> {
+    use ast::BinOp::{Add, Div, Mul, Sub};
+
+    match (peel_parens(lhs), peel_parens(rhs)) {
+        (AstExpr::Int { value: a, .. }, AstExpr::Int { value: b, .. }) => {
+            let value = match op {
+                Add => a.checked_add(*b),
+                Sub => a.checked_sub(*b),
+                Mul => a.checked_mul(*b),
+                _ => None,
+            }?;
+            Some(AstExpr::Int {
+                range,
+                meta: meta.clone(),
+                value,
+                literal: None,
+            })
+        }
+        (AstExpr::Float { value: a, .. }, AstExpr::Float { value: b, .. }) => {
+            let value = match op {
+                Add => a + b,
+                Sub => a - b,
+                Mul => a * b,
+                Div => a / b,
+                _ => return None,
+            };
+            value.is_finite().then(|| AstExpr::Float {
+                range,
+                meta: meta.clone(),
+                value,
+                literal: None,
+            })
+        }
+        (
+            AstExpr::Numeric {
+                value: a, unit: u, ..
+            },
+            AstExpr::Numeric {
+                value: b, unit: v, ..
+            },
+        ) if u == v => {
+            let value = match op {
+                Add => a + b,
+                Sub => a - b,
+                _ => return None,
+            };
+            value.is_finite().then(|| AstExpr::Numeric {
+                range,
+                meta: meta.clone(),
+                value,
+                unit: *u,
+                literal: None,
+            })
+        }
+        _ => None,
+    }
+}
+
+fn literal_text(node: &SyntaxNode, ctx: &Context) -> Option<String> {
+    ctx.options
+        .keep_literal_text
+        .then(|| node.text().to_string())
+}
+
+fn peel_parens(expr: &AstExpr) -> &AstExpr {
+    match expr {
+        AstExpr::Parenthesized { expr, .. } => peel_parens(expr),
+        _ => expr,
+    }
+}
+
+/// Whether the last token before the closing parenthesis is a comma.
+fn has_trailing_comma(node: &SyntaxNode) -> bool {
+    node.children()
+        .rev()
+        .filter(|c| !c.kind().is_trivia())
+        .nth(1)
+        .is_some_and(|c| c.kind() == SyntaxKind::Comma)
+}
+
+fn pattern_kind(pattern: ast::Pattern) -> PatternKind {
+    match pattern {
+        ast::Pattern::Normal(_) => PatternKind::Ident,
+        ast::Pattern::Destructuring(_) => PatternKind::Destructuring,
+        ast::Pattern::Placeholder(_) => PatternKind::Placeholder,
+        ast::Pattern::Parenthesized(_) => PatternKind::Paren,
+    }
+}
+
+fn body_kind(body: ast::Expr) -> BodyKind {
+    match body {
+        ast::Expr::ContentBlock(_) => BodyKind::Content,
+        ast::Expr::CodeBlock(_) => BodyKind::Code,
+        _ => BodyKind::Expr,
+    }
+}
+
+/// A rule nested in a content or code block only affects the rest of that
+/// block; anywhere else it applies to the rest of the document. Rules that are
+/// not part of the tree (placeholders) are treated as top-level.
+fn rule_scope(node: &LinkedNode) -> RuleScope {
+    let mut current = node.parent();
+    while let Some(node) = current {
+        if matches!(
+            node.kind(),
+            SyntaxKind::ContentBlock | SyntaxKind::CodeBlock
+        ) {
+            return RuleScope::Local;
+        }
+        current = node.parent();
+    }
+    RuleScope::Block
+}
+
+/// Extends the range of `node` backwards over the comments directly above it.
+/// Comments attach when each starts its own line and no blank line separates
+/// them from the node; in markup, the `#` before the node is skipped too.
+fn with_leading_comments(node: &LinkedNode, ctx: &Context) -> Range {
+    let range = ctx.source_range(node);
+    let Some(parent) = node.parent() else {
+        return range;
+    };
+    let siblings = &parent.get().children().as_slice()[..node.index()];
+
+    let mut start = node.offset();
+    let mut rest = siblings;
+    if let [init @ .., last] = rest
+        && last.kind() == SyntaxKind::Hash
+    {
+        start -= last.len();
+        rest = init;
+    }
+    let mut skipped = 0;
+    loop {
+        let (comment, gap, before) = match rest {
+            [init @ .., comment, gap]
+                if is_comment(comment.kind()) && is_single_line_break(gap) =>
+            {
+                (comment, gap, init)
+            }
+            _ => break,
+        };
+        let starts_line = match before.last() {
+            None => true,
+            Some(prev) => prev.kind().is_trivia() && prev.text().contains('\n'),
+        };
+        if !starts_line {
+            break;
+        }
+        skipped += gap.len() + comment.len();
+        rest = before;
+    }
+    if skipped == 0 {
+        return range;
+    }
+    start -= skipped;
+    range.map(|[_, end]| [start, end])
+}
+
+/// The label right after `node`, separated by at most spaces, which is the
+/// label Typst attaches to it.
+fn trailing_label(node: &LinkedNode) -> Option<String> {
+    let parent = node.parent()?;
+    let next = parent.get().children().as_slice()[node.index() + 1..]
+        .iter()
+        .find(|c| c.kind() != SyntaxKind::Space)?;
+    next.cast::<ast::Label>().map(|l| l.get().to_string())
+}
+
+/// How many levels of Typst raw blocks inside Typst raw blocks are parsed.
+const MAX_NESTING: usize = 4;
+
+/// Parses the content of a `typ`, `typst`, or `typc` raw block, with ranges
+/// pointing into the outer document.
+fn nested_typst(raw: ast::Raw, node: &LinkedNode, ctx: &Context) -> Option<AstParseResult> {
+    if !ctx.options.parse_nested_typst || ctx.nesting >= MAX_NESTING {
+        return None;
+    }
+    let mode = match raw.lang()?.get().as_str() {
+        "typ" | "typst" => ParseMode::Markup,
+        "typc" => ParseMode::Code,
+        _ => return None,
+    };
+
+    // Parse the source between the first and last line so that offsets in
+    // the nested tree are a fixed shift away from the outer ones.
+    let mut lines = children(node, raw.lines()).map(|(_, line)| line);
+    let first = lines.next()?;
+    let last = lines.last().unwrap_or_else(|| first.clone());
+    let [raw_start, _] = ctx.source_range(node)?;
+    let [start, _] = ctx.source_range(&first)?;
+    let [_, end] = ctx.source_range(&last)?;
+    let text = cst::node_text(node.get());
+    let content = &text[start - raw_start..end - raw_start];
+
+    let root = mode.parse(content);
+    let mapper = ctx.mapper.nested(start);
+    convert_tree(&root, &mode, ctx.options, &mapper, ctx.nesting + 1).ok()
+}
+
+fn is_comment(kind: SyntaxKind) -> bool {
+    matches!(kind, SyntaxKind::LineComment | SyntaxKind::BlockComment)
+}
+
+fn is_single_line_break(node: &SyntaxNode) -> bool {
+    node.kind() == SyntaxKind::Space && node.text().matches('\n').count() == 1
+}
+
+fn known_element(target: Option<ast::Expr>) -> Option<bool> {
+    match target {
+        Some(ast::Expr::Ident(ident)) => Some(builtins::is_known_element(ident.get())),
+        _ => None,
+    }
+}
+
+fn trim_text(text: &str, range: Range) -> (String, Range) {
+    let start_trimmed = text.trim_start_matches(is_trimmed);
+    let trimmed = start_trimmed.trim_end_matches(is_trimmed);
+    let leading = text.len() - start_trimmed.len();
+    let range = range.map(|[start, _]| [start + leading, start + leading + trimmed.len()]);
+    (trimmed.to_string(), range)
+}
+
+/// Whether `trim_text` removes `c`: breaking whitespace only, as the parser
+/// turns ASCII whitespace into `Space` nodes and a non-breaking space is
+/// content.
+fn is_trimmed(c: char) -> bool {
+    c.is_whitespace() && !matches!(c, '\u{a0}' | '\u{2007}' | '\u{202f}')
+}
+
+/// Matches `link("url")[body]` calls, which are represented as links with a
+/// display body with `ParseOptions::element_calls`. Any other shape of `link`
+/// call stays a plain function call.
+fn link_call(call: ast::FuncCall) -> Option<(String, ast::ContentBlock)> {
+    let ast::Expr::Ident(callee) = call.callee() else {
+        return None;
+    };
+    if callee.get() != "link" {
+        return None;
+    }
+
+    let mut args = call.args().items();
+    let (
+        Some(ast::Arg::Pos(ast::Expr::Str(url))),
+        Some(ast::Arg::Pos(ast::Expr::ContentBlock(body))),
+        None,
+    ) = (args.next(), args.next(), args.next())
+    else {
+        return None;
+    };
+    Some((url.get().to_string(), body))
+}
+
+/// Matches `heading[body]` calls, optionally with a literal `level` or
+/// `depth` argument, which are represented as headings with
+/// `ParseOptions::element_calls`. Calls with any other arguments stay plain
+/// function calls so no information is lost. The flag tells whether the
+/// depth is an absolute `level` rather than one relative to the offset.
+fn heading_call(call: ast::FuncCall) -> Option<(usize, bool, ast::ContentBlock)> {
+    let ast::Expr::Ident(callee) = call.callee() else {
+        return None;
+    };
+    if callee.get() != "heading" {
+        return None;
+    }
+
+    let mut depth = 1;
+    let mut absolute = false;
+    let mut body = None;
+    for arg in call.args().items() {
+        match arg {
+            ast::Arg::Named(named) if matches!(named.name().as_str(), "level" | "depth") => {
+                let ast::Expr::Int(value) = named.expr() else {
+                    return None;
+                };
+                depth = usize::try_from(value.get()).ok().filter(|&d| d > 0)?;
+                absolute = named.name().as_str() == "level";
+            }
+            ast::Arg::Pos(ast::Expr::ContentBlock(content)) if body.is_none() => {
+                body = Some(content);
+            }
+            _ => return None,
+        }
+    }
+    Some((depth, absolute, body?))
+}
+
+/// The `offset` of each top-level `set heading(..)` rule that sets one, in
+/// document order and keyed by where the rule starts. The offset is `None`
+/// when it isn't an integer literal or the rule is conditional.
+pub fn heading_offsets(root: &LinkedNode) -> Vec<(usize, Option<usize>)> {
+    root.children()
+        .filter_map(|child| {
+            let rule = child.cast::<ast::SetRule>()?;
+            let ast::Expr::Ident(target) = rule.target() else {
+                return None;
+            };
+            if target.get() != "heading" {
+                return None;
+            }
+            let offset = rule.args().items().find_map(|arg| match arg {
+                ast::Arg::Named(named) if named.name().as_str() == "offset" => Some(named.expr()),
+                _ => None,
+            })?;
+            let value = match offset {
+                ast::Expr::Int(value) if rule.condition().is_none() => {
+                    usize::try_from(value.get()).ok()
+                }
+                _ => None,
+            };
+            Some((child.offset(), value))
+        })
+        .collect()
+}
+
+/// Best-effort level of a heading once the nearest preceding top-level
+/// `set heading(offset: ..)` is applied to its relative `depth`. Rules inside
+/// blocks, show rules and imported styles are not considered; a dynamic offset
+/// gives `None`.
+fn effective_depth(node: &LinkedNode, depth: usize, ctx: &Context) -> Option<usize> {
+    let [start, _] = ctx.source_range(node)?;
+    match ctx.heading_offsets.partition_point(|(pos, _)| *pos < start) {
+        0 => Some(depth),
+        i => ctx.heading_offsets[i - 1].1.map(|offset| depth + offset),
+    }
+}
+
+/// The whitespace and comments between the operands of a binary expression
+/// and its operator, measured in the unit of emitted ranges.
+fn operator_gaps(node: &LinkedNode, ctx: &Context) -> Option<(usize, usize)> {
+    let [start, end] = ctx.source_range(node)?;
+    let [lhs, middle @ .., rhs] = node.get().children().as_slice() else {
+        return None;
+    };
+    let leading: usize = middle
+        .iter()
+        .take_while(|c| c.kind().is_trivia())
+        .map(SyntaxNode::len)
+        .sum();
+    let trailing: usize = middle
+        .iter()
+        .rev()
+        .take_while(|c| c.kind().is_trivia())
+        .map(SyntaxNode::len)
+        .sum();
+    let lhs_end = start + lhs.len();
+    let rhs_start = end - rhs.len();
+    let [lhs_end, op_start] = ctx.mapper.map([lhs_end, lhs_end + leading]);
+    let [op_end, rhs_start] = ctx.mapper.map([rhs_start - trailing, rhs_start]);
+    Some((op_start - lhs_end, rhs_start - op_end))
+}
+
+fn keyword_range(node: &LinkedNode, kind: SyntaxKind, ctx: &Context) -> Range {
+    node.children()
+        .find(|c| c.kind() == kind)
+        .and_then(|c| ctx.range(&c))
+}
+
+fn math_delimiter(delimited: ast::MathDelimited) -> MathDelimiter {
+    let open = delimited.open().to_untyped().text();
+    let close = delimited.close().to_untyped().text();
+    match (open.as_str(), close.as_str()) {
+        ("(", ")") => MathDelimiter::Paren,
+        ("[", "]") => MathDelimiter::Bracket,
+        ("{", "}") => MathDelimiter::Brace,
+        ("|", "|") | ("‖", "‖") => MathDelimiter::Bar,
+        _ => MathDelimiter::Custom,
+    }
+}
+
+/// Whether `node` is the body of a call to `lr` or `math.lr`, as opposed to
+/// named arguments like `size`.
+fn is_lr_argument(node: &LinkedNode) -> bool {
+    let Some(args) = node.parent() else {
+        return false;
+    };
+    let Some(call) = args.parent().and_then(|n| n.cast::<ast::FuncCall>()) else {
+        return false;
+    };
+    match call.callee() {
+        ast::Expr::MathIdent(ident) => ident.get() == "lr",
+        ast::Expr::Ident(ident) => ident.get() == "lr",
+        ast::Expr::FieldAccess(access) => access.field().get() == "lr",
+        _ => false,
+    }
+}
+
 fn convert_math_text_kind(kind: ast::MathTextKind) -> MathTextKind {
     match kind {
         ast::MathTextKind::Character(c) => MathTextKind::Character { value: c },
@@ -352,99 +1168,145 @@ fn convert_binop(op: ast::BinOp) -> BinOp {
     }
 }
 
-fn convert_array_item(item: ast::ArrayItem, offsets: &OffsetMap) -> ArrayItem {
+fn convert_array_item<'a>(
+    item: ast::ArrayItem<'a>,
+    node: &LinkedNode<'a>,
+    ctx: &Context,
+) -> ArrayItem {
     match item {
         ast::ArrayItem::Pos(e) => ArrayItem::Pos {
-            expr: convert_expr(e, offsets),
+            expr: convert_expr(e, node, ctx),
         },
         ast::ArrayItem::Spread(s) => ArrayItem::Spread {
-            expr: convert_expr(s.expr(), offsets),
+            spread_range: keyword_range(node, SyntaxKind::Dots, ctx),
+            expr: convert_expr(s.expr(), &child(node, s.expr().to_untyped()), ctx),
             sink_ident: s.sink_ident().map(|i| i.get().to_string()),
         },
     }
 }
 
-fn convert_dict_item(item: ast::DictItem, offsets: &OffsetMap) -> DictItem {
+fn convert_dict_item<'a>(
+    item: ast::DictItem<'a>,
+    node: &LinkedNode<'a>,
+    ctx: &Context,
+) -> DictItem {
     match item {
         ast::DictItem::Named(n) => DictItem::Named {
             name: n.name().get().to_string(),
-            expr: convert_expr(n.expr(), offsets),
+            expr: convert_expr(n.expr(), &child(node, n.expr().to_untyped()), ctx),
         },
         ast::DictItem::Keyed(k) => DictItem::Keyed {
-            key: convert_expr(k.key(), offsets),
-            expr: convert_expr(k.expr(), offsets),
+            key: convert_expr(k.key(), &child(node, k.key().to_untyped()), ctx),
+            expr: convert_expr(k.expr(), &child(node, k.expr().to_untyped()), ctx),
         },
         ast::DictItem::Spread(s) => DictItem::Spread {
-            expr: convert_expr(s.expr(), offsets),
+            spread_range: keyword_range(node, SyntaxKind::Dots, ctx),
+            expr: convert_expr(s.expr(), &child(node, s.expr().to_untyped()), ctx),
             sink_ident: s.sink_ident().map(|i| i.get().to_string()),
         },
     }
 }
 
-fn convert_arg(arg: ast::Arg, offsets: &OffsetMap) -> Arg {
+/// Converts the arguments of a call or set rule whose linked node is `parent`.
+fn convert_args<'a>(args: ast::Args<'a>, parent: &LinkedNode<'a>, ctx: &Context) -> Vec<Arg> {
+    children(&child(parent, args.to_untyped()), args.items())
+        .map(|(arg, node)| convert_arg(arg, &node, ctx))
+        .collect()
+}
+
+fn convert_arg<'a>(arg: ast::Arg<'a>, node: &LinkedNode<'a>, ctx: &Context) -> Arg {
     match arg {
         ast::Arg::Pos(e) => Arg::Pos {
-            expr: convert_expr(e, offsets),
+            expr: convert_expr(e, node, ctx),
         },
         ast::Arg::Named(n) => Arg::Named {
             name: n.name().get().to_string(),
-            expr: convert_expr(n.expr(), offsets),
+            expr: convert_expr(n.expr(), &child(node, n.expr().to_untyped()), ctx),
         },
         ast::Arg::Spread(s) => Arg::Spread {
-            expr: convert_expr(s.expr(), offsets),
+            spread_range: keyword_range(node, SyntaxKind::Dots, ctx),
+            expr: convert_expr(s.expr(), &child(node, s.expr().to_untyped()), ctx),
             sink_ident: s.sink_ident().map(|i| i.get().to_string()),
         },
     }
 }
 
-fn convert_param(param: ast::Param, offsets: &OffsetMap) -> Param {
+/// Converts the markup of `block`, a child of `parent`.
+fn content_body<'a>(
+    block: ast::ContentBlock<'a>,
+    parent: &LinkedNode<'a>,
+    ctx: &Context,
+) -> Vec<AstExpr> {
+    let node = child(parent, block.to_untyped());
+    convert_markup(block.body(), &child(&node, block.body().to_untyped()), ctx)
+}
+
+fn convert_param<'a>(param: ast::Param<'a>, node: &LinkedNode<'a>, ctx: &Context) -> Param {
     match param {
         ast::Param::Pos(p) => Param::Pos {
-            pattern: convert_pattern(p, offsets),
+            pattern: convert_pattern(p, node, ctx),
         },
         ast::Param::Named(n) => Param::Named {
             name: n.name().get().to_string(),
-            expr: convert_expr(n.expr(), offsets),
+            expr: convert_expr(n.expr(), &child(node, n.expr().to_untyped()), ctx),
         },
         ast::Param::Spread(s) => Param::Spread {
             sink_ident: s.sink_ident().map(|i| i.get().to_string()),
-            sink_expr: s.sink_expr().map(|e| convert_expr(e, offsets)),
+            sink_expr: s
+                .sink_expr()
+                .map(|e| convert_expr(e, &child(node, e.to_untyped()), ctx)),
         },
     }
 }
 
-fn convert_pattern(pattern: ast::Pattern, offsets: &OffsetMap) -> Pattern {
+/// Converts `pattern`, whose linked node is `node`.
+pub fn convert_pattern<'a>(
+    pattern: ast::Pattern<'a>,
+    node: &LinkedNode<'a>,
+    ctx: &Context,
+) -> Pattern {
+    // Destructuring patterns nest without passing through `convert_expr`.
+    let plain = matches!(pattern, ast::Pattern::Normal(_));
+    if ctx.too_deep(node) || ctx.beyond_frontier(node, !plain) {
+        return Pattern::Normal {
+            expr: Box::new(ctx.truncated(node)),
+        };
+    }
     match pattern {
         ast::Pattern::Normal(e) => Pattern::Normal {
-            expr: Box::new(convert_expr(e, offsets)),
+            expr: Box::new(convert_expr(e, node, ctx)),
         },
-        ast::Pattern::Placeholder(u) => Pattern::Placeholder {
-            range: range_of(u.to_untyped(), offsets),
+        ast::Pattern::Placeholder(_) => Pattern::Placeholder {
+            range: ctx.range(node),
         },
         ast::Pattern::Parenthesized(p) => Pattern::Parenthesized {
-            expr: Box::new(convert_expr(p.expr(), offsets)),
+            expr: Box::new(convert_expr(
+                p.expr(),
+                &child(node, p.expr().to_untyped()),
+                ctx,
+            )),
         },
         ast::Pattern::Destructuring(d) => Pattern::Destructuring {
-            range: range_of(d.to_untyped(), offsets),
-            items: d
-                .items()
-                .map(|i| convert_destructuring_item(i, offsets))
+            range: ctx.range(node),
+            items: children(node, d.items())
+                .map(|(i, node)| convert_destructuring_item(i, &node, ctx))
                 .collect(),
         },
     }
 }
 
-fn convert_destructuring_item(
-    item: ast::DestructuringItem,
-    offsets: &OffsetMap,
+fn convert_destructuring_item<'a>(
+    item: ast::DestructuringItem<'a>,
+    node: &LinkedNode<'a>,
+    ctx: &Context,
 ) -> DestructuringItem {
     match item {
         ast::DestructuringItem::Pattern(p) => DestructuringItem::Pattern {
-            pattern: convert_pattern(p, offsets),
+            pattern: convert_pattern(p, node, ctx),
         },
         ast::DestructuringItem::Named(n) => DestructuringItem::Named {
             name: n.name().get().to_string(),
-            pattern: convert_pattern(n.pattern(), offsets),
+            pattern: convert_pattern(n.pattern(), &child(node, n.pattern().to_untyped()), ctx),
         },
         ast::DestructuringItem::Spread(s) => DestructuringItem::Spread {
             sink_ident: s.sink_ident().map(|i| i.get().to_string()),
@@ -452,10 +1314,16 @@ fn convert_destructuring_item(
     }
 }
 
-fn convert_let_binding_kind(kind: ast::LetBindingKind, offsets: &OffsetMap) -> LetBindingKind {
+/// Converts the kind of the `let` binding whose linked node is `parent`.
+fn convert_let_binding_kind<'a>(
+    kind: ast::LetBindingKind<'a>,
+    parent: &LinkedNode<'a>,
+    ctx: &Context,
+) -> LetBindingKind {
     match kind {
         ast::LetBindingKind::Normal(p) => LetBindingKind::Normal {
-            pattern: convert_pattern(p, offsets),
+            pattern: convert_pattern(p, &child(parent, p.to_untyped()), ctx),
+            pattern_kind: pattern_kind(p),
         },
         ast::LetBindingKind::Closure(i) => LetBindingKind::Closure {
             name: i.get().to_string(),
@@ -463,25 +1331,49 @@ fn convert_let_binding_kind(kind: ast::LetBindingKind, offsets: &OffsetMap) -> L
     }
 }
 
-fn convert_imports(imports: ast::Imports) -> Imports {
+/// Converts the imports of the `import` whose linked node is `parent`.
+fn convert_imports<'a>(
+    imports: ast::Imports<'a>,
+    parent: &LinkedNode<'a>,
+    ctx: &Context,
+) -> Imports {
     match imports {
         ast::Imports::Wildcard => Imports::Wildcard,
-        ast::Imports::Items(items) => Imports::Items {
-            items: items.iter().map(convert_import_item).collect(),
-        },
+        ast::Imports::Items(items) => {
+            let node = child(parent, items.to_untyped());
+            Imports::Items {
+                items: items
+                    .iter()
+                    .map(|i| convert_import_item(i, &node, ctx))
+                    .collect(),
+            }
+        }
     }
 }
 
-fn convert_import_item(item: ast::ImportItem) -> ImportItem {
+/// Converts `item`, a child of `parent`.
+fn convert_import_item<'a>(
+    item: ast::ImportItem<'a>,
+    parent: &LinkedNode<'a>,
+    ctx: &Context,
+) -> ImportItem {
     match item {
         ast::ImportItem::Simple(path) => ImportItem::Simple {
             path: path.iter().map(|i| i.get().to_string()).collect(),
             name: path.name().get().to_string(),
         },
-        ast::ImportItem::Renamed(r) => ImportItem::Renamed {
-            path: r.path().iter().map(|i| i.get().to_string()).collect(),
-            original_name: r.original_name().get().to_string(),
-            new_name: r.new_name().get().to_string(),
-        },
+        ast::ImportItem::Renamed(r) => {
+            let node = child(parent, r.to_untyped());
+            ImportItem::Renamed {
+                path: r.path().iter().map(|i| i.get().to_string()).collect(),
+                original_name: r.original_name().get().to_string(),
+                original_name_range: ctx.range(&child(
+                    &child(&node, r.path().to_untyped()),
+                    r.original_name().to_untyped(),
+                )),
+                new_name: r.new_name().get().to_string(),
+                new_name_range: ctx.range(&child(&node, r.new_name().to_untyped())),
+            }
+        }
     }
 }
@@ -0,0 +1,284 @@
+//! Checks that the hand-written declarations in `src/types.ts`, which are
+//! published with the package, describe what the AST types serialize to.
+//!
+//! The Rust side is read from the declarations `ts-rs` generates, so nothing
+//! is written to disk. Both sides are reduced to their union members: string
+//! literals, or objects keyed by `kind` with their field names. Field types
+//! are not compared. The module is left out under `forward_compat`, whose
+//! `unknown` variant is not published.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use ts_rs::TS;
+
+use super::AstParseResult;
+use super::expr::AstExpr;
+use super::types::*;
+use crate::cst::ParseError;
+use crate::lint::Warning;
+
+/// A member of a union: a string literal, or an object with its fields
+/// (`?` marks optional ones).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Member {
+    Literal(String),
+    Object(BTreeSet<String>),
+}
+
+/// Fields the output layer adds to every node after serialization.
+const OUTPUT_FIELDS: &[&str] = &["kindId?"];
+
+struct Declarations {
+    types: HashMap<String, String>,
+}
+
+impl Declarations {
+    fn parse(source: &str) -> Self {
+        let source = strip_comments(source);
+        let mut types = HashMap::new();
+        let mut rest = source.as_str();
+        while let Some(start) = rest.find("export ") {
+            rest = &rest[start + "export ".len()..];
+            if let Some(decl) = rest.strip_prefix("interface ") {
+                let name = identifier(decl);
+                let body_start = decl.find('{').unwrap();
+                let end = body_start + matching(&decl[body_start..]);
+                let mut value = decl[body_start..end].to_string();
+                let heritage = decl[name.len()..body_start].trim();
+                if let Some(base) = heritage.strip_prefix("extends ") {
+                    value = format!("{base} & {value}");
+                }
+                types.insert(name.to_string(), value);
+                rest = &decl[end..];
+            } else if let Some(decl) = rest.strip_prefix("type ") {
+                let name = identifier(decl);
+                let value = &decl[decl.find('=').unwrap() + 1..];
+                let end = top_level(value, ';').next().unwrap().len();
+                types.insert(name.to_string(), value[..end].trim().to_string());
+                rest = &value[end..];
+            }
+        }
+        Self { types }
+    }
+
+    /// The members of the union `ty`, keyed by their `kind` field or literal.
+    fn members(&self, ty: &str) -> BTreeMap<String, Member> {
+        self.resolve(ty)
+            .into_iter()
+            .map(|member| match &member {
+                Member::Object(fields) => {
+                    let kind = fields
+                        .iter()
+                        .find_map(|field| field.strip_prefix("kind=").map(str::to_string))
+                        .unwrap_or_default();
+                    (kind, member)
+                }
+                Member::Literal(literal) => (literal.clone(), member),
+            })
+            .collect()
+    }
+
+    fn resolve(&self, ty: &str) -> Vec<Member> {
+        let ty = ty.trim().trim_start_matches('|').trim();
+        let parts: Vec<&str> = top_level(ty, '|').collect();
+        if parts.len() > 1 {
+            return parts
+                .into_iter()
+                .flat_map(|part| self.resolve(part))
+                .collect();
+        }
+        let parts: Vec<&str> = top_level(ty, '&').collect();
+        if parts.len() > 1 {
+            let mut members = vec![Member::Object(BTreeSet::new())];
+            for part in parts {
+                let resolved = self.resolve(part);
+                members = members
+                    .iter()
+                    .flat_map(|member| resolved.iter().map(move |other| merge(member, other)))
+                    .collect();
+            }
+            return members;
+        }
+        if let Some(inner) = ty.strip_prefix('(') {
+            return self.resolve(&inner[..inner.len() - 1]);
+        }
+        if ty.starts_with('{') {
+            return vec![Member::Object(fields(ty))];
+        }
+        if ty.starts_with('"') {
+            return vec![Member::Literal(ty.trim_matches('"').to_string())];
+        }
+        match self.types.get(ty) {
+            Some(value) => self.resolve(value),
+            None => panic!("`{ty}` is not declared"),
+        }
+    }
+}
+
+fn merge(a: &Member, b: &Member) -> Member {
+    match (a, b) {
+        (Member::Object(a), Member::Object(b)) => Member::Object(a | b),
+        _ => panic!("cannot intersect {a:?} and {b:?}"),
+    }
+}
+
+/// The fields of the object type `ty`; the `kind` field also records its
+/// value, as `kind=<value>`.
+fn fields(ty: &str) -> BTreeSet<String> {
+    let body = &ty[1..matching(ty) - 1];
+    let mut fields = BTreeSet::new();
+    for field in top_level(body, ';').flat_map(|field| top_level(field, ',')) {
+        let field = field.trim();
+        let Some((name, value)) = field.split_once(':') else {
+            continue;
+        };
+        let name = name.trim().trim_matches('"');
+        if name == "kind" {
+            fields.insert(format!("kind={}", value.trim().trim_matches('"')));
+        }
+        fields.insert(name.to_string());
+    }
+    fields
+}
+
+/// The parts of `text` split at `separator` outside of brackets and strings.
+fn top_level(text: &str, separator: char) -> impl Iterator<Item = &str> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut start = 0;
+    let mut parts = vec![];
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            '{' | '[' | '(' | '<' => depth += 1,
+            '}' | ']' | ')' | '>' => depth -= 1,
+            _ if c == separator && depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts.into_iter().filter(|part| !part.trim().is_empty())
+}
+
+/// The length of the bracketed group `text` starts with.
+fn matching(text: &str) -> usize {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' | '[' | '(' => depth += 1,
+            '}' | ']' | ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    panic!("unbalanced brackets in `{text}`")
+}
+
+fn identifier(text: &str) -> &str {
+    let end = text
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(text.len());
+    &text[..end]
+}
+
+fn strip_comments(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    loop {
+        let block = rest.find("/*");
+        let line = rest.find("//");
+        match (block, line) {
+            (Some(b), l) if l.is_none_or(|l| b < l) => {
+                out.push_str(&rest[..b]);
+                rest = &rest[b..];
+                rest = &rest[rest.find("*/").unwrap() + 2..];
+            }
+            (_, Some(l)) => {
+                out.push_str(&rest[..l]);
+                rest = &rest[l..];
+                rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+            }
+            _ => break,
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Compares the members of the Rust type `T` with the TypeScript type `name`.
+fn check<T: TS>(published: &Declarations, name: &str, errors: &mut Vec<String>) {
+    let generated = Declarations {
+        types: HashMap::new(),
+    };
+    let expected = generated.members(&strip_comments(&T::inline()));
+    let mut actual = published.members(name);
+    for member in actual.values_mut() {
+        if let Member::Object(fields) = member {
+            for field in OUTPUT_FIELDS {
+                fields.remove(*field);
+            }
+        }
+    }
+    for (kind, member) in &expected {
+        match actual.get(kind) {
+            None => errors.push(format!("{name}: `{kind}` is missing")),
+            Some(Member::Object(declared)) => {
+                let Member::Object(fields) = member else {
+                    unreachable!()
+                };
+                for field in fields.difference(declared) {
+                    errors.push(format!("{name}: `{kind}` lacks `{field}`"));
+                }
+                for field in declared.difference(fields) {
+                    errors.push(format!("{name}: `{kind}` has no `{field}`"));
+                }
+            }
+            Some(Member::Literal(_)) => {}
+        }
+    }
+    for kind in actual.keys().filter(|kind| !expected.contains_key(*kind)) {
+        errors.push(format!("{name}: `{kind}` is not produced"));
+    }
+}
+
+#[test]
+fn published_declarations_match_the_ast() {
+    let published = Declarations::parse(include_str!("../types.ts"));
+    let mut errors = vec![];
+    check::<AstExpr>(&published, "AstExpr", &mut errors);
+    check::<Arg>(&published, "AstArg", &mut errors);
+    check::<ArrayItem>(&published, "AstArrayItem", &mut errors);
+    check::<DictItem>(&published, "AstDictItem", &mut errors);
+    check::<Param>(&published, "AstParam", &mut errors);
+    check::<Pattern>(&published, "AstPattern", &mut errors);
+    check::<DestructuringItem>(&published, "AstDestructuringItem", &mut errors);
+    check::<LetBindingKind>(&published, "AstLetBindingKind", &mut errors);
+    check::<Imports>(&published, "AstImports", &mut errors);
+    check::<ImportItem>(&published, "AstImportItem", &mut errors);
+    check::<MathTextKind>(&published, "AstMathTextKind", &mut errors);
+    check::<MathIdentRole>(&published, "AstMathIdentRole", &mut errors);
+    check::<MathDelimiter>(&published, "AstMathDelimiter", &mut errors);
+    check::<HeadingSyntax>(&published, "AstHeadingSyntax", &mut errors);
+    check::<Unit>(&published, "AstUnit", &mut errors);
+    check::<UnOp>(&published, "AstUnOp", &mut errors);
+    check::<BinOp>(&published, "AstBinOp", &mut errors);
+    check::<PatternKind>(&published, "AstPatternKind", &mut errors);
+    check::<RuleScope>(&published, "AstRuleScope", &mut errors);
+    check::<BodyKind>(&published, "AstBodyKind", &mut errors);
+    check::<AstParseResult>(&published, "ParseAstResult", &mut errors);
+    check::<ParseError>(&published, "ParseError", &mut errors);
+    check::<Warning>(&published, "ParseWarning", &mut errors);
+    assert!(
+        errors.is_empty(),
+        "src/types.ts is out of date:\n{}",
+        errors.join("\n")
+    );
+}
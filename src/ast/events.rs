@@ -0,0 +1,248 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use typst_syntax::LinkedNode;
+use typst_syntax::ast;
+
+use super::convert::{Context, Frontier, Skipped, convert_expr, convert_pattern};
+use super::expr::{AstExpr, push_pattern};
+use super::offset::{Range, children};
+use super::root_exprs;
+use super::stable_id::StableIds;
+use crate::parse_mode::ParseMode;
+use crate::parse_options::ParseOptions;
+use crate::range_mapper::RangeMapper;
+
+pub enum AstEvent {
+    /// A node with children was entered. Its children follow, then [`AstEvent::End`].
+    Start { kind: &'static str, range: Range },
+    /// A node without children.
+    Leaf { kind: &'static str, range: Range },
+    /// The most recently started node was left.
+    End { kind: &'static str },
+}
+
+/// Walks the AST of `text` in pre/post order, calling `f` for every node.
+///
+/// The AST is never built: each expression is converted on its own, with
+/// the expressions below it left out until it has been emitted, so memory
+/// beyond the syntax tree is bounded by the nodes along the current path and
+/// their direct children. Expressions nested deeper than the default
+/// `max_depth` of [`ParseOptions`] come out as `truncated` leaves.
+///
+/// A node is a [`AstEvent::Leaf`] whenever it has no children, such as an
+/// empty heading.
+///
+/// ```
+/// use typst_ast::ParseMode;
+/// use typst_ast::ast::{AstEvent, ast_events};
+///
+/// let mut headings = 0;
+/// ast_events("= One\n== Two\n= \nText", ParseMode::Markup, &mut |event| {
+///     if let AstEvent::Start { kind: "heading", .. } | AstEvent::Leaf { kind: "heading", .. } =
+///         event
+///     {
+///         headings += 1;
+///     }
+/// })
+/// .unwrap();
+/// assert_eq!(headings, 3);
+/// ```
+pub fn ast_events(text: &str, mode: ParseMode, f: &mut impl FnMut(AstEvent)) -> Result<(), String> {
+    let root = mode.parse(text);
+    let options = ParseOptions::default();
+    let mapper = RangeMapper::new(&root, &options);
+    let linked = LinkedNode::new(&root);
+    let stable_ids = StableIds::default();
+    let walker = Walker {
+        root: &linked,
+        options: &options,
+        mapper: &mapper,
+        stable_ids: &stable_ids,
+    };
+
+    for (expr, node) in children(&linked, root_exprs(&root, &mode)?) {
+        walker.expr(expr, &node, f);
+    }
+    Ok(())
+}
+
+/// The stand-ins a [`Frontier`] left, by range and syntax kind.
+type Pending = HashMap<(Range, String), Vec<Skipped>>;
+
+struct Walker<'a, 'b> {
+    root: &'b LinkedNode<'a>,
+    options: &'b ParseOptions,
+    mapper: &'b RangeMapper,
+    stable_ids: &'b StableIds,
+}
+
+impl<'a> Walker<'a, '_> {
+    fn context<'c>(&'c self, frontier: &'c Frontier) -> Context<'c> {
+        Context {
+            options: self.options,
+            mapper: self.mapper,
+            stable_ids: self.stable_ids,
+            nesting: 0,
+            heading_offsets: &[],
+            depth: Cell::new(0),
+            source: None,
+            frontier: Some(frontier),
+        }
+    }
+
+    /// Converts `expr` without the expressions below it and emits it, then
+    /// does the same for those in its place.
+    fn expr(&self, expr: ast::Expr<'a>, node: &LinkedNode<'a>, f: &mut impl FnMut(AstEvent)) {
+        let frontier = Frontier::default();
+        let converted = convert_expr(expr, node, &self.context(&frontier));
+        let mut pending = pending(frontier);
+        self.emit(&converted, &mut pending, f);
+    }
+
+    /// Like [`expr`](Self::expr), for a pattern, which is not a node of its
+    /// own: only the expressions in it are emitted.
+    fn pattern(
+        &self,
+        pattern: ast::Pattern<'a>,
+        node: &LinkedNode<'a>,
+        f: &mut impl FnMut(AstEvent),
+    ) {
+        let frontier = Frontier::default();
+        let converted = convert_pattern(pattern, node, &self.context(&frontier));
+        let mut pending = pending(frontier);
+        let mut exprs = vec![];
+        push_pattern(&converted, &mut exprs);
+        for expr in exprs {
+            self.emit(expr, &mut pending, f);
+        }
+    }
+
+    fn emit(&self, expr: &AstExpr, pending: &mut Pending, f: &mut impl FnMut(AstEvent)) {
+        if let AstExpr::Truncated { syntax_kind, .. } = expr
+            && let Some(skipped) = pending
+                .get_mut(&(expr.range(), syntax_kind.clone()))
+                .and_then(Vec::pop)
+        {
+            self.resume(skipped, f);
+            return;
+        }
+
+        let children = expr.children();
+        let kind = expr.kind();
+        if children.is_empty() {
+            f(AstEvent::Leaf {
+                kind,
+                range: expr.range(),
+            });
+            return;
+        }
+
+        f(AstEvent::Start {
+            kind,
+            range: expr.range(),
+        });
+        for child in children {
+            self.emit(child, pending, f);
+        }
+        f(AstEvent::End { kind });
+    }
+
+    /// Converts and emits a node a [`Frontier`] left out.
+    fn resume(&self, skipped: Skipped, f: &mut impl FnMut(AstEvent)) {
+        let mut node = self.root.clone();
+        for index in skipped.path {
+            node = node.children().nth(index).unwrap_or(node);
+        }
+        if skipped.pattern
+            && let Some(pattern) = node.cast::<ast::Pattern>()
+        {
+            self.pattern(pattern, &node, f);
+        } else if let Some(expr) = node.cast::<ast::Expr>() {
+            self.expr(expr, &node, f);
+        }
+    }
+}
+
+fn pending(frontier: Frontier) -> Pending {
+    let mut pending = Pending::new();
+    for skipped in frontier.into_skipped() {
+        pending
+            .entry((skipped.range, skipped.syntax_kind.clone()))
+            .or_default()
+            .push(skipped);
+    }
+    pending
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::golden::CORPUS;
+    use crate::ast::make_ast_result;
+
+    fn trace(expr: &AstExpr, out: &mut Vec<String>) {
+        let children = expr.children();
+        if children.is_empty() {
+            out.push(format!("{} {:?}", expr.kind(), expr.range()));
+            return;
+        }
+        out.push(format!("+{} {:?}", expr.kind(), expr.range()));
+        for child in children {
+            trace(child, out);
+        }
+        out.push(format!("-{}", expr.kind()));
+    }
+
+    #[test]
+    fn emits_balanced_events() {
+        let mut trace = vec![];
+        ast_events("#f(1)", ParseMode::Markup, &mut |event| {
+            trace.push(match event {
+                AstEvent::Start { kind, .. } => format!("+{kind}"),
+                AstEvent::Leaf { kind, .. } => kind.to_string(),
+                AstEvent::End { kind } => format!("-{kind}"),
+            })
+        })
+        .unwrap();
+        assert_eq!(trace, ["+funcCall", "ident", "int", "-funcCall"]);
+    }
+
+    #[test]
+    fn matches_the_converted_ast() {
+        let extra = [
+            (
+                ParseMode::Code,
+                "let (a, (b, ..c), d: (e, _)) = x\nfor (k, v) in d { f(k)[#v] }",
+            ),
+            (
+                ParseMode::Code,
+                "let f((x, y), z: 1, ..rest) = x => y\n(a, b) = (1, 2)",
+            ),
+            (
+                ParseMode::Markup,
+                "#{\n  let x = ((1 + 2) * 3)\n  [= #x *a* _b_]\n}\n= ",
+            ),
+            (ParseMode::Math, "$ (a + b)/c^2 sqrt(x) lr(( a )) $"),
+        ];
+        for (mode, text) in CORPUS.iter().copied().chain(extra) {
+            let mut actual = vec![];
+            ast_events(text, mode, &mut |event| {
+                actual.push(match event {
+                    AstEvent::Start { kind, range } => format!("+{kind} {range:?}"),
+                    AstEvent::Leaf { kind, range } => format!("{kind} {range:?}"),
+                    AstEvent::End { kind } => format!("-{kind}"),
+                })
+            })
+            .unwrap();
+
+            let root = mode.parse(text);
+            let result = make_ast_result(&root, &mode, &ParseOptions::default()).unwrap();
+            let mut expected = vec![];
+            for expr in &result.root {
+                trace(expr, &mut expected);
+            }
+            assert_eq!(actual, expected, "{text}");
+        }
+    }
+}
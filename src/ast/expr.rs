@@ -1,117 +1,202 @@
 use serde::Serialize;
 
+use super::AstParseResult;
 use super::offset::Range;
 use super::types::*;
 
-#[derive(Serialize)]
-#[serde(tag = "kind", rename_all = "camelCase")]
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
 pub enum AstExpr {
     // Markup
     Text {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         text: String,
     },
     Space {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
     },
     Linebreak {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
     },
     Parbreak {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
+        /// The number of empty lines, e.g. 1 for `\n\n`.
+        blank_lines: usize,
     },
     Escape {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         character: char,
     },
     Shorthand {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         character: char,
     },
     SmartQuote {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         double: bool,
     },
     Strong {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         body: Vec<AstExpr>,
     },
     Emph {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         body: Vec<AstExpr>,
     },
     Raw {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         lines: Vec<String>,
         lang: Option<String>,
         block: bool,
+        /// Whether the text is fenced by three or more backticks, which
+        /// allows a language tag, even if it is on a single line.
+        fenced: bool,
+        /// The number of backticks on either side.
+        backticks: usize,
+        /// The parsed content of a `typ`/`typst`/`typc` block, only with
+        /// `ParseOptions::parse_nested_typst`.
+        nested: Option<Box<AstParseResult>>,
     },
     Link {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         url: String,
+        /// The display content of a `link("url")[body]` call, only with
+        /// `ParseOptions::element_calls`; `None` for a bare URL.
+        body: Option<Vec<AstExpr>>,
     },
     Label {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         name: String,
     },
     Ref {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         target: String,
         supplement: Option<Vec<AstExpr>>,
     },
     Heading {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         depth: usize,
+        /// `depth` adjusted by a preceding `set heading(offset: ..)`, if known.
+        /// An explicit `level` argument is absolute and used as is.
+        effective_depth: Option<usize>,
+        syntax: HeadingSyntax,
         body: Vec<AstExpr>,
     },
     ListItem {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         body: Vec<AstExpr>,
     },
     EnumItem {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
+        #[cfg_attr(test, ts(type = "number | null"))]
         number: Option<u64>,
         body: Vec<AstExpr>,
     },
     TermItem {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         term: Vec<AstExpr>,
         description: Vec<AstExpr>,
+        /// `term` flattened to plain text.
+        term_text: String,
+        /// `description` flattened to plain text.
+        description_text: String,
     },
     Equation {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         body: Vec<AstExpr>,
         block: bool,
+        /// The label attached right after the equation, if any.
+        label: Option<String>,
     },
 
     // Math
     Math {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         body: Vec<AstExpr>,
     },
     MathText {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         text: MathTextKind,
     },
     MathIdent {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         name: String,
+        role: MathIdentRole,
     },
     MathShorthand {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         character: char,
     },
     MathAlignPoint {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
     },
     MathDelimited {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
+        delimiter: MathDelimiter,
+        /// Whether the pair is the body of an explicit `lr(..)` call, as in
+        /// `lr((x), size: #150%)`.
+        sized: bool,
         open: Box<AstExpr>,
         body: Vec<AstExpr>,
         close: Box<AstExpr>,
     },
     MathAttach {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         base: Box<AstExpr>,
         bottom: Option<Box<AstExpr>>,
         top: Option<Box<AstExpr>>,
@@ -119,15 +204,21 @@ pub enum AstExpr {
     },
     MathPrimes {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         count: usize,
     },
     MathFrac {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         num: Box<AstExpr>,
         denom: Box<AstExpr>,
     },
     MathRoot {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         index: Option<u8>,
         radicand: Box<AstExpr>,
     },
@@ -135,82 +226,155 @@ pub enum AstExpr {
     // Literals
     Ident {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         name: String,
     },
     None {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
     },
     Auto {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
     },
     Bool {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         value: bool,
     },
     Int {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
+        /// A string only for unsafe integers with `big_int_as_string`.
+        #[cfg_attr(test, ts(type = "number | string"))]
         value: i64,
+        /// The literal as written, e.g. `0xff`; only with `keep_literal_text`,
+        /// and not on folded constants.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(test, ts(optional))]
+        literal: Option<String>,
     },
     Float {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
+        /// A string instead of a non-finite number with `safe_floats`.
+        #[cfg_attr(test, ts(type = "number | \"inf\" | \"-inf\" | \"nan\""))]
         value: f64,
+        /// The literal as written, e.g. `1e3`; only with `keep_literal_text`,
+        /// and not on folded constants.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(test, ts(optional))]
+        literal: Option<String>,
     },
     Numeric {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
+        /// A string instead of a non-finite number with `safe_floats`.
+        #[cfg_attr(test, ts(type = "number | \"inf\" | \"-inf\" | \"nan\""))]
         value: f64,
         unit: Unit,
+        /// The literal as written, e.g. `1.50pt`; only with `keep_literal_text`,
+        /// and not on folded constants.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(test, ts(optional))]
+        literal: Option<String>,
     },
     Str {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         value: String,
+        /// The literal as written, quotes and escapes included; only with
+        /// `string_raw`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(test, ts(optional))]
+        raw: Option<String>,
     },
 
     // Code structures
     CodeBlock {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         body: Vec<AstExpr>,
     },
     ContentBlock {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         body: Vec<AstExpr>,
     },
+    /// `(x)`. Note that `(x,)` is a one-element [`AstExpr::Array`] instead.
     Parenthesized {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         expr: Box<AstExpr>,
     },
     Array {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         items: Vec<ArrayItem>,
+        trailing_comma: bool,
     },
     Dict {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         items: Vec<DictItem>,
+        trailing_comma: bool,
     },
 
     // Operations
     Unary {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         op: UnOp,
         expr: Box<AstExpr>,
     },
     Binary {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         op: BinOp,
         lhs: Box<AstExpr>,
         rhs: Box<AstExpr>,
+        /// Distance from `lhs` to the operator, only with `operator_gaps`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(test, ts(optional))]
+        lhs_gap: Option<usize>,
+        /// Distance from the operator to `rhs`, only with `operator_gaps`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(test, ts(optional))]
+        rhs_gap: Option<usize>,
     },
     FieldAccess {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         target: Box<AstExpr>,
         field: String,
     },
     FuncCall {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         callee: Box<AstExpr>,
         args: Vec<Arg>,
     },
     Closure {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         name: Option<String>,
         params: Vec<Param>,
         body: Box<AstExpr>,
@@ -219,72 +383,802 @@ pub enum AstExpr {
     // Bindings
     LetBinding {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         binding_kind: LetBindingKind,
         init: Option<Box<AstExpr>>,
     },
     DestructAssignment {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         pattern: Pattern,
+        pattern_kind: PatternKind,
         value: Box<AstExpr>,
     },
 
     // Rules
     SetRule {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         target: Box<AstExpr>,
         args: Vec<Arg>,
         condition: Option<Box<AstExpr>>,
+        is_known_element: Option<bool>,
+        scope: RuleScope,
     },
     ShowRule {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         selector: Option<Box<AstExpr>>,
         transform: Box<AstExpr>,
+        is_known_element: Option<bool>,
+        scope: RuleScope,
     },
     Contextual {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         body: Box<AstExpr>,
     },
 
     // Control flow
     Conditional {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         condition: Box<AstExpr>,
         if_body: Box<AstExpr>,
         else_body: Option<Box<AstExpr>>,
+        if_keyword_range: Range,
+        else_keyword_range: Range,
+        if_body_kind: BodyKind,
+        else_body_kind: Option<BodyKind>,
     },
     WhileLoop {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         condition: Box<AstExpr>,
         body: Box<AstExpr>,
+        body_kind: BodyKind,
     },
     ForLoop {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         pattern: Pattern,
+        pattern_kind: PatternKind,
         iterable: Box<AstExpr>,
         body: Box<AstExpr>,
+        body_kind: BodyKind,
     },
 
     // Module
     ModuleImport {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         source: Box<AstExpr>,
         new_name: Option<String>,
+        new_name_range: Range,
         imports: Option<Imports>,
     },
     ModuleInclude {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
         source: Box<AstExpr>,
     },
 
     // Jump
     LoopBreak {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
     },
     LoopContinue {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
     },
     FuncReturn {
         range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
+        keyword_range: Range,
         body: Option<Box<AstExpr>>,
     },
+
+    /// An expression nested deeper than `ParseOptions::max_depth` in the
+    /// syntax tree, left out like the nodes the CST drops at that depth and
+    /// identified by the debug name of its `SyntaxKind`.
+    Truncated {
+        range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
+        syntax_kind: String,
+    },
+
+    /// An expression this crate does not know about yet, identified by the
+    /// debug name of its `SyntaxKind`.
+    #[cfg(feature = "forward_compat")]
+    Unknown {
+        range: Range,
+        #[serde(flatten)]
+        meta: NodeMeta,
+        syntax_kind: String,
+    },
+}
+
+impl AstExpr {
+    /// The serialized `kind` tag of this node, e.g. `"funcCall"`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AstExpr::Text { .. } => "text",
+            AstExpr::Space { .. } => "space",
+            AstExpr::Linebreak { .. } => "linebreak",
+            AstExpr::Parbreak { .. } => "parbreak",
+            AstExpr::Escape { .. } => "escape",
+            AstExpr::Shorthand { .. } => "shorthand",
+            AstExpr::SmartQuote { .. } => "smartQuote",
+            AstExpr::Strong { .. } => "strong",
+            AstExpr::Emph { .. } => "emph",
+            AstExpr::Raw { .. } => "raw",
+            AstExpr::Link { .. } => "link",
+            AstExpr::Label { .. } => "label",
+            AstExpr::Ref { .. } => "ref",
+            AstExpr::Heading { .. } => "heading",
+            AstExpr::ListItem { .. } => "listItem",
+            AstExpr::EnumItem { .. } => "enumItem",
+            AstExpr::TermItem { .. } => "termItem",
+            AstExpr::Equation { .. } => "equation",
+            AstExpr::Math { .. } => "math",
+            AstExpr::MathText { .. } => "mathText",
+            AstExpr::MathIdent { .. } => "mathIdent",
+            AstExpr::MathShorthand { .. } => "mathShorthand",
+            AstExpr::MathAlignPoint { .. } => "mathAlignPoint",
+            AstExpr::MathDelimited { .. } => "mathDelimited",
+            AstExpr::MathAttach { .. } => "mathAttach",
+            AstExpr::MathPrimes { .. } => "mathPrimes",
+            AstExpr::MathFrac { .. } => "mathFrac",
+            AstExpr::MathRoot { .. } => "mathRoot",
+            AstExpr::Ident { .. } => "ident",
+            AstExpr::None { .. } => "none",
+            AstExpr::Auto { .. } => "auto",
+            AstExpr::Bool { .. } => "bool",
+            AstExpr::Int { .. } => "int",
+            AstExpr::Float { .. } => "float",
+            AstExpr::Numeric { .. } => "numeric",
+            AstExpr::Str { .. } => "str",
+            AstExpr::CodeBlock { .. } => "codeBlock",
+            AstExpr::ContentBlock { .. } => "contentBlock",
+            AstExpr::Parenthesized { .. } => "parenthesized",
+            AstExpr::Array { .. } => "array",
+            AstExpr::Dict { .. } => "dict",
+            AstExpr::Unary { .. } => "unary",
+            AstExpr::Binary { .. } => "binary",
+            AstExpr::FieldAccess { .. } => "fieldAccess",
+            AstExpr::FuncCall { .. } => "funcCall",
+            AstExpr::Closure { .. } => "closure",
+            AstExpr::LetBinding { .. } => "letBinding",
+            AstExpr::DestructAssignment { .. } => "destructAssignment",
+            AstExpr::SetRule { .. } => "setRule",
+            AstExpr::ShowRule { .. } => "showRule",
+            AstExpr::Contextual { .. } => "contextual",
+            AstExpr::Conditional { .. } => "conditional",
+            AstExpr::WhileLoop { .. } => "whileLoop",
+            AstExpr::ForLoop { .. } => "forLoop",
+            AstExpr::ModuleImport { .. } => "moduleImport",
+            AstExpr::ModuleInclude { .. } => "moduleInclude",
+            AstExpr::LoopBreak { .. } => "loopBreak",
+            AstExpr::LoopContinue { .. } => "loopContinue",
+            AstExpr::FuncReturn { .. } => "funcReturn",
+            AstExpr::Truncated { .. } => "truncated",
+            #[cfg(feature = "forward_compat")]
+            AstExpr::Unknown { .. } => "unknown",
+        }
+    }
+
+    pub fn range(&self) -> Range {
+        match self {
+            AstExpr::Text { range, .. }
+            | AstExpr::Space { range, .. }
+            | AstExpr::Linebreak { range, .. }
+            | AstExpr::Parbreak { range, .. }
+            | AstExpr::Escape { range, .. }
+            | AstExpr::Shorthand { range, .. }
+            | AstExpr::SmartQuote { range, .. }
+            | AstExpr::Strong { range, .. }
+            | AstExpr::Emph { range, .. }
+            | AstExpr::Raw { range, .. }
+            | AstExpr::Link { range, .. }
+            | AstExpr::Label { range, .. }
+            | AstExpr::Ref { range, .. }
+            | AstExpr::Heading { range, .. }
+            | AstExpr::ListItem { range, .. }
+            | AstExpr::EnumItem { range, .. }
+            | AstExpr::TermItem { range, .. }
+            | AstExpr::Equation { range, .. }
+            | AstExpr::Math { range, .. }
+            | AstExpr::MathText { range, .. }
+            | AstExpr::MathIdent { range, .. }
+            | AstExpr::MathShorthand { range, .. }
+            | AstExpr::MathAlignPoint { range, .. }
+            | AstExpr::MathDelimited { range, .. }
+            | AstExpr::MathAttach { range, .. }
+            | AstExpr::MathPrimes { range, .. }
+            | AstExpr::MathFrac { range, .. }
+            | AstExpr::MathRoot { range, .. }
+            | AstExpr::Ident { range, .. }
+            | AstExpr::None { range, .. }
+            | AstExpr::Auto { range, .. }
+            | AstExpr::Bool { range, .. }
+            | AstExpr::Int { range, .. }
+            | AstExpr::Float { range, .. }
+            | AstExpr::Numeric { range, .. }
+            | AstExpr::Str { range, .. }
+            | AstExpr::CodeBlock { range, .. }
+            | AstExpr::ContentBlock { range, .. }
+            | AstExpr::Parenthesized { range, .. }
+            | AstExpr::Array { range, .. }
+            | AstExpr::Dict { range, .. }
+            | AstExpr::Unary { range, .. }
+            | AstExpr::Binary { range, .. }
+            | AstExpr::FieldAccess { range, .. }
+            | AstExpr::FuncCall { range, .. }
+            | AstExpr::Closure { range, .. }
+            | AstExpr::LetBinding { range, .. }
+            | AstExpr::DestructAssignment { range, .. }
+            | AstExpr::SetRule { range, .. }
+            | AstExpr::ShowRule { range, .. }
+            | AstExpr::Contextual { range, .. }
+            | AstExpr::Conditional { range, .. }
+            | AstExpr::WhileLoop { range, .. }
+            | AstExpr::ForLoop { range, .. }
+            | AstExpr::ModuleImport { range, .. }
+            | AstExpr::ModuleInclude { range, .. }
+            | AstExpr::LoopBreak { range, .. }
+            | AstExpr::LoopContinue { range, .. }
+            | AstExpr::FuncReturn { range, .. }
+            | AstExpr::Truncated { range, .. } => *range,
+            #[cfg(feature = "forward_compat")]
+            AstExpr::Unknown { range, .. } => *range,
+        }
+    }
+
+    /// The optional per-node data of this node.
+    pub fn meta(&self) -> &NodeMeta {
+        match self {
+            AstExpr::Text { meta, .. }
+            | AstExpr::Space { meta, .. }
+            | AstExpr::Linebreak { meta, .. }
+            | AstExpr::Parbreak { meta, .. }
+            | AstExpr::Escape { meta, .. }
+            | AstExpr::Shorthand { meta, .. }
+            | AstExpr::SmartQuote { meta, .. }
+            | AstExpr::Strong { meta, .. }
+            | AstExpr::Emph { meta, .. }
+            | AstExpr::Raw { meta, .. }
+            | AstExpr::Link { meta, .. }
+            | AstExpr::Label { meta, .. }
+            | AstExpr::Ref { meta, .. }
+            | AstExpr::Heading { meta, .. }
+            | AstExpr::ListItem { meta, .. }
+            | AstExpr::EnumItem { meta, .. }
+            | AstExpr::TermItem { meta, .. }
+            | AstExpr::Equation { meta, .. }
+            | AstExpr::Math { meta, .. }
+            | AstExpr::MathText { meta, .. }
+            | AstExpr::MathIdent { meta, .. }
+            | AstExpr::MathShorthand { meta, .. }
+            | AstExpr::MathAlignPoint { meta, .. }
+            | AstExpr::MathDelimited { meta, .. }
+            | AstExpr::MathAttach { meta, .. }
+            | AstExpr::MathPrimes { meta, .. }
+            | AstExpr::MathFrac { meta, .. }
+            | AstExpr::MathRoot { meta, .. }
+            | AstExpr::Ident { meta, .. }
+            | AstExpr::None { meta, .. }
+            | AstExpr::Auto { meta, .. }
+            | AstExpr::Bool { meta, .. }
+            | AstExpr::Int { meta, .. }
+            | AstExpr::Float { meta, .. }
+            | AstExpr::Numeric { meta, .. }
+            | AstExpr::Str { meta, .. }
+            | AstExpr::CodeBlock { meta, .. }
+            | AstExpr::ContentBlock { meta, .. }
+            | AstExpr::Parenthesized { meta, .. }
+            | AstExpr::Array { meta, .. }
+            | AstExpr::Dict { meta, .. }
+            | AstExpr::Unary { meta, .. }
+            | AstExpr::Binary { meta, .. }
+            | AstExpr::FieldAccess { meta, .. }
+            | AstExpr::FuncCall { meta, .. }
+            | AstExpr::Closure { meta, .. }
+            | AstExpr::LetBinding { meta, .. }
+            | AstExpr::DestructAssignment { meta, .. }
+            | AstExpr::SetRule { meta, .. }
+            | AstExpr::ShowRule { meta, .. }
+            | AstExpr::Contextual { meta, .. }
+            | AstExpr::Conditional { meta, .. }
+            | AstExpr::WhileLoop { meta, .. }
+            | AstExpr::ForLoop { meta, .. }
+            | AstExpr::ModuleImport { meta, .. }
+            | AstExpr::ModuleInclude { meta, .. }
+            | AstExpr::LoopBreak { meta, .. }
+            | AstExpr::LoopContinue { meta, .. }
+            | AstExpr::FuncReturn { meta, .. }
+            | AstExpr::Truncated { meta, .. } => meta,
+            #[cfg(feature = "forward_compat")]
+            AstExpr::Unknown { meta, .. } => meta,
+        }
+    }
+
+    pub fn meta_mut(&mut self) -> &mut NodeMeta {
+        match self {
+            AstExpr::Text { meta, .. }
+            | AstExpr::Space { meta, .. }
+            | AstExpr::Linebreak { meta, .. }
+            | AstExpr::Parbreak { meta, .. }
+            | AstExpr::Escape { meta, .. }
+            | AstExpr::Shorthand { meta, .. }
+            | AstExpr::SmartQuote { meta, .. }
+            | AstExpr::Strong { meta, .. }
+            | AstExpr::Emph { meta, .. }
+            | AstExpr::Raw { meta, .. }
+            | AstExpr::Link { meta, .. }
+            | AstExpr::Label { meta, .. }
+            | AstExpr::Ref { meta, .. }
+            | AstExpr::Heading { meta, .. }
+            | AstExpr::ListItem { meta, .. }
+            | AstExpr::EnumItem { meta, .. }
+            | AstExpr::TermItem { meta, .. }
+            | AstExpr::Equation { meta, .. }
+            | AstExpr::Math { meta, .. }
+            | AstExpr::MathText { meta, .. }
+            | AstExpr::MathIdent { meta, .. }
+            | AstExpr::MathShorthand { meta, .. }
+            | AstExpr::MathAlignPoint { meta, .. }
+            | AstExpr::MathDelimited { meta, .. }
+            | AstExpr::MathAttach { meta, .. }
+            | AstExpr::MathPrimes { meta, .. }
+            | AstExpr::MathFrac { meta, .. }
+            | AstExpr::MathRoot { meta, .. }
+            | AstExpr::Ident { meta, .. }
+            | AstExpr::None { meta, .. }
+            | AstExpr::Auto { meta, .. }
+            | AstExpr::Bool { meta, .. }
+            | AstExpr::Int { meta, .. }
+            | AstExpr::Float { meta, .. }
+            | AstExpr::Numeric { meta, .. }
+            | AstExpr::Str { meta, .. }
+            | AstExpr::CodeBlock { meta, .. }
+            | AstExpr::ContentBlock { meta, .. }
+            | AstExpr::Parenthesized { meta, .. }
+            | AstExpr::Array { meta, .. }
+            | AstExpr::Dict { meta, .. }
+            | AstExpr::Unary { meta, .. }
+            | AstExpr::Binary { meta, .. }
+            | AstExpr::FieldAccess { meta, .. }
+            | AstExpr::FuncCall { meta, .. }
+            | AstExpr::Closure { meta, .. }
+            | AstExpr::LetBinding { meta, .. }
+            | AstExpr::DestructAssignment { meta, .. }
+            | AstExpr::SetRule { meta, .. }
+            | AstExpr::ShowRule { meta, .. }
+            | AstExpr::Contextual { meta, .. }
+            | AstExpr::Conditional { meta, .. }
+            | AstExpr::WhileLoop { meta, .. }
+            | AstExpr::ForLoop { meta, .. }
+            | AstExpr::ModuleImport { meta, .. }
+            | AstExpr::ModuleInclude { meta, .. }
+            | AstExpr::LoopBreak { meta, .. }
+            | AstExpr::LoopContinue { meta, .. }
+            | AstExpr::FuncReturn { meta, .. }
+            | AstExpr::Truncated { meta, .. } => meta,
+            #[cfg(feature = "forward_compat")]
+            AstExpr::Unknown { meta, .. } => meta,
+        }
+    }
+
+    /// The direct child expressions of this node, in source order.
+    pub fn children(&self) -> Vec<&AstExpr> {
+        let mut out = Vec::new();
+        match self {
+            AstExpr::Text { .. }
+            | AstExpr::Space { .. }
+            | AstExpr::Linebreak { .. }
+            | AstExpr::Parbreak { .. }
+            | AstExpr::Escape { .. }
+            | AstExpr::Shorthand { .. }
+            | AstExpr::SmartQuote { .. }
+            | AstExpr::Raw { .. }
+            | AstExpr::Label { .. }
+            | AstExpr::MathText { .. }
+            | AstExpr::MathIdent { .. }
+            | AstExpr::MathShorthand { .. }
+            | AstExpr::MathAlignPoint { .. }
+            | AstExpr::MathPrimes { .. }
+            | AstExpr::Ident { .. }
+            | AstExpr::None { .. }
+            | AstExpr::Auto { .. }
+            | AstExpr::Bool { .. }
+            | AstExpr::Int { .. }
+            | AstExpr::Float { .. }
+            | AstExpr::Numeric { .. }
+            | AstExpr::Str { .. }
+            | AstExpr::LoopBreak { .. }
+            | AstExpr::LoopContinue { .. }
+            | AstExpr::Truncated { .. } => {}
+            #[cfg(feature = "forward_compat")]
+            AstExpr::Unknown { .. } => {}
+
+            AstExpr::Strong { body, .. }
+            | AstExpr::Emph { body, .. }
+            | AstExpr::Heading { body, .. }
+            | AstExpr::ListItem { body, .. }
+            | AstExpr::EnumItem { body, .. }
+            | AstExpr::Equation { body, .. }
+            | AstExpr::Math { body, .. }
+            | AstExpr::CodeBlock { body, .. }
+            | AstExpr::ContentBlock { body, .. } => out.extend(body),
+            AstExpr::Link { body, .. } => out.extend(body.iter().flatten()),
+            AstExpr::Ref { supplement, .. } => out.extend(supplement.iter().flatten()),
+            AstExpr::TermItem {
+                term, description, ..
+            } => {
+                out.extend(term);
+                out.extend(description);
+            }
+            AstExpr::MathDelimited {
+                open, body, close, ..
+            } => {
+                out.push(open);
+                out.extend(body);
+                out.push(close);
+            }
+            AstExpr::MathAttach {
+                base, bottom, top, ..
+            } => {
+                out.push(base);
+                out.extend(bottom.as_deref());
+                out.extend(top.as_deref());
+            }
+            AstExpr::MathFrac { num, denom, .. } => {
+                out.push(num);
+                out.push(denom);
+            }
+            AstExpr::MathRoot { radicand, .. } => out.push(radicand),
+            AstExpr::Parenthesized { expr, .. } | AstExpr::Unary { expr, .. } => out.push(expr),
+            AstExpr::Array { items, .. } => {
+                for item in items {
+                    match item {
+                        ArrayItem::Pos { expr } | ArrayItem::Spread { expr, .. } => out.push(expr),
+                    }
+                }
+            }
+            AstExpr::Dict { items, .. } => {
+                for item in items {
+                    match item {
+                        DictItem::Named { expr, .. } | DictItem::Spread { expr, .. } => {
+                            out.push(expr)
+                        }
+                        DictItem::Keyed { key, expr } => {
+                            out.push(key);
+                            out.push(expr);
+                        }
+                    }
+                }
+            }
+            AstExpr::Binary { lhs, rhs, .. } => {
+                out.push(lhs);
+                out.push(rhs);
+            }
+            AstExpr::FieldAccess { target, .. } => out.push(target),
+            AstExpr::FuncCall { callee, args, .. } => {
+                out.push(callee);
+                push_args(args, &mut out);
+            }
+            AstExpr::Closure { params, body, .. } => {
+                for param in params {
+                    match param {
+                        Param::Pos { pattern } => push_pattern(pattern, &mut out),
+                        Param::Named { expr, .. } => out.push(expr),
+                        Param::Spread { sink_expr, .. } => out.extend(sink_expr),
+                    }
+                }
+                out.push(body);
+            }
+            AstExpr::LetBinding {
+                binding_kind, init, ..
+            } => {
+                if let LetBindingKind::Normal { pattern, .. } = binding_kind {
+                    push_pattern(pattern, &mut out);
+                }
+                out.extend(init.as_deref());
+            }
+            AstExpr::DestructAssignment { pattern, value, .. } => {
+                push_pattern(pattern, &mut out);
+                out.push(value);
+            }
+            AstExpr::SetRule {
+                target,
+                args,
+                condition,
+                ..
+            } => {
+                out.push(target);
+                push_args(args, &mut out);
+                out.extend(condition.as_deref());
+            }
+            AstExpr::ShowRule {
+                selector,
+                transform,
+                ..
+            } => {
+                out.extend(selector.as_deref());
+                out.push(transform);
+            }
+            AstExpr::Contextual { body, .. } => out.push(body),
+            AstExpr::Conditional {
+                condition,
+                if_body,
+                else_body,
+                ..
+            } => {
+                out.push(condition);
+                out.push(if_body);
+                out.extend(else_body.as_deref());
+            }
+            AstExpr::WhileLoop {
+                condition, body, ..
+            } => {
+                out.push(condition);
+                out.push(body);
+            }
+            AstExpr::ForLoop {
+                pattern,
+                iterable,
+                body,
+                ..
+            } => {
+                push_pattern(pattern, &mut out);
+                out.push(iterable);
+                out.push(body);
+            }
+            AstExpr::ModuleImport { source, .. } | AstExpr::ModuleInclude { source, .. } => {
+                out.push(source)
+            }
+            AstExpr::FuncReturn { body, .. } => out.extend(body.as_deref()),
+        }
+        out
+    }
+
+    /// Mutable access to the expressions [`children`](Self::children) returns.
+    pub fn children_mut(&mut self) -> Vec<&mut AstExpr> {
+        let mut out = Vec::new();
+        match self {
+            AstExpr::Text { .. }
+            | AstExpr::Space { .. }
+            | AstExpr::Linebreak { .. }
+            | AstExpr::Parbreak { .. }
+            | AstExpr::Escape { .. }
+            | AstExpr::Shorthand { .. }
+            | AstExpr::SmartQuote { .. }
+            | AstExpr::Raw { .. }
+            | AstExpr::Label { .. }
+            | AstExpr::MathText { .. }
+            | AstExpr::MathIdent { .. }
+            | AstExpr::MathShorthand { .. }
+            | AstExpr::MathAlignPoint { .. }
+            | AstExpr::MathPrimes { .. }
+            | AstExpr::Ident { .. }
+            | AstExpr::None { .. }
+            | AstExpr::Auto { .. }
+            | AstExpr::Bool { .. }
+            | AstExpr::Int { .. }
+            | AstExpr::Float { .. }
+            | AstExpr::Numeric { .. }
+            | AstExpr::Str { .. }
+            | AstExpr::LoopBreak { .. }
+            | AstExpr::LoopContinue { .. }
+            | AstExpr::Truncated { .. } => {}
+            #[cfg(feature = "forward_compat")]
+            AstExpr::Unknown { .. } => {}
+
+            AstExpr::Strong { body, .. }
+            | AstExpr::Emph { body, .. }
+            | AstExpr::Heading { body, .. }
+            | AstExpr::ListItem { body, .. }
+            | AstExpr::EnumItem { body, .. }
+            | AstExpr::Equation { body, .. }
+            | AstExpr::Math { body, .. }
+            | AstExpr::CodeBlock { body, .. }
+            | AstExpr::ContentBlock { body, .. } => out.extend(body),
+            AstExpr::Link { body, .. } => out.extend(body.iter_mut().flatten()),
+            AstExpr::Ref { supplement, .. } => out.extend(supplement.iter_mut().flatten()),
+            AstExpr::TermItem {
+                term, description, ..
+            } => {
+                out.extend(term);
+                out.extend(description);
+            }
+            AstExpr::MathDelimited {
+                open, body, close, ..
+            } => {
+                out.push(open);
+                out.extend(body);
+                out.push(close);
+            }
+            AstExpr::MathAttach {
+                base, bottom, top, ..
+            } => {
+                out.push(base);
+                out.extend(bottom.as_deref_mut());
+                out.extend(top.as_deref_mut());
+            }
+            AstExpr::MathFrac { num, denom, .. } => {
+                out.push(num);
+                out.push(denom);
+            }
+            AstExpr::MathRoot { radicand, .. } => out.push(radicand),
+            AstExpr::Parenthesized { expr, .. } | AstExpr::Unary { expr, .. } => out.push(expr),
+            AstExpr::Array { items, .. } => {
+                for item in items {
+                    match item {
+                        ArrayItem::Pos { expr } | ArrayItem::Spread { expr, .. } => out.push(expr),
+                    }
+                }
+            }
+            AstExpr::Dict { items, .. } => {
+                for item in items {
+                    match item {
+                        DictItem::Named { expr, .. } | DictItem::Spread { expr, .. } => {
+                            out.push(expr)
+                        }
+                        DictItem::Keyed { key, expr } => {
+                            out.push(key);
+                            out.push(expr);
+                        }
+                    }
+                }
+            }
+            AstExpr::Binary { lhs, rhs, .. } => {
+                out.push(lhs);
+                out.push(rhs);
+            }
+            AstExpr::FieldAccess { target, .. } => out.push(target),
+            AstExpr::FuncCall { callee, args, .. } => {
+                out.push(callee);
+                push_args_mut(args, &mut out);
+            }
+            AstExpr::Closure { params, body, .. } => {
+                for param in params {
+                    match param {
+                        Param::Pos { pattern } => push_pattern_mut(pattern, &mut out),
+                        Param::Named { expr, .. } => out.push(expr),
+                        Param::Spread { sink_expr, .. } => out.extend(sink_expr),
+                    }
+                }
+                out.push(body);
+            }
+            AstExpr::LetBinding {
+                binding_kind, init, ..
+            } => {
+                if let LetBindingKind::Normal { pattern, .. } = binding_kind {
+                    push_pattern_mut(pattern, &mut out);
+                }
+                out.extend(init.as_deref_mut());
+            }
+            AstExpr::DestructAssignment { pattern, value, .. } => {
+                push_pattern_mut(pattern, &mut out);
+                out.push(value);
+            }
+            AstExpr::SetRule {
+                target,
+                args,
+                condition,
+                ..
+            } => {
+                out.push(target);
+                push_args_mut(args, &mut out);
+                out.extend(condition.as_deref_mut());
+            }
+            AstExpr::ShowRule {
+                selector,
+                transform,
+                ..
+            } => {
+                out.extend(selector.as_deref_mut());
+                out.push(transform);
+            }
+            AstExpr::Contextual { body, .. } => out.push(body),
+            AstExpr::Conditional {
+                condition,
+                if_body,
+                else_body,
+                ..
+            } => {
+                out.push(condition);
+                out.push(if_body);
+                out.extend(else_body.as_deref_mut());
+            }
+            AstExpr::WhileLoop {
+                condition, body, ..
+            } => {
+                out.push(condition);
+                out.push(body);
+            }
+            AstExpr::ForLoop {
+                pattern,
+                iterable,
+                body,
+                ..
+            } => {
+                push_pattern_mut(pattern, &mut out);
+                out.push(iterable);
+                out.push(body);
+            }
+            AstExpr::ModuleImport { source, .. } | AstExpr::ModuleInclude { source, .. } => {
+                out.push(source)
+            }
+            AstExpr::FuncReturn { body, .. } => out.extend(body.as_deref_mut()),
+        }
+        out
+    }
+}
+
+fn push_args<'a>(args: &'a [Arg], out: &mut Vec<&'a AstExpr>) {
+    for arg in args {
+        match arg {
+            Arg::Pos { expr } | Arg::Named { expr, .. } | Arg::Spread { expr, .. } => {
+                out.push(expr)
+            }
+        }
+    }
+}
+
+pub(super) fn push_pattern<'a>(pattern: &'a Pattern, out: &mut Vec<&'a AstExpr>) {
+    match pattern {
+        Pattern::Normal { expr } | Pattern::Parenthesized { expr } => out.push(expr),
+        Pattern::Placeholder { .. } => {}
+        Pattern::Destructuring { items, .. } => {
+            for item in items {
+                match item {
+                    DestructuringItem::Pattern { pattern }
+                    | DestructuringItem::Named { pattern, .. } => push_pattern(pattern, out),
+                    DestructuringItem::Spread { .. } => {}
+                }
+            }
+        }
+    }
+}
+
+fn push_args_mut<'a>(args: &'a mut [Arg], out: &mut Vec<&'a mut AstExpr>) {
+    for arg in args {
+        match arg {
+            Arg::Pos { expr } | Arg::Named { expr, .. } | Arg::Spread { expr, .. } => {
+                out.push(expr)
+            }
+        }
+    }
+}
+
+fn push_pattern_mut<'a>(pattern: &'a mut Pattern, out: &mut Vec<&'a mut AstExpr>) {
+    match pattern {
+        Pattern::Normal { expr } | Pattern::Parenthesized { expr } => out.push(expr),
+        Pattern::Placeholder { .. } => {}
+        Pattern::Destructuring { items, .. } => {
+            for item in items {
+                match item {
+                    DestructuringItem::Pattern { pattern }
+                    | DestructuringItem::Named { pattern, .. } => push_pattern_mut(pattern, out),
+                    DestructuringItem::Spread { .. } => {}
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,107 @@
+//! A corpus of representative snippets and a helper asserting that a source
+//! regenerator (the formatter, an unparser) preserves their AST.
+
+use serde_json::Value;
+
+use super::make_ast_result;
+use crate::format::format;
+use crate::parse_mode::ParseMode;
+use crate::parse_options::ParseOptions;
+
+pub const CORPUS: &[(ParseMode, &str)] = &[
+    (
+        ParseMode::Markup,
+        "= Introduction <intro>\nSome *strong* and _emph_ text, see @intro.\n\n- one\n- two\n+ first\n/ Term: description",
+    ),
+    (
+        ParseMode::Markup,
+        "Quotes \"like 'this'\" with \\#escapes -- and~shorthands...\\\nand a link https://typst.app.",
+    ),
+    (
+        ParseMode::Markup,
+        "```rust\nfn main() {\n\tprintln!(\"hi\");\n}\n```\nInline `raw` and ``` ``fenced`` ```.",
+    ),
+    (
+        ParseMode::Markup,
+        "#set text(size: 11pt, font: \"New Computer Modern\")\n#show heading: it => block(it.body)\n#show \"x\": [y]",
+    ),
+    (
+        ParseMode::Markup,
+        "#let f(x, y: 2, ..rest) = {\n  let z = x+y * 2\n  if z > 3 [big] else { z }\n}\n#f(1, y: 3)[body]",
+    ),
+    (
+        ParseMode::Code,
+        "let (a, (b, _), ..c) = (1, (2, 3), 4, 5)\n(a, b) = (b, a)\nlet d = (x: 1, \"y\": 2, ..e)",
+    ),
+    (
+        ParseMode::Code,
+        "for (k, v) in dict.pairs() {\n  if k in (\"a\", \"b\") { continue }\n  while not done { break }\n}",
+    ),
+    (
+        ParseMode::Code,
+        "import \"lib.typ\": a, b as c\ninclude \"chapter.typ\"\ncontext text.lang",
+    ),
+    (
+        ParseMode::Code,
+        "0xff + 0b101 - 0o17 * 1e3 / 2.5em + 50% + 1fr + -3deg\ntrue and false or none == auto",
+    ),
+    (
+        ParseMode::Code,
+        "let g = (..args) => args.pos().map(x => x * 2).sum()\nreturn g(1, 2)",
+    ),
+    (
+        ParseMode::Math,
+        "sum_(i=0)^n x_i^2 = (a + b)/2 + sqrt(x) + root(3, y)",
+    ),
+    (
+        ParseMode::Math,
+        "f'(x) = lim_(h -> 0) (f(x+h) - f(x))/h & alpha != beta \\\n [|x|] + floor(x) + #calc.pi",
+    ),
+];
+
+/// The AST of `text` as JSON with every range and stable id removed, so that
+/// trees from differently spaced sources can be compared.
+pub fn structure(text: &str, mode: ParseMode) -> Value {
+    let root = mode.parse(text);
+    let result = make_ast_result(&root, &mode, &ParseOptions::default()).unwrap();
+    let mut value = serde_json::to_value(&result.root).unwrap();
+    strip_positions(&mut value);
+    value
+}
+
+fn strip_positions(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|key, _| key != "range" && !key.ends_with("Range") && key != "stableId");
+            map.values_mut().for_each(strip_positions);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_positions),
+        _ => {}
+    }
+}
+
+/// Asserts that `text` is error-free and that `regenerate` turns it into
+/// source with the same AST structure.
+pub fn assert_round_trip(text: &str, mode: ParseMode, regenerate: impl Fn(&str) -> String) {
+    assert!(
+        !mode.parse(text).erroneous(),
+        "corpus snippet has errors:\n{text}"
+    );
+    let regenerated = regenerate(text);
+    assert_eq!(
+        structure(text, mode),
+        structure(&regenerated, mode),
+        "round trip changed the AST of:\n{text}\n---\n{regenerated}"
+    );
+}
+
+#[test]
+fn formatting_preserves_the_corpus() {
+    let mut changed = 0;
+    for &(mode, text) in CORPUS {
+        assert_round_trip(text, mode, |text| format(text, mode).unwrap());
+        changed += usize::from(format(text, mode).unwrap() != text);
+    }
+    // Make sure the corpus actually exercises the formatter.
+    assert!(changed > 0);
+}
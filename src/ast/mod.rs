@@ -1,7 +1,20 @@
+pub mod builtins;
 mod convert;
+#[cfg(all(test, not(feature = "forward_compat")))]
+mod declarations;
+mod events;
 pub mod expr;
+#[cfg(test)]
+mod golden;
 mod offset;
+mod recovery;
+mod stable_id;
+mod text;
 pub mod types;
+mod unparse;
+mod visit;
+
+use std::cell::Cell;
 
 use serde::Serialize;
 use typst_syntax::LinkedNode;
@@ -9,58 +22,221 @@ use typst_syntax::SyntaxNode;
 use typst_syntax::ast;
 
 use crate::cst::{self, ParseError};
+use crate::lint::{self, Warning};
+use crate::newlines::{LineEnding, line_ending};
+use crate::output::KIND_TABLE;
 use crate::parse_mode::ParseMode;
-use convert::{convert_code, convert_markup, convert_math};
-use offset::{OffsetMap, build_offset_map};
+use crate::parse_options::ParseOptions;
+use crate::range_mapper::RangeMapper;
+use convert::{Context, convert_code, convert_markup, convert_math};
+use stable_id::{StableIds, build_stable_ids};
 
+pub use events::{AstEvent, ast_events};
 pub use expr::AstExpr;
+pub use recovery::{Recovery, RecoveryReport, parse_with_recovery_report};
+pub(crate) use text::markup_text;
+pub use text::{extract_text, to_plaintext};
+pub use unparse::unparse;
+use visit::retain_kinds;
+pub use visit::{walk, walk_mut};
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
 pub struct AstParseResult {
     pub root: Vec<AstExpr>,
+    pub root_count: usize,
+    /// Whether the text parsed without errors, so that `errors` is empty.
+    pub ok: bool,
     pub errors: Vec<ParseError>,
+    pub warnings: Vec<Warning>,
+    pub line_ending: LineEnding,
+    /// The kind for each id, only with `ParseOptions::numeric_kinds` or
+    /// `ParseOptions::kinds_only_numeric`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, ts(optional))]
+    pub kind_map: Option<&'static [&'static str]>,
+    /// The mode the text was parsed in, only with `ParseMode::Auto`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, ts(optional))]
+    pub detected_mode: Option<ParseMode>,
 }
 
-pub fn make_ast_result(root: &SyntaxNode, mode: &ParseMode) -> Result<AstParseResult, String> {
+pub fn make_ast_result(
+    root: &SyntaxNode,
+    mode: &ParseMode,
+    options: &ParseOptions,
+) -> Result<AstParseResult, String> {
+    make_mapped_ast_result(root, mode, options, &RangeMapper::new(root, options))
+}
+
+/// Like [`make_ast_result`], with ranges translated by `mapper`.
+pub(crate) fn make_mapped_ast_result(
+    root: &SyntaxNode,
+    mode: &ParseMode,
+    options: &ParseOptions,
+    mapper: &RangeMapper,
+) -> Result<AstParseResult, String> {
+    convert_tree(root, mode, options, mapper, 0)
+}
+
+/// Converts a tree whose ranges are translated by `mapper`. `nesting` counts
+/// the raw blocks the tree was parsed from (see `parse_nested_typst`).
+fn convert_tree(
+    root: &SyntaxNode,
+    mode: &ParseMode,
+    options: &ParseOptions,
+    mapper: &RangeMapper,
+    nesting: usize,
+) -> Result<AstParseResult, String> {
     let linked = LinkedNode::new(root);
-    let mut offsets = OffsetMap::new();
-    build_offset_map(&linked, &mut offsets);
+    let stable_ids = if options.stable_ids {
+        build_stable_ids(root, options.max_depth())
+    } else {
+        StableIds::default()
+    };
+    let heading_offsets = convert::heading_offsets(&linked);
+    let source = options.include_source.then(|| cst::node_text(root));
+    let ctx = Context {
+        options,
+        mapper,
+        stable_ids: &stable_ids,
+        nesting,
+        heading_offsets: &heading_offsets,
+        depth: Cell::new(0),
+        source: source.as_deref(),
+        frontier: None,
+    };
+
+    let mut exprs = convert_root(&linked, mode, &ctx)?;
+    if let Some(kinds) = &options.kinds {
+        exprs = retain_kinds(exprs, kinds);
+    }
+
+    // Conversion leaves out what the CST leaves out, and reports it alike.
+    let mut errors = cst::collect_errors(&linked);
+    let too_deep = cst::find_too_deep(root, options.max_depth());
+    if let Some(range) = too_deep {
+        errors.push(cst::too_deep_error(options.max_depth(), range));
+        cst::sort_and_dedup(&mut errors);
+    }
+    cst::map_error_ranges(&mut errors, mapper);
+
+    let mut warnings = vec![];
+    if options.lint_indentation {
+        warnings.extend(lint::mixed_indentation(&linked));
+    }
+    warnings.extend(lint::return_outside_closure(&linked));
+    warnings.extend(lint::empty_markup(&linked));
+    lint::map_warning_ranges(&mut warnings, mapper);
 
-    let exprs = match mode {
+    Ok(AstParseResult {
+        root_count: exprs.len(),
+        root: exprs,
+        ok: !root.erroneous() && too_deep.is_none(),
+        errors,
+        warnings,
+        // The removed CRs are those of the outermost text.
+        line_ending: line_ending(root, mapper.removed_crs().filter(|_| nesting == 0)),
+        kind_map: ((options.numeric_kinds || options.kinds_only_numeric) && nesting == 0)
+            .then_some(KIND_TABLE),
+        detected_mode: (*mode == ParseMode::Auto).then(|| ParseMode::of_root(root)),
+    })
+}
+
+/// Like [`make_ast_result`], but turns a panic during conversion into an
+/// error so one bad document cannot take down a long-running worker.
+///
+/// This only isolates panics where unwinding is available. On
+/// `wasm32-unknown-unknown` panics abort, so the guarantee is limited to
+/// native builds there.
+pub fn make_ast_result_isolated(
+    root: &SyntaxNode,
+    mode: &ParseMode,
+    options: &ParseOptions,
+) -> Result<AstParseResult, String> {
+    catch_panic(|| make_ast_result(root, mode, options))
+}
+
+pub(crate) fn catch_panic<T>(f: impl FnOnce() -> Result<T, String>) -> Result<T, String> {
+    // Conversion only reads from the tree and builds fresh output, so nothing
+    // observable is left half-updated if it unwinds.
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".into());
+        Err(format!("AST conversion panicked: {message}"))
+    })
+}
+
+/// Counts the top-level expressions without converting them.
+pub fn count_root_exprs(root: &SyntaxNode, mode: &ParseMode) -> Result<usize, String> {
+    Ok(root_exprs(root, mode)?.count())
+}
+
+fn convert_root(
+    root: &LinkedNode,
+    mode: &ParseMode,
+    ctx: &Context,
+) -> Result<Vec<AstExpr>, String> {
+    let node = root.get();
+    Ok(match mode {
+        ParseMode::Markup => convert_markup(
+            node.cast().ok_or("Failed to cast root to Markup")?,
+            root,
+            ctx,
+        ),
+        ParseMode::Code => {
+            convert_code(node.cast().ok_or("Failed to cast root to Code")?, root, ctx)
+        }
+        ParseMode::Math => {
+            convert_math(node.cast().ok_or("Failed to cast root to Math")?, root, ctx)
+        }
+        ParseMode::Auto => return convert_root(root, &ParseMode::of_root(node), ctx),
+    })
+}
+
+fn root_exprs<'a>(
+    root: &'a SyntaxNode,
+    mode: &ParseMode,
+) -> Result<Box<dyn Iterator<Item = ast::Expr<'a>> + 'a>, String> {
+    Ok(match mode {
         ParseMode::Markup => {
             let markup: ast::Markup = root.cast().ok_or("Failed to cast root to Markup")?;
-            convert_markup(markup, &offsets)
+            Box::new(markup.exprs())
         }
         ParseMode::Code => {
             let code: ast::Code = root.cast().ok_or("Failed to cast root to Code")?;
-            convert_code(code, &offsets)
+            Box::new(code.exprs())
         }
         ParseMode::Math => {
             let math: ast::Math = root.cast().ok_or("Failed to cast root to Math")?;
-            convert_math(math, &offsets)
+            Box::new(math.exprs())
         }
-    };
-
-    let errors = cst::collect_errors(&linked);
-
-    Ok(AstParseResult {
-        root: exprs,
-        errors,
+        ParseMode::Auto => return root_exprs(root, &ParseMode::of_root(root)),
     })
 }
 
 #[cfg(test)]
 mod tests {
+    use super::offset::Range;
+    use super::types::{
+        Arg, ArrayItem, BodyKind, DictItem, HeadingSyntax, ImportItem, Imports, LetBindingKind,
+        MathDelimiter, MathIdentRole, PatternKind, RuleScope, Unit,
+    };
     use super::*;
+    use crate::parse_options::TextMode;
+    use crate::range_mapper::OffsetEncoding;
 
     fn parse_ok(text: &str, mode: ParseMode) -> AstParseResult {
-        let root = match mode {
-            ParseMode::Markup => typst_syntax::parse(text),
-            ParseMode::Code => typst_syntax::parse_code(text),
-            ParseMode::Math => typst_syntax::parse_math(text),
-        };
+        parse_with(text, mode, &ParseOptions::default())
+    }
 
-        make_ast_result(&root, &mode).expect("make_ast_result should not fail")
+    fn parse_with(text: &str, mode: ParseMode, options: &ParseOptions) -> AstParseResult {
+        let root = mode.parse(text);
+        make_ast_result(&root, &mode, options).expect("make_ast_result should not fail")
     }
 
     #[test]
@@ -74,6 +250,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn keeps_partial_ast_alongside_errors() {
+        let result = parse_ok("= Title\n#f(1,\n*bold*", ParseMode::Markup);
+        assert!(!result.errors.is_empty());
+        assert!(!result.ok);
+        assert!(parse_ok("= Title\n#f(1)", ParseMode::Markup).ok);
+        let kinds: Vec<_> = result.root.iter().map(AstExpr::kind).collect();
+        assert_eq!(kinds.first(), Some(&"heading"));
+        assert!(kinds.contains(&"funcCall"));
+
+        for mode in [ParseMode::Markup, ParseMode::Code, ParseMode::Math] {
+            let root = mode.parse("#let = ) [ $");
+            assert!(make_ast_result(&root, &mode, &ParseOptions::default()).is_ok());
+        }
+    }
+
     #[test]
     fn uses_null_range_for_placeholder_nodes() {
         let result = parse_ok("#show:", ParseMode::Code);
@@ -86,11 +278,53 @@ mod tests {
         };
 
         match *transform {
-            AstExpr::None { range } => assert!(range.is_none()),
+            AstExpr::None { range, .. } => assert!(range.is_none()),
             _ => panic!("expected placeholder none expression"),
         }
     }
 
+    #[test]
+    fn includes_source_when_requested() {
+        let options = ParseOptions {
+            include_source: true,
+            offset_encoding: OffsetEncoding::Utf16,
+            ..Default::default()
+        };
+        let result = parse_with("ä *bold* #show:", ParseMode::Markup, &options);
+        let sources: Vec<_> = result
+            .root
+            .iter()
+            .map(|expr| expr.meta().source.as_deref())
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                Some("ä"),
+                Some(" "),
+                Some("*bold*"),
+                Some(" "),
+                Some("show:")
+            ]
+        );
+        let Some(AstExpr::ShowRule { transform, .. }) = result.root.last() else {
+            panic!("expected show rule");
+        };
+        assert_eq!(transform.meta().source, None);
+        assert_eq!(
+            parse_ok("*bold*", ParseMode::Markup).root[0].meta().source,
+            None
+        );
+
+        // Merged text covers the source of all its parts.
+        let options = ParseOptions {
+            include_source: true,
+            text_mode: TextMode::Resolved,
+            ..Default::default()
+        };
+        let result = parse_with("it's -- \\#", ParseMode::Markup, &options);
+        assert_eq!(result.root[0].meta().source.as_deref(), Some("it's -- \\#"));
+    }
+
     #[test]
     fn handles_erroneous_math_without_panicking() {
         for src in ["√", "x_"] {
@@ -107,4 +341,1137 @@ mod tests {
         let result = parse_ok("[*", ParseMode::Markup);
         assert!(!result.errors.is_empty());
     }
+
+    #[test]
+    fn counts_root_exprs() {
+        let root = typst_syntax::parse("= Hi\n\nSome *text*.");
+        let result = make_ast_result(&root, &ParseMode::Markup, &ParseOptions::default()).unwrap();
+        assert_eq!(result.root_count, result.root.len());
+        assert_eq!(
+            count_root_exprs(&root, &ParseMode::Markup).unwrap(),
+            result.root.len()
+        );
+    }
+
+    #[test]
+    fn reports_the_detected_mode() {
+        let kinds = |text: &str| {
+            let result = parse_ok(text, ParseMode::Auto);
+            let kinds: Vec<_> = result.root.iter().map(AstExpr::kind).collect();
+            (result.detected_mode, kinds, result.errors.len())
+        };
+        assert_eq!(kinds("$x$"), (Some(ParseMode::Math), vec!["mathText"], 0));
+        assert_eq!(kinds("#f(x)"), (Some(ParseMode::Code), vec!["funcCall"], 0));
+        assert_eq!(kinds("*x*"), (Some(ParseMode::Markup), vec!["strong"], 0));
+        let result = parse_ok("$x$", ParseMode::Auto);
+        assert_eq!(result.root[0].range(), Some([1, 2]));
+        assert_eq!(parse_ok("$x$", ParseMode::Markup).detected_mode, None);
+    }
+
+    #[test]
+    fn captures_link_display_body() {
+        let text = "https://typst.app #link(\"https://typst.app\")[Typst]";
+        let kinds: Vec<_> = parse_ok(text, ParseMode::Markup)
+            .root
+            .iter()
+            .map(AstExpr::kind)
+            .collect();
+        assert_eq!(kinds, ["link", "space", "funcCall"]);
+
+        let options = ParseOptions {
+            element_calls: true,
+            ..Default::default()
+        };
+        let result = parse_with(text, ParseMode::Markup, &options);
+        let links: Vec<_> = result
+            .root
+            .iter()
+            .filter_map(|expr| match expr {
+                AstExpr::Link { url, body, .. } => Some((url.as_str(), body)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].0, "https://typst.app");
+        assert!(links[0].1.is_none());
+        assert_eq!(links[1].0, "https://typst.app");
+        assert!(matches!(
+            links[1].1.as_deref(),
+            Some([AstExpr::Text { text, .. }]) if text == "Typst"
+        ));
+    }
+
+    #[test]
+    fn trims_text_when_requested() {
+        let src = "a \u{2003}word\u{a0} b";
+        let text_nodes = |result: AstParseResult| -> Vec<(String, Range)> {
+            result
+                .root
+                .into_iter()
+                .filter_map(|expr| match expr {
+                    AstExpr::Text { range, text, .. } => Some((text, range)),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let untrimmed = text_nodes(parse_ok(src, ParseMode::Markup));
+        assert_eq!(untrimmed[1], ("\u{2003}word".to_string(), Some([2, 9])));
+        assert_eq!(untrimmed[2], ("\u{a0} b".to_string(), Some([9, 13])));
+
+        let options = ParseOptions {
+            trim_text: true,
+            ..Default::default()
+        };
+        let trimmed = text_nodes(parse_with(src, ParseMode::Markup, &options));
+        assert_eq!(trimmed[1], ("word".to_string(), Some([5, 9])));
+        // Non-breaking spaces are content, not whitespace to trim.
+        assert_eq!(trimmed[2], ("\u{a0} b".to_string(), Some([9, 13])));
+        for (text, range) in trimmed {
+            let [start, end] = range.unwrap();
+            assert_eq!(&src[start..end], text);
+        }
+    }
+
+    #[test]
+    fn flags_known_rule_targets() {
+        let result = parse_ok(
+            "#set heading(numbering: \"1.\")\n#show foo: none\n#show \"x\": none\n#set text.lang(1)",
+            ParseMode::Markup,
+        );
+        let flags: Vec<_> = result
+            .root
+            .iter()
+            .filter_map(|expr| match expr {
+                AstExpr::SetRule {
+                    is_known_element, ..
+                }
+                | AstExpr::ShowRule {
+                    is_known_element, ..
+                } => Some(*is_known_element),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(flags, [Some(true), Some(false), None, None]);
+    }
+
+    #[test]
+    fn classifies_pattern_shapes() {
+        let result = parse_ok(
+            "let x = 1\nlet (x) = 1\nlet (a, b) = c\n_ = d\n(a, b) = c\nfor _ in y {}\nfor (k, v) in d {}",
+            ParseMode::Code,
+        );
+        let kinds: Vec<_> = result
+            .root
+            .iter()
+            .map(|expr| match expr {
+                AstExpr::LetBinding {
+                    binding_kind: LetBindingKind::Normal { pattern_kind, .. },
+                    ..
+                }
+                | AstExpr::DestructAssignment { pattern_kind, .. }
+                | AstExpr::ForLoop { pattern_kind, .. } => *pattern_kind,
+                _ => panic!("unexpected expression"),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                PatternKind::Ident,
+                PatternKind::Paren,
+                PatternKind::Destructuring,
+                PatternKind::Placeholder,
+                PatternKind::Destructuring,
+                PatternKind::Placeholder,
+                PatternKind::Destructuring,
+            ]
+        );
+    }
+
+    #[test]
+    fn classifies_loop_and_conditional_bodies() {
+        let result = parse_ok(
+            "for x in y [a]\nwhile z { b }\nif c { d } else if e [f]",
+            ParseMode::Code,
+        );
+        let kinds: Vec<_> = result
+            .root
+            .into_iter()
+            .map(|expr| match expr {
+                AstExpr::ForLoop { body_kind, .. } | AstExpr::WhileLoop { body_kind, .. } => {
+                    (body_kind, None)
+                }
+                AstExpr::Conditional {
+                    if_body_kind,
+                    else_body_kind,
+                    ..
+                } => (if_body_kind, else_body_kind),
+                _ => panic!("unexpected expression"),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (BodyKind::Content, None),
+                (BodyKind::Code, None),
+                (BodyKind::Code, Some(BodyKind::Expr)),
+            ]
+        );
+    }
+
+    #[test]
+    fn shifts_ranges_by_base_offset() {
+        let options = ParseOptions {
+            base_offset: 100,
+            ..Default::default()
+        };
+        let result = parse_with("#show:", ParseMode::Code, &options);
+        let Some(AstExpr::ShowRule {
+            range, transform, ..
+        }) = result.root.into_iter().next()
+        else {
+            panic!("expected show rule");
+        };
+        assert_eq!(range, Some([101, 106]));
+        assert!(matches!(*transform, AstExpr::None { range: None, .. }));
+        assert!(
+            result
+                .errors
+                .iter()
+                .all(|e| e.range[0] >= 100 && e.range[1] <= 106)
+        );
+    }
+
+    #[test]
+    fn exposes_range_and_children_accessors() {
+        let result = parse_ok(
+            "= A *b*\n#f(1, x: 2)[c] $a^2$ #for (k, v) in d { k }",
+            ParseMode::Markup,
+        );
+        for expr in &result.root {
+            assert!(expr.range().is_some());
+            let composite = matches!(
+                expr,
+                AstExpr::Heading { .. }
+                    | AstExpr::FuncCall { .. }
+                    | AstExpr::Equation { .. }
+                    | AstExpr::ForLoop { .. }
+            );
+            assert_eq!(!expr.children().is_empty(), composite, "{:?}", expr.range());
+        }
+
+        let Some(call) = result
+            .root
+            .iter()
+            .find(|expr| matches!(expr, AstExpr::FuncCall { .. }))
+        else {
+            panic!("expected function call");
+        };
+        // callee, two argument values, and the trailing content block
+        assert_eq!(call.children().len(), 4);
+
+        let Some(for_loop @ AstExpr::ForLoop { .. }) = result.root.last() else {
+            panic!("expected for loop");
+        };
+        // k and v from the pattern, the iterable, and the body
+        assert_eq!(for_loop.children().len(), 4);
+    }
+
+    #[test]
+    fn detects_trailing_commas() {
+        let result = parse_ok(
+            "(1, 2)\n(1, 2,)\n(1,)\n(a: 1)\n(a: 1, /* c */ )\n()\n(:)",
+            ParseMode::Code,
+        );
+        let flags: Vec<_> = result
+            .root
+            .iter()
+            .map(|expr| match expr {
+                AstExpr::Array { trailing_comma, .. } | AstExpr::Dict { trailing_comma, .. } => {
+                    *trailing_comma
+                }
+                _ => panic!("expected collection, got {}", expr.kind()),
+            })
+            .collect();
+        assert_eq!(flags, [false, true, true, false, true, false, false]);
+    }
+
+    #[test]
+    fn distinguishes_parenthesized_from_single_element_array() {
+        let result = parse_ok("(1)\n(1,)", ParseMode::Code);
+        assert!(matches!(
+            &result.root[0],
+            AstExpr::Parenthesized { expr, .. } if matches!(**expr, AstExpr::Int { value: 1, .. })
+        ));
+        assert!(matches!(
+            &result.root[1],
+            AstExpr::Array { items, trailing_comma: true, .. } if items.len() == 1
+        ));
+    }
+
+    #[test]
+    fn exposes_conditional_keyword_ranges() {
+        let result = parse_ok("if x { 1 } else { 2 }", ParseMode::Code);
+        let Some(AstExpr::Conditional {
+            if_keyword_range,
+            else_keyword_range,
+            ..
+        }) = result.root.into_iter().next()
+        else {
+            panic!("expected conditional");
+        };
+        assert_eq!(if_keyword_range, Some([0, 2]));
+        assert_eq!(else_keyword_range, Some([11, 15]));
+
+        let result = parse_ok("if x { 1 }", ParseMode::Code);
+        let Some(AstExpr::Conditional {
+            else_keyword_range, ..
+        }) = result.root.into_iter().next()
+        else {
+            panic!("expected conditional");
+        };
+        assert!(else_keyword_range.is_none());
+    }
+
+    #[test]
+    fn distinguishes_heading_syntax() {
+        let text = "== Markup\n#heading[Func]\n#heading(level: 3)[Deep]\n#heading(numbering: \"1.\")[Other]";
+        let calls = parse_ok(text, ParseMode::Markup)
+            .root
+            .iter()
+            .filter(|expr| expr.kind() == "funcCall")
+            .count();
+        assert_eq!(calls, 3);
+
+        let options = ParseOptions {
+            element_calls: true,
+            ..Default::default()
+        };
+        let result = parse_with(text, ParseMode::Markup, &options);
+        let headings: Vec<_> = result
+            .root
+            .iter()
+            .filter_map(|expr| match expr {
+                AstExpr::Heading { depth, syntax, .. } => Some((*depth, *syntax)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            headings,
+            [
+                (2, HeadingSyntax::Markup),
+                (1, HeadingSyntax::Func),
+                (3, HeadingSyntax::Func),
+            ]
+        );
+        assert!(
+            result
+                .root
+                .iter()
+                .any(|expr| matches!(expr, AstExpr::FuncCall { .. }))
+        );
+    }
+
+    #[test]
+    fn exposes_return_keyword_and_warns_outside_closures() {
+        let result = parse_ok("return 1", ParseMode::Code);
+        let Some(AstExpr::FuncReturn { keyword_range, .. }) = result.root.first() else {
+            panic!("expected return");
+        };
+        assert_eq!(*keyword_range, Some([0, 6]));
+        assert_eq!(result.warnings.len(), 1);
+
+        let result = parse_ok("let f() = { return 1 }", ParseMode::Code);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn isolates_panics_as_errors() {
+        let root = typst_syntax::parse("= Hi");
+        assert!(
+            make_ast_result_isolated(&root, &ParseMode::Markup, &ParseOptions::default()).is_ok()
+        );
+
+        let result: Result<(), String> = catch_panic(|| panic!("boom"));
+        assert_eq!(result.unwrap_err(), "AST conversion panicked: boom");
+    }
+
+    #[test]
+    fn classifies_math_delimiters() {
+        let result = parse_ok("(a) [b] {c} ⌊d⌋ (e] [| f |]", ParseMode::Math);
+        let delimiters: Vec<_> = result
+            .root
+            .iter()
+            .filter_map(|expr| match expr {
+                AstExpr::MathDelimited { delimiter, .. } => Some(*delimiter),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            delimiters,
+            [
+                MathDelimiter::Paren,
+                MathDelimiter::Bracket,
+                MathDelimiter::Brace,
+                MathDelimiter::Custom,
+                MathDelimiter::Custom,
+                MathDelimiter::Custom,
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_raw_string_spelling_when_requested() {
+        let options = ParseOptions {
+            string_raw: true,
+            ..Default::default()
+        };
+        let result = parse_with(r#"#"\u{1F600}""#, ParseMode::Markup, &options);
+        let Some(AstExpr::Str { value, raw, .. }) = result.root.first() else {
+            panic!("expected string");
+        };
+        assert_eq!(value, "\u{1F600}");
+        assert_eq!(raw.as_deref(), Some(r#""\u{1F600}""#));
+
+        let result = parse_ok(r#"#"\n""#, ParseMode::Markup);
+        assert!(matches!(&result.root[0], AstExpr::Str { value, raw: None, .. } if value == "\n"));
+    }
+
+    #[test]
+    fn keeps_literal_text_when_requested() {
+        let options = ParseOptions {
+            keep_literal_text: true,
+            ..Default::default()
+        };
+        let literals: Vec<_> =
+            parse_with("#(0xff, 1e3, 1.50pt, 1_000)", ParseMode::Markup, &options)
+                .root
+                .iter()
+                .flat_map(|expr| {
+                    let mut found = vec![];
+                    walk(expr, &mut |expr| match expr {
+                        AstExpr::Int { value, literal, .. } => {
+                            found.push((*value as f64, literal.clone()))
+                        }
+                        AstExpr::Float { value, literal, .. }
+                        | AstExpr::Numeric { value, literal, .. } => {
+                            found.push((*value, literal.clone()))
+                        }
+                        _ => {}
+                    });
+                    found
+                })
+                .collect();
+        // Typst has no digit separators, so `1_000` is `1` followed by text.
+        let expected = [
+            (255.0, "0xff"),
+            (1000.0, "1e3"),
+            (1.5, "1.50pt"),
+            (1.0, "1"),
+        ];
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(value, literal)| (value, Some(literal.to_string())))
+            .collect();
+        assert_eq!(literals, expected);
+
+        let result = parse_ok("#0xff", ParseMode::Markup);
+        assert!(matches!(
+            &result.root[0],
+            AstExpr::Int {
+                value: 255,
+                literal: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn marks_delimiters_sized_by_lr() {
+        let sized = |text: &str| {
+            let mut found = vec![];
+            for expr in &parse_ok(text, ParseMode::Markup).root {
+                walk(expr, &mut |expr| {
+                    if let AstExpr::MathDelimited { sized, .. } = expr {
+                        found.push(*sized);
+                    }
+                });
+            }
+            found
+        };
+        assert_eq!(sized("$ (x) $"), [false]);
+        assert_eq!(sized("$ lr((x)) $"), [true]);
+        assert_eq!(
+            sized("$ lr([x], size: #150%) + math.lr({y}) $"),
+            [true, true]
+        );
+        assert_eq!(sized("$ abs((x)) $"), [false]);
+        // Broken calls and unclosed pairs still convert.
+        assert_eq!(sized("$ lr((x) $"), [true]);
+        assert!(sized("$ lr( (x $").is_empty());
+    }
+
+    #[test]
+    fn folds_literal_arithmetic_when_requested() {
+        let src =
+            "1pt + 2pt\n2 * (3 + 4)\n1.5 / 2.0\n1pt + 1em\n1 + 2.0\n7 / 2\n9223372036854775807 + 1";
+        let options = ParseOptions {
+            fold_constants: true,
+            ..Default::default()
+        };
+        let result = parse_with(src, ParseMode::Code, &options);
+        assert!(matches!(
+            result.root[0],
+            AstExpr::Numeric {
+                value: 3.0,
+                unit: Unit::Pt,
+                range: Some([0, 9]),
+                ..
+            }
+        ));
+        assert!(matches!(result.root[1], AstExpr::Int { value: 14, .. }));
+        assert!(matches!(result.root[2], AstExpr::Float { value: 0.75, .. }));
+        for expr in &result.root[3..] {
+            assert_eq!(expr.kind(), "binary");
+        }
+
+        let result = parse_ok("1 + 2", ParseMode::Code);
+        assert_eq!(result.root[0].kind(), "binary");
+    }
+
+    #[test]
+    fn flattens_term_item_text() {
+        let result = parse_ok("/ *Bold* term: A _short_ description", ParseMode::Markup);
+        let Some(AstExpr::TermItem {
+            term,
+            term_text,
+            description_text,
+            ..
+        }) = result.root.first()
+        else {
+            panic!("expected term item");
+        };
+        assert!(matches!(term.first(), Some(AstExpr::Strong { .. })));
+        assert_eq!(term_text, "Bold term");
+        assert_eq!(description_text, "A short description");
+    }
+
+    #[test]
+    fn extracts_plain_text_for_indexing() {
+        let plaintext = |text| to_plaintext(text, &ParseOptions::default()).unwrap();
+        assert_eq!(plaintext("= *Hello* world"), "Hello world");
+        assert_eq!(
+            plaintext("- _one_\\\n  two \\# -- three\n\n\n/ Term: desc #f(x) `raw` $x$"),
+            "one two # – three Term: desc"
+        );
+        assert_eq!(plaintext("#let x = 1\n```\ncode\n```"), "");
+    }
+
+    #[test]
+    fn derives_rule_scope_from_enclosing_block() {
+        let result = parse_ok(
+            "#set text(red)\n#[#show emph: strong]\n#let f() = { set par(justify: true) }",
+            ParseMode::Markup,
+        );
+        let mut scopes = vec![];
+        crate::analysis::for_each_expr(&result.root, &mut |expr| match expr {
+            AstExpr::SetRule { scope, .. } | AstExpr::ShowRule { scope, .. } => scopes.push(*scope),
+            _ => {}
+        });
+        assert_eq!(
+            scopes,
+            [RuleScope::Block, RuleScope::Local, RuleScope::Local]
+        );
+    }
+
+    #[test]
+    fn exposes_import_rename_ranges() {
+        let src = "import \"a.typ\" as m: x as y";
+        let result = parse_ok(src, ParseMode::Code);
+        let Some(AstExpr::ModuleImport {
+            new_name_range,
+            imports: Some(Imports::Items { items }),
+            ..
+        }) = result.root.first()
+        else {
+            panic!("expected module import");
+        };
+        let slice = |range: &Range| &src[range.unwrap()[0]..range.unwrap()[1]];
+        assert_eq!(slice(new_name_range), "m");
+        let [
+            ImportItem::Renamed {
+                original_name_range,
+                new_name_range,
+                ..
+            },
+        ] = items.as_slice()
+        else {
+            panic!("expected one renamed item");
+        };
+        assert_eq!(slice(original_name_range), "x");
+        assert_eq!(slice(new_name_range), "y");
+    }
+
+    #[test]
+    fn finds_ranges_of_nodes_nested_in_lists() {
+        // Values of named pairs and renamed items sit below the node listing
+        // them, and identical items must not be confused with each other.
+        let src =
+            "#let d = (a: x, b: x, c: (y: z))\n#f(k: x, x, ..x)\n#import \"m\": p as q, r as s";
+        let result = parse_ok(src, ParseMode::Markup);
+        let mut idents = 0;
+        for expr in &result.root {
+            walk(expr, &mut |expr| {
+                if let AstExpr::Ident { range, name, .. } = expr {
+                    let [start, end] = range.unwrap();
+                    assert_eq!(&src[start..end], name);
+                    idents += 1;
+                }
+            });
+        }
+        assert_eq!(idents, 8);
+        let Some(AstExpr::ModuleImport {
+            imports: Some(Imports::Items { items }),
+            ..
+        }) = result.root.last()
+        else {
+            panic!("expected module import");
+        };
+        let ImportItem::Renamed {
+            original_name_range: Some([start, end]),
+            ..
+        } = items[1]
+        else {
+            panic!("expected renamed item");
+        };
+        assert_eq!(&src[start..end], "r");
+    }
+
+    #[test]
+    fn extends_let_ranges_over_leading_comments() {
+        let src = "// unrelated\n\n/// Doc one.\n/// Doc two.\n#let x = 1\nlet y = 2 // trailing\n#let z = 3";
+        let options = ParseOptions {
+            include_comments_in_ranges: true,
+            ..Default::default()
+        };
+        let starts = |result: AstParseResult| -> Vec<usize> {
+            result
+                .root
+                .iter()
+                .filter(|expr| matches!(expr, AstExpr::LetBinding { .. }))
+                .map(|expr| expr.range().unwrap()[0])
+                .collect()
+        };
+
+        let doc = src.find("/// Doc one").unwrap();
+        let z = src.find("#let z").unwrap();
+        assert_eq!(
+            starts(parse_with(src, ParseMode::Markup, &options)),
+            [doc, z + 1]
+        );
+        assert_eq!(
+            starts(parse_ok(src, ParseMode::Markup)),
+            [src.find("let x").unwrap(), z + 1]
+        );
+    }
+
+    #[test]
+    fn attaches_trailing_labels_to_equations() {
+        let result = parse_ok("$ x $ <eq:a>\n$ y $\n\n<l>\n$z$<eq:z>", ParseMode::Markup);
+        let labels: Vec<_> = result
+            .root
+            .iter()
+            .filter_map(|expr| match expr {
+                AstExpr::Equation { label, .. } => Some(label.as_deref()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(labels, [Some("eq:a"), None, Some("eq:z")]);
+        assert_eq!(
+            result
+                .root
+                .iter()
+                .filter(|expr| matches!(expr, AstExpr::Label { .. }))
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn counts_blank_lines_in_parbreaks() {
+        let result = parse_ok("a\n\nb\n\n\nc\n \n\n\nd", ParseMode::Markup);
+        let blank_lines: Vec<_> = result
+            .root
+            .iter()
+            .filter_map(|expr| match expr {
+                AstExpr::Parbreak { blank_lines, .. } => Some(*blank_lines),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(blank_lines, [1, 2, 3]);
+    }
+
+    #[test]
+    fn stable_ids_survive_unrelated_edits() {
+        let options = ParseOptions {
+            stable_ids: true,
+            ..Default::default()
+        };
+        let id_of_strong = |src: &str| {
+            let result = parse_with(src, ParseMode::Markup, &options);
+            let mut ids = vec![];
+            crate::analysis::for_each_expr(&result.root, &mut |expr| {
+                if let AstExpr::Strong { meta, .. } = expr {
+                    ids.push(meta.stable_id.clone().expect("expected a stable id"));
+                }
+            });
+            ids
+        };
+
+        let before = id_of_strong("= Title\n\nSome *bold* text. *bold*");
+        let after = id_of_strong("= A new title\n\nIntro.\n\nSome   *bold* text! *bold*");
+        assert_eq!(before, after);
+        assert_eq!(before.len(), 2);
+        assert_ne!(before[0], before[1]);
+        assert_ne!(before, id_of_strong("Some *bolder* text. *bolder*"));
+        // Pinned, as ids must not change across builds and platforms.
+        assert_eq!(before[0], "2067214899e05af9-0");
+
+        let result = parse_ok("*bold*", ParseMode::Markup);
+        assert!(matches!(
+            &result.root[0],
+            AstExpr::Strong { meta, .. } if meta.stable_id.is_none()
+        ));
+    }
+
+    #[test]
+    fn flags_hash_prefixed_expressions() {
+        let result = parse_ok(
+            "#let x = 1
+#{ let y = 2 }
+$#x + y$",
+            ParseMode::Markup,
+        );
+        let mut flagged = vec![];
+        crate::analysis::for_each_expr(&result.root, &mut |expr| {
+            flagged.push((expr.kind(), expr.meta().hash_prefixed));
+        });
+        let prefixed: Vec<_> = flagged
+            .iter()
+            .filter(|(_, p)| *p)
+            .map(|(k, _)| *k)
+            .collect();
+        assert_eq!(prefixed, ["letBinding", "codeBlock", "ident"]);
+        assert!(flagged.contains(&("letBinding", false)));
+
+        let result = parse_ok("let x = 1", ParseMode::Code);
+        assert!(!result.root[0].meta().hash_prefixed);
+    }
+
+    #[test]
+    fn reports_raw_fences() {
+        let fences: Vec<_> = parse_ok("`a` ```rust x``` ````\nb\n````", ParseMode::Markup)
+            .root
+            .iter()
+            .filter_map(|expr| match expr {
+                AstExpr::Raw {
+                    block,
+                    fenced,
+                    backticks,
+                    ..
+                } => Some((*block, *fenced, *backticks)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            fences,
+            [(false, false, 1), (false, true, 3), (true, true, 4)]
+        );
+    }
+
+    #[test]
+    fn parses_nested_typst_raw_blocks() {
+        let src = "Example:\n```typ\n= Hi *there*\n```\n```rust\nfn x() {}\n```";
+        let options = ParseOptions {
+            parse_nested_typst: true,
+            ..Default::default()
+        };
+        let result = parse_with(src, ParseMode::Markup, &options);
+        let nested: Vec<_> = result
+            .root
+            .iter()
+            .filter_map(|expr| match expr {
+                AstExpr::Raw { nested, .. } => Some(nested.as_deref()),
+                _ => None,
+            })
+            .collect();
+        let [Some(typ), None] = nested.as_slice() else {
+            panic!("expected only the typ block to be parsed");
+        };
+        let Some(heading @ AstExpr::Heading { .. }) = typ.root.first() else {
+            panic!("expected nested heading");
+        };
+        let [start, end] = heading.range().unwrap();
+        assert_eq!(&src[start..end], "= Hi *there*");
+
+        let result = parse_ok(src, ParseMode::Markup);
+        assert!(result.root.iter().all(|expr| !matches!(
+            expr,
+            AstExpr::Raw {
+                nested: Some(_),
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn caps_nested_typst_depth() {
+        let mut src = "x".to_string();
+        for i in 0..8 {
+            let fence = "`".repeat(3 + i);
+            src = format!("{fence}typ\n{src}\n{fence}");
+        }
+        let options = ParseOptions {
+            parse_nested_typst: true,
+            ..Default::default()
+        };
+        let mut depth = 0;
+        let mut result = parse_with(&src, ParseMode::Markup, &options);
+        while let Some(AstExpr::Raw {
+            nested: Some(nested),
+            ..
+        }) = result.root.into_iter().next()
+        {
+            depth += 1;
+            result = *nested;
+        }
+        assert_eq!(depth, 4);
+    }
+
+    fn effective_depths(text: &str) -> Vec<Option<usize>> {
+        let options = ParseOptions {
+            element_calls: true,
+            ..Default::default()
+        };
+        parse_with(text, ParseMode::Markup, &options)
+            .root
+            .iter()
+            .filter_map(|expr| match expr {
+                AstExpr::Heading {
+                    effective_depth, ..
+                } => Some(*effective_depth),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn applies_literal_heading_offsets() {
+        assert_eq!(
+            effective_depths(
+                "= A\n#set heading(offset: 1)\n= B\n#heading(level: 2)[C]\n#heading(depth: 2)[D]\n#set heading(offset: 0)\n== E"
+            ),
+            vec![Some(1), Some(2), Some(2), Some(3), Some(2)]
+        );
+        assert_eq!(
+            effective_depths("#set heading(numbering: \"1.\")\n= A"),
+            vec![Some(1)]
+        );
+    }
+
+    #[test]
+    fn leaves_dynamic_heading_offsets_unknown() {
+        assert_eq!(effective_depths("#set heading(offset: n)\n= A"), vec![None]);
+        assert_eq!(
+            effective_depths("#set heading(offset: 1) if x\n= A"),
+            vec![None]
+        );
+        // Rules inside blocks are not tracked.
+        assert_eq!(
+            effective_depths("#[#set heading(offset: 2)]\n= A"),
+            vec![Some(1)]
+        );
+    }
+
+    #[test]
+    fn reports_operator_gaps() {
+        let options = ParseOptions {
+            operator_gaps: true,
+            ..Default::default()
+        };
+        let gaps = |text: &str| match &parse_with(text, ParseMode::Code, &options).root[..] {
+            [
+                AstExpr::Binary {
+                    lhs_gap, rhs_gap, ..
+                },
+            ] => (*lhs_gap, *rhs_gap),
+            _ => panic!("expected a binary expression"),
+        };
+        assert_eq!(gaps("a+b"), (Some(0), Some(0)));
+        assert_eq!(gaps("a + b"), (Some(1), Some(1)));
+        assert_eq!(gaps("a +b"), (Some(1), Some(0)));
+        assert_eq!(gaps("a  not in b"), (Some(2), Some(1)));
+
+        let [AstExpr::Binary { lhs_gap, .. }] = &parse_ok("a + b", ParseMode::Code).root[..] else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!(*lhs_gap, None);
+    }
+
+    #[test]
+    fn reports_recoveries() {
+        let text = "for x in\n) 1";
+        let root = ParseMode::Code.parse(text);
+        let report =
+            parse_with_recovery_report(&root, &ParseMode::Code, &ParseOptions::default()).unwrap();
+        let recoveries: Vec<_> = report
+            .recoveries
+            .iter()
+            .map(|r| (r.range, r.description.as_str()))
+            .collect();
+        assert_eq!(
+            recoveries,
+            [
+                (
+                    Some([0, 8]),
+                    "used a `none` placeholder for a missing part of `forLoop`"
+                ),
+                (
+                    Some([8, 8]),
+                    "expected expression; continued as if it were there"
+                ),
+                (Some([9, 10]), "skipped `)`: unexpected closing paren"),
+            ]
+        );
+        assert_eq!(report.result.errors.len(), 2);
+
+        let root = ParseMode::Code.parse("1 + 2");
+        let report =
+            parse_with_recovery_report(&root, &ParseMode::Code, &ParseOptions::default()).unwrap();
+        assert!(report.recoveries.is_empty());
+    }
+
+    #[test]
+    fn classifies_math_idents() {
+        let result = parse_ok("sin alpha + xy + lim_(n -> oo)", ParseMode::Math);
+        let mut roles = vec![];
+        crate::analysis::for_each_expr(&result.root, &mut |expr| {
+            if let AstExpr::MathIdent { name, role, .. } = expr {
+                roles.push((name.clone(), *role));
+            }
+        });
+        assert_eq!(
+            roles,
+            [
+                ("sin".to_string(), MathIdentRole::Function),
+                ("alpha".to_string(), MathIdentRole::Symbol),
+                ("xy".to_string(), MathIdentRole::Variable),
+                ("lim".to_string(), MathIdentRole::Function),
+                ("oo".to_string(), MathIdentRole::Symbol),
+            ]
+        );
+    }
+
+    #[test]
+    fn attaches_node_depths() {
+        fn depths(expr: &AstExpr, out: &mut Vec<(&'static str, usize)>) {
+            out.push((expr.kind(), expr.meta().node_depth.unwrap()));
+            for child in expr.children() {
+                depths(child, out);
+            }
+        }
+
+        let options = ParseOptions {
+            node_depth: true,
+            ..Default::default()
+        };
+        let result = parse_with("*a* #f(x + 1) $y^2$", ParseMode::Markup, &options);
+        let mut out = vec![];
+        for expr in &result.root {
+            depths(expr, &mut out);
+        }
+        assert_eq!(
+            out,
+            [
+                ("strong", 0),
+                ("text", 1),
+                ("space", 0),
+                ("funcCall", 0),
+                ("ident", 1),
+                ("binary", 1),
+                ("ident", 2),
+                ("int", 2),
+                ("space", 0),
+                ("equation", 0),
+                ("mathAttach", 1),
+                ("mathText", 2),
+                ("mathText", 2),
+            ]
+        );
+
+        let result = parse_ok("*a*", ParseMode::Markup);
+        assert_eq!(result.root[0].meta().node_depth, None);
+    }
+
+    #[test]
+    fn resolves_text_when_requested() {
+        let text = "She said \"it's\" \\#1 -- ok *'a'*";
+        let options = ParseOptions {
+            text_mode: TextMode::Resolved,
+            ..Default::default()
+        };
+        let result = parse_with(text, ParseMode::Markup, &options);
+        let [
+            AstExpr::Text {
+                range, text: first, ..
+            },
+            AstExpr::Strong { body, .. },
+        ] = &result.root[..]
+        else {
+            panic!("expected text followed by strong");
+        };
+        assert_eq!(first, "She said “it’s” #1 – ok ");
+        assert_eq!(*range, Some([0, text.find('*').unwrap()]));
+        let [AstExpr::Text { text: inner, .. }] = &body[..] else {
+            panic!("expected resolved text in strong");
+        };
+        assert_eq!(inner, "‘a’");
+
+        let result = parse_ok(text, ParseMode::Markup);
+        assert!(
+            result
+                .root
+                .iter()
+                .any(|e| matches!(e, AstExpr::Escape { character: '#', .. }))
+        );
+    }
+
+    #[test]
+    fn records_spread_token_ranges() {
+        let result = parse_ok("f(..rest)\n(..a, b)\n(x: 1, ..d)", ParseMode::Code);
+        let spreads: Vec<_> = result
+            .root
+            .iter()
+            .map(|expr| match expr {
+                AstExpr::FuncCall { args, .. } => match &args[..] {
+                    [Arg::Spread { spread_range, .. }] => *spread_range,
+                    _ => panic!("expected a spread argument"),
+                },
+                AstExpr::Array { items, .. } => match &items[..] {
+                    [
+                        ArrayItem::Spread { spread_range, .. },
+                        ArrayItem::Pos { .. },
+                    ] => *spread_range,
+                    _ => panic!("expected a spread item"),
+                },
+                AstExpr::Dict { items, .. } => match &items[..] {
+                    [
+                        DictItem::Named { .. },
+                        DictItem::Spread { spread_range, .. },
+                    ] => *spread_range,
+                    _ => panic!("expected a spread item"),
+                },
+                _ => panic!("unexpected expression"),
+            })
+            .collect();
+        assert_eq!(spreads, [Some([2, 4]), Some([11, 13]), Some([26, 28])]);
+    }
+
+    #[test]
+    fn shifts_ranges_after_multi_byte_characters_in_utf16() {
+        let text = "😀 漢\n= Hi";
+        let options = ParseOptions {
+            offset_encoding: OffsetEncoding::Utf16,
+            ..Default::default()
+        };
+        let result = parse_with(text, ParseMode::Markup, &options);
+        let heading = result.root.iter().find(|e| e.kind() == "heading").unwrap();
+        let start: usize = text[..text.find('=').unwrap()]
+            .chars()
+            .map(char::len_utf16)
+            .sum();
+        assert_eq!(start, 5);
+        assert_eq!(heading.range(), Some([start, start + 4]));
+
+        let root = ParseMode::Markup.parse(text);
+        let cst = crate::cst::build_cst(&root, &options);
+        assert_eq!(cst.root.range, Some([0, start + 4]));
+    }
+
+    #[test]
+    fn truncates_excessive_nesting_instead_of_overflowing() {
+        // typst-syntax's parser recurses too, so parse and drop the tree on a
+        // thread with a large stack; conversion runs on the test thread.
+        let on_big_stack = |f: Box<dyn FnOnce() -> Option<SyntaxNode> + Send>| {
+            std::thread::Builder::new()
+                .stack_size(256 << 20)
+                .spawn(f)
+                .unwrap()
+                .join()
+                .unwrap()
+        };
+        let text = format!("#{}1{}", "(".repeat(5000), ")".repeat(5000));
+        let root = on_big_stack(Box::new(move || Some(ParseMode::Markup.parse(&text)))).unwrap();
+
+        // Converting 512 levels takes more than a test thread's stack in
+        // debug builds, so a smaller limit shows that truncation bounds it.
+        let options = ParseOptions {
+            max_depth: Some(64),
+            stable_ids: true,
+            include_source: true,
+            lint_indentation: true,
+            ..Default::default()
+        };
+        let result = make_ast_result(&root, &ParseMode::Markup, &options).unwrap();
+        assert!(!result.ok);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(
+            result.errors[0].message,
+            "maximum nesting depth of 64 exceeded"
+        );
+        assert_eq!(result.errors[0].range, [64, 65]);
+        let mut truncated = vec![];
+        walk(&result.root[0], &mut |expr| {
+            if let AstExpr::Truncated {
+                range, syntax_kind, ..
+            } = expr
+            {
+                truncated.push((*range, syntax_kind.clone()));
+            }
+        });
+        assert_eq!(truncated, [(Some([65, 9938]), "Parenthesized".into())]);
+
+        let cst = cst::build_cst(&root, &options);
+        assert_eq!(cst.errors.len(), 1);
+        assert_eq!(cst.errors[0].message, result.errors[0].message);
+        assert_eq!(cst.errors[0].range, result.errors[0].range);
+
+        let shallow = ParseOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let within = ParseMode::Code.parse("(1)");
+        assert!(
+            make_ast_result(&within, &ParseMode::Code, &shallow)
+                .unwrap()
+                .ok
+        );
+        let beyond = ParseMode::Code.parse("((1))");
+        let result = make_ast_result(&beyond, &ParseMode::Code, &shallow).unwrap();
+        assert_eq!(result.errors[0].range, [1, 2]);
+        assert_eq!(cst::build_cst(&beyond, &shallow).errors[0].range, [1, 2]);
+        let AstExpr::Parenthesized { expr, .. } = &result.root[0] else {
+            panic!("expected a parenthesized expression");
+        };
+        let AstExpr::Parenthesized { expr, .. } = expr.as_ref() else {
+            panic!("expected a parenthesized expression");
+        };
+        assert_eq!(expr.kind(), "truncated");
+        assert_eq!(expr.range(), Some([2, 3]));
+
+        on_big_stack(Box::new(move || {
+            drop(root);
+            None
+        }));
+    }
 }
@@ -1,20 +1,36 @@
-use std::collections::HashMap;
-
+use typst_syntax::ast::AstNode;
 use typst_syntax::{LinkedNode, SyntaxNode};
 
-pub type OffsetMap = HashMap<*const SyntaxNode, usize>;
 pub type Range = Option<[usize; 2]>;
 
-pub fn build_offset_map(node: &LinkedNode, map: &mut OffsetMap) {
-    map.insert(node.get() as *const SyntaxNode, node.offset());
-    for child in node.children() {
-        build_offset_map(&child, map);
-    }
+/// The linked node of `node`, a child of `parent` handed out by one of its
+/// typed accessors.
+///
+/// Accessors return references into the children of `parent`, so the child
+/// is recognized by its address while the tree is borrowed. The placeholders
+/// accessors substitute for missing parts are not part of the tree; they get
+/// a detached linked node without a parent, and thus without a range.
+pub fn child<'a>(parent: &LinkedNode<'a>, node: &'a SyntaxNode) -> LinkedNode<'a> {
+    parent
+        .children()
+        .find(|child| std::ptr::eq(child.get(), node))
+        .unwrap_or_else(|| LinkedNode::new(node))
 }
 
-pub fn range_of(node: &SyntaxNode, offsets: &OffsetMap) -> Range {
-    offsets
-        .get(&(node as *const SyntaxNode))
-        .copied()
-        .map(|offset| [offset, offset + node.len()])
+/// Pairs each of `nodes`, children of `parent` in document order as returned
+/// by a typed accessor, with its linked node. The children of `parent` are
+/// walked once, so that this stays linear for nodes with many children.
+pub fn children<'a, T: AstNode<'a> + Copy>(
+    parent: &LinkedNode<'a>,
+    nodes: impl Iterator<Item = T>,
+) -> impl Iterator<Item = (T, LinkedNode<'a>)> {
+    let mut linked = parent.children();
+    nodes.map(move |typed| {
+        let node = typed.to_untyped();
+        let found = linked
+            .by_ref()
+            .find(|child| std::ptr::eq(child.get(), node))
+            .unwrap_or_else(|| LinkedNode::new(node));
+        (typed, found)
+    })
 }
@@ -0,0 +1,83 @@
+use serde::Serialize;
+use typst_syntax::{LinkedNode, SyntaxNode};
+
+use super::expr::AstExpr;
+use super::offset::Range;
+use super::{AstParseResult, make_ast_result};
+use crate::cst;
+use crate::parse_mode::ParseMode;
+use crate::parse_options::ParseOptions;
+use crate::range_mapper::RangeMapper;
+
+/// A place where the parser or the AST conversion recovered from broken
+/// input.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Recovery {
+    pub range: Range,
+    pub description: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct RecoveryReport {
+    #[serde(flatten)]
+    pub result: AstParseResult,
+    pub recoveries: Vec<Recovery>,
+}
+
+/// Like [`make_ast_result`], but also lists the recoveries behind the shape
+/// of the AST: input the parser skipped or syntax it assumed to be there,
+/// and the placeholder nodes (those without a range) standing in for missing
+/// expressions. A placeholder is reported with the range of the nearest
+/// enclosing node that has one.
+pub fn parse_with_recovery_report(
+    root: &SyntaxNode,
+    mode: &ParseMode,
+    options: &ParseOptions,
+) -> Result<RecoveryReport, String> {
+    let result = make_ast_result(root, mode, options)?;
+    let text = cst::node_text(root);
+    let mapper = RangeMapper::new(root, options);
+
+    let mut recoveries: Vec<_> = cst::collect_errors(&LinkedNode::new(root))
+        .into_iter()
+        .map(|error| {
+            let [start, end] = error.range;
+            let description = if start == end {
+                format!("{}; continued as if it were there", error.message)
+            } else {
+                format!("skipped `{}`: {}", &text[start..end], error.message)
+            };
+            Recovery {
+                range: Some(mapper.map(error.range)),
+                description,
+            }
+        })
+        .collect();
+    for expr in &result.root {
+        find_placeholders(expr, None, &mut recoveries);
+    }
+    recoveries.sort_by_key(|r| r.range.map_or(usize::MAX, |[start, _]| start));
+
+    Ok(RecoveryReport { result, recoveries })
+}
+
+fn find_placeholders(expr: &AstExpr, parent: Option<&AstExpr>, out: &mut Vec<Recovery>) {
+    let range = expr.range();
+    if range.is_none() {
+        out.push(Recovery {
+            range: parent.and_then(AstExpr::range),
+            description: match parent {
+                Some(parent) => format!(
+                    "used a `{}` placeholder for a missing part of `{}`",
+                    expr.kind(),
+                    parent.kind()
+                ),
+                None => format!("used a `{}` placeholder", expr.kind()),
+            },
+        });
+    }
+    let parent = if range.is_some() { Some(expr) } else { parent };
+    for child in expr.children() {
+        find_placeholders(child, parent, out);
+    }
+}
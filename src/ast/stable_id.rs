@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::hash::Hasher;
+
+use siphasher::sip::SipHasher13;
+use typst_syntax::{LinkedNode, SyntaxKind, SyntaxNode};
+
+use crate::cst;
+
+/// The stable ids of the nodes of a tree, laid out like the tree itself so
+/// that a node's id is found by the path of child indices leading to it.
+#[derive(Default)]
+pub struct StableIds {
+    children: Vec<(String, StableIds)>,
+}
+
+impl StableIds {
+    /// The id of `node`, or `None` for the root and detached nodes, and when
+    /// no ids were built.
+    pub fn get(&self, node: &LinkedNode) -> Option<&str> {
+        let mut path = vec![];
+        let mut current = node;
+        while let Some(parent) = current.parent() {
+            path.push(current.index());
+            current = parent;
+        }
+        let mut ids = self;
+        let mut id = None;
+        for index in path.into_iter().rev() {
+            let (child_id, child_ids) = ids.children.get(index)?;
+            id = Some(child_id.as_str());
+            ids = child_ids;
+        }
+        id
+    }
+}
+
+/// Assigns every node an id that survives edits elsewhere in the document.
+///
+/// An id hashes the node's kind, its content with whitespace and comments
+/// ignored, and the kinds of its ancestors. Byte offsets and sibling indices
+/// play no part, so inserting or removing unrelated content keeps ids intact.
+/// Identical siblings are told apart by a counter suffix, which means an id
+/// *can* change when an identical sibling is inserted before the node, and
+/// any edit inside a node changes the ids of the node and its ancestors.
+///
+/// Ids are the same across builds, Rust versions, and platforms: they are
+/// hashed with fixed-key SipHash over kind names and text bytes, never over
+/// `usize`s or `std`'s unspecified `Hash` encodings.
+///
+/// Nodes more than `max_depth` levels below the root, which conversion
+/// leaves out, get no id.
+pub fn build_stable_ids(root: &SyntaxNode, max_depth: usize) -> StableIds {
+    let mut ids = StableIds::default();
+    let mut path = hasher();
+    write_kind(&mut path, root.kind());
+    assign(root, path.finish(), &mut ids, max_depth);
+    ids
+}
+
+/// Records ids for the children of `node`, down to `levels` levels below it,
+/// and returns its content hash.
+fn assign(node: &SyntaxNode, path: u64, ids: &mut StableIds, levels: usize) -> u64 {
+    let mut content = hasher();
+    write_kind(&mut content, node.kind());
+    if node.children().len() == 0 {
+        write_str(&mut content, node.text());
+    }
+    if levels == 0 {
+        // The text stands in for the hashes of the children left out.
+        write_str(&mut content, &cst::node_text(node));
+        return content.finish();
+    }
+
+    let mut seen: HashMap<u64, usize> = HashMap::new();
+    for child in node.children() {
+        let mut child_path = hasher();
+        child_path.write_u64(path);
+        write_kind(&mut child_path, child.kind());
+        let child_path = child_path.finish();
+        let mut child_ids = StableIds::default();
+        let child_content = assign(child, child_path, &mut child_ids, levels - 1);
+        if !child.kind().is_trivia() {
+            content.write_u64(child_content);
+        }
+
+        let mut id = hasher();
+        id.write_u64(child_path);
+        id.write_u64(child_content);
+        let id = id.finish();
+        let count = seen.entry(id).or_default();
+        ids.children.push((format!("{id:016x}-{count}"), child_ids));
+        *count += 1;
+    }
+    content.finish()
+}
+
+fn hasher() -> SipHasher13 {
+    SipHasher13::new_with_keys(0, 0)
+}
+
+fn write_kind(hasher: &mut SipHasher13, kind: SyntaxKind) {
+    write_str(hasher, kind.name());
+}
+
+/// Writes the bytes of `text` followed by a terminator that no UTF-8 text
+/// contains, so that adjacent strings cannot run into each other.
+fn write_str(hasher: &mut SipHasher13, text: &str) {
+    hasher.write(text.as_bytes());
+    hasher.write_u8(0xff);
+}
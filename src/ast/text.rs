@@ -0,0 +1,70 @@
+use super::expr::AstExpr;
+use super::make_ast_result;
+use crate::parse_options::ParseOptions;
+
+/// Flattens markup into its plain text, dropping formatting. Code and math
+/// contribute nothing, except that raw text is kept verbatim.
+pub fn extract_text(exprs: &[AstExpr]) -> String {
+    let mut out = String::new();
+    push_text(exprs, true, &mut out);
+    out
+}
+
+/// Parses `text` and returns the words of its markup for search indexing,
+/// separated by single spaces. Unlike [`extract_text`], raw text is left out
+/// along with code and math.
+///
+/// ```
+/// use typst_ast::ParseOptions;
+/// use typst_ast::ast::to_plaintext;
+///
+/// let text = to_plaintext("= *Hello* world", &ParseOptions::default()).unwrap();
+/// assert_eq!(text, "Hello world");
+/// ```
+pub fn to_plaintext(text: &str, options: &ParseOptions) -> Result<String, String> {
+    let mode = options.mode.unwrap_or_default();
+    let result = make_ast_result(&mode.parse(text), &mode, options)?;
+    let text = markup_text(&result.root);
+    Ok(text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Like [`extract_text`], but without raw text.
+pub(crate) fn markup_text(exprs: &[AstExpr]) -> String {
+    let mut out = String::new();
+    push_text(exprs, false, &mut out);
+    out
+}
+
+fn push_text(exprs: &[AstExpr], raw: bool, out: &mut String) {
+    for expr in exprs {
+        match expr {
+            AstExpr::Text { text, .. } => out.push_str(text),
+            AstExpr::Space { .. } => out.push(' '),
+            AstExpr::Linebreak { .. } => out.push('\n'),
+            AstExpr::Parbreak { .. } => out.push_str("\n\n"),
+            AstExpr::Escape { character, .. } | AstExpr::Shorthand { character, .. } => {
+                out.push(*character)
+            }
+            AstExpr::SmartQuote { double, .. } => out.push(if *double { '"' } else { '\'' }),
+            AstExpr::Raw { lines, .. } if raw => out.push_str(&lines.join("\n")),
+            AstExpr::Link { url, body, .. } => match body {
+                Some(body) => push_text(body, raw, out),
+                None => out.push_str(url),
+            },
+            AstExpr::Strong { body, .. }
+            | AstExpr::Emph { body, .. }
+            | AstExpr::Heading { body, .. }
+            | AstExpr::ListItem { body, .. }
+            | AstExpr::EnumItem { body, .. }
+            | AstExpr::ContentBlock { body, .. } => push_text(body, raw, out),
+            AstExpr::TermItem {
+                term, description, ..
+            } => {
+                push_text(term, raw, out);
+                out.push_str(": ");
+                push_text(description, raw, out);
+            }
+            _ => {}
+        }
+    }
+}
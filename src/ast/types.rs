@@ -3,14 +3,44 @@ use serde::Serialize;
 use super::expr::AstExpr;
 use super::offset::Range;
 
-#[derive(Serialize)]
-#[serde(tag = "kind", rename_all = "camelCase")]
+/// Optional per-node data shared by every expression, flattened into the
+/// node's JSON object. Fields are omitted while unset.
+#[derive(Serialize, Clone, Default, Debug, PartialEq)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct NodeMeta {
+    /// Only filled in with `ParseOptions::stable_ids`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, ts(optional))]
+    pub stable_id: Option<String>,
+    /// Whether the expression is embedded in markup or math behind a `#`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    #[cfg_attr(test, ts(as = "Option<bool>", optional))]
+    pub hash_prefixed: bool,
+    /// How many nodes enclose this one; only with `ParseOptions::node_depth`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, ts(optional))]
+    pub node_depth: Option<usize>,
+    /// The text the node covers; only with `ParseOptions::include_source`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(test, ts(optional))]
+    pub source: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
 pub enum MathTextKind {
     Character { value: char },
     Number { value: String },
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(test, derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum Unit {
     Pt,
@@ -24,7 +54,8 @@ pub enum Unit {
     Percent,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum UnOp {
     Pos,
@@ -32,7 +63,19 @@ pub enum UnOp {
     Not,
 }
 
-#[derive(Serialize)]
+impl UnOp {
+    /// The name the operator is serialized as.
+    pub fn name(self) -> &'static str {
+        match self {
+            UnOp::Pos => "pos",
+            UnOp::Neg => "neg",
+            UnOp::Not => "not",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(ts_rs::TS))]
 #[serde(rename_all = "camelCase")]
 pub enum BinOp {
     Add,
@@ -56,20 +99,59 @@ pub enum BinOp {
     DivAssign,
 }
 
-#[derive(Serialize)]
-#[serde(tag = "kind", rename_all = "camelCase")]
+impl BinOp {
+    /// The name the operator is serialized as.
+    pub fn name(self) -> &'static str {
+        match self {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::Div => "div",
+            BinOp::And => "and",
+            BinOp::Or => "or",
+            BinOp::Eq => "eq",
+            BinOp::Neq => "neq",
+            BinOp::Lt => "lt",
+            BinOp::Leq => "leq",
+            BinOp::Gt => "gt",
+            BinOp::Geq => "geq",
+            BinOp::Assign => "assign",
+            BinOp::In => "in",
+            BinOp::NotIn => "notIn",
+            BinOp::AddAssign => "addAssign",
+            BinOp::SubAssign => "subAssign",
+            BinOp::MulAssign => "mulAssign",
+            BinOp::DivAssign => "divAssign",
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
 pub enum ArrayItem {
     Pos {
         expr: AstExpr,
     },
     Spread {
+        /// The `..` token.
+        spread_range: Range,
         expr: AstExpr,
         sink_ident: Option<String>,
     },
 }
 
-#[derive(Serialize)]
-#[serde(tag = "kind", rename_all = "camelCase")]
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
 pub enum DictItem {
     Named {
         name: String,
@@ -80,13 +162,20 @@ pub enum DictItem {
         expr: AstExpr,
     },
     Spread {
+        /// The `..` token.
+        spread_range: Range,
         expr: AstExpr,
         sink_ident: Option<String>,
     },
 }
 
-#[derive(Serialize)]
-#[serde(tag = "kind", rename_all = "camelCase")]
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
 pub enum Arg {
     Pos {
         expr: AstExpr,
@@ -96,13 +185,20 @@ pub enum Arg {
         expr: AstExpr,
     },
     Spread {
+        /// The `..` token.
+        spread_range: Range,
         expr: AstExpr,
         sink_ident: Option<String>,
     },
 }
 
-#[derive(Serialize)]
-#[serde(tag = "kind", rename_all = "camelCase")]
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
 pub enum Param {
     Pos {
         pattern: Pattern,
@@ -117,8 +213,13 @@ pub enum Param {
     },
 }
 
-#[derive(Serialize)]
-#[serde(tag = "kind", rename_all = "camelCase")]
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
 pub enum Pattern {
     Normal {
         expr: Box<AstExpr>,
@@ -135,30 +236,118 @@ pub enum Pattern {
     },
 }
 
-#[derive(Serialize)]
-#[serde(tag = "kind", rename_all = "camelCase")]
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
 pub enum DestructuringItem {
     Pattern { pattern: Pattern },
     Named { name: String, pattern: Pattern },
     Spread { sink_ident: Option<String> },
 }
 
-#[derive(Serialize)]
-#[serde(tag = "kind", rename_all = "camelCase")]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum BodyKind {
+    Content,
+    Code,
+    Expr,
+}
+
+/// The coarse shape of a [`Pattern`]; a `normal` pattern is reported as
+/// `ident`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum PatternKind {
+    Ident,
+    Destructuring,
+    Placeholder,
+    Paren,
+}
+
+/// How far a `set` or `show` rule reaches: the rest of its enclosing block
+/// (`local`) or the rest of the document (`block`).
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum RuleScope {
+    Local,
+    Block,
+}
+
+/// The kind of delimiter pair around a `MathDelimited` body.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum MathDelimiter {
+    Paren,
+    Bracket,
+    Brace,
+    Bar,
+    Custom,
+}
+
+/// What a `MathIdent` most likely names, judged by a bundled table.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum MathIdentRole {
+    Function,
+    Symbol,
+    Variable,
+}
+
+/// Whether a heading was written as `= Title` markup or as a `heading` call,
+/// which only becomes a heading with `ParseOptions::element_calls`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum HeadingSyntax {
+    Markup,
+    Func,
+}
+
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
 pub enum LetBindingKind {
-    Normal { pattern: Pattern },
-    Closure { name: String },
+    Normal {
+        pattern: Pattern,
+        pattern_kind: PatternKind,
+    },
+    Closure {
+        name: String,
+    },
 }
 
-#[derive(Serialize)]
-#[serde(tag = "kind", rename_all = "camelCase")]
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
 pub enum Imports {
     Wildcard,
     Items { items: Vec<ImportItem> },
 }
 
-#[derive(Serialize)]
-#[serde(tag = "kind", rename_all = "camelCase")]
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
 pub enum ImportItem {
     Simple {
         path: Vec<String>,
@@ -167,6 +356,8 @@ pub enum ImportItem {
     Renamed {
         path: Vec<String>,
         original_name: String,
+        original_name_range: Range,
         new_name: String,
+        new_name_range: Range,
     },
 }
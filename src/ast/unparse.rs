@@ -0,0 +1,425 @@
+use super::expr::AstExpr;
+use super::types::{
+    Arg, ArrayItem, BinOp, DestructuringItem, DictItem, LetBindingKind, Param, Pattern, UnOp, Unit,
+};
+
+/// Regenerates code-mode source for `expr`, without a leading `#`.
+///
+/// Literals, identifiers, operators, calls, field accesses, collections,
+/// closures, and `let` bindings are supported; everything else, including all
+/// markup and math nodes, is an error for now. The output is normalized
+/// rather than a copy of the original source, but parses back to the same
+/// tree. Parentheses are only added where a tree built by hand needs them to
+/// keep its structure.
+pub fn unparse(expr: &AstExpr) -> Result<String, String> {
+    let mut out = String::new();
+    write_expr(expr, &mut out)?;
+    Ok(out)
+}
+
+fn write_expr(expr: &AstExpr, out: &mut String) -> Result<(), String> {
+    match expr {
+        AstExpr::None { .. } => out.push_str("none"),
+        AstExpr::Auto { .. } => out.push_str("auto"),
+        AstExpr::Bool { value, .. } => out.push_str(if *value { "true" } else { "false" }),
+        AstExpr::Int { value, .. } => out.push_str(&value.to_string()),
+        AstExpr::Float { value, .. } => out.push_str(&finite(*value, "float")?),
+        AstExpr::Numeric { value, unit, .. } => {
+            out.push_str(&finite(*value, "numeric")?);
+            out.push_str(unit_suffix(*unit));
+        }
+        AstExpr::Str { value, .. } => write_str(value, out),
+        AstExpr::Ident { name, .. } => out.push_str(name),
+        AstExpr::Parenthesized { expr, .. } => {
+            out.push('(');
+            write_expr(expr, out)?;
+            out.push(')');
+        }
+        AstExpr::Unary { op, expr, .. } => {
+            out.push_str(match op {
+                UnOp::Pos => "+",
+                UnOp::Neg => "-",
+                UnOp::Not => "not ",
+            });
+            write_operand(expr, unary_precedence(*op) > precedence(expr), out)?;
+        }
+        AstExpr::Binary { op, lhs, rhs, .. } => {
+            let prec = binary_precedence(*op);
+            let right_assoc = prec == ASSIGN_PRECEDENCE;
+            let lhs_prec = precedence(lhs);
+            let rhs_prec = precedence(rhs);
+            write_operand(
+                lhs,
+                lhs_prec < prec || (lhs_prec == prec && right_assoc),
+                out,
+            )?;
+            out.push_str(&format!(" {} ", binary_symbol(*op)));
+            write_operand(
+                rhs,
+                rhs_prec < prec || (rhs_prec == prec && !right_assoc),
+                out,
+            )?;
+        }
+        AstExpr::FieldAccess { target, field, .. } => {
+            write_operand(target, precedence(target) != ATOM_PRECEDENCE, out)?;
+            out.push('.');
+            out.push_str(field);
+        }
+        AstExpr::FuncCall { callee, args, .. } => {
+            write_operand(callee, precedence(callee) != ATOM_PRECEDENCE, out)?;
+            out.push('(');
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                match arg {
+                    Arg::Pos { expr } => write_expr(expr, out)?,
+                    Arg::Named { name, expr } => {
+                        out.push_str(name);
+                        out.push_str(": ");
+                        write_expr(expr, out)?;
+                    }
+                    Arg::Spread { expr, .. } => {
+                        out.push_str("..");
+                        write_expr(expr, out)?;
+                    }
+                }
+            }
+            out.push(')');
+        }
+        AstExpr::Array { items, .. } => {
+            out.push('(');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                match item {
+                    ArrayItem::Pos { expr } => write_expr(expr, out)?,
+                    ArrayItem::Spread { expr, .. } => {
+                        out.push_str("..");
+                        write_expr(expr, out)?;
+                    }
+                }
+            }
+            // A single item needs a comma to not be read as parenthesized.
+            if let [ArrayItem::Pos { .. }] = items.as_slice() {
+                out.push(',');
+            }
+            out.push(')');
+        }
+        AstExpr::Dict { items, .. } => {
+            if items.is_empty() {
+                out.push_str("(:)");
+                return Ok(());
+            }
+            out.push('(');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                match item {
+                    DictItem::Named { name, expr } => {
+                        out.push_str(name);
+                        out.push_str(": ");
+                        write_expr(expr, out)?;
+                    }
+                    DictItem::Keyed { key, expr } => {
+                        write_expr(key, out)?;
+                        out.push_str(": ");
+                        write_expr(expr, out)?;
+                    }
+                    DictItem::Spread { expr, .. } => {
+                        out.push_str("..");
+                        write_expr(expr, out)?;
+                    }
+                }
+            }
+            out.push(')');
+        }
+        AstExpr::Closure { params, body, .. } => {
+            write_params(params, out)?;
+            out.push_str(" => ");
+            write_expr(body, out)?;
+        }
+        AstExpr::LetBinding {
+            binding_kind, init, ..
+        } => {
+            out.push_str("let ");
+            match (binding_kind, init.as_deref()) {
+                (LetBindingKind::Closure { name }, Some(AstExpr::Closure { params, body, .. })) => {
+                    out.push_str(name);
+                    write_params(params, out)?;
+                    out.push_str(" = ");
+                    write_expr(body, out)?;
+                }
+                (LetBindingKind::Closure { .. }, _) => {
+                    return Err("a closure binding needs a closure".into());
+                }
+                (LetBindingKind::Normal { pattern, .. }, init) => {
+                    write_pattern(pattern, out)?;
+                    if let Some(init) = init {
+                        out.push_str(" = ");
+                        write_expr(init, out)?;
+                    }
+                }
+            }
+        }
+        _ => return Err(format!("cannot unparse `{}` nodes yet", expr.kind())),
+    }
+    Ok(())
+}
+
+fn write_operand(expr: &AstExpr, parenthesize: bool, out: &mut String) -> Result<(), String> {
+    if parenthesize {
+        out.push('(');
+    }
+    write_expr(expr, out)?;
+    if parenthesize {
+        out.push(')');
+    }
+    Ok(())
+}
+
+fn write_params(params: &[Param], out: &mut String) -> Result<(), String> {
+    out.push('(');
+    for (i, param) in params.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        match param {
+            Param::Pos { pattern } => write_pattern(pattern, out)?,
+            Param::Named { name, expr } => {
+                out.push_str(name);
+                out.push_str(": ");
+                write_expr(expr, out)?;
+            }
+            Param::Spread { sink_ident, .. } => {
+                out.push_str("..");
+                out.push_str(sink_ident.as_deref().unwrap_or_default());
+            }
+        }
+    }
+    out.push(')');
+    Ok(())
+}
+
+fn write_pattern(pattern: &Pattern, out: &mut String) -> Result<(), String> {
+    match pattern {
+        Pattern::Normal { expr } => write_expr(expr, out)?,
+        Pattern::Placeholder { .. } => out.push('_'),
+        Pattern::Parenthesized { expr } => {
+            out.push('(');
+            write_expr(expr, out)?;
+            out.push(')');
+        }
+        Pattern::Destructuring { items, .. } => {
+            out.push('(');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                match item {
+                    DestructuringItem::Pattern { pattern } => write_pattern(pattern, out)?,
+                    DestructuringItem::Named { name, pattern } => {
+                        out.push_str(name);
+                        out.push_str(": ");
+                        write_pattern(pattern, out)?;
+                    }
+                    DestructuringItem::Spread { sink_ident } => {
+                        out.push_str("..");
+                        out.push_str(sink_ident.as_deref().unwrap_or_default());
+                    }
+                }
+            }
+            if let [DestructuringItem::Pattern { .. }] = items.as_slice() {
+                out.push(',');
+            }
+            out.push(')');
+        }
+    }
+    Ok(())
+}
+
+fn write_str(value: &str, out: &mut String) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn finite(value: f64, kind: &str) -> Result<String, String> {
+    if value.is_finite() {
+        // `Debug` keeps a fractional part or exponent, so `1000.0` stays a
+        // float instead of turning into an int.
+        Ok(format!("{value:?}"))
+    } else {
+        Err(format!("cannot unparse a non-finite {kind}"))
+    }
+}
+
+fn unit_suffix(unit: Unit) -> &'static str {
+    match unit {
+        Unit::Pt => "pt",
+        Unit::Mm => "mm",
+        Unit::Cm => "cm",
+        Unit::In => "in",
+        Unit::Rad => "rad",
+        Unit::Deg => "deg",
+        Unit::Em => "em",
+        Unit::Fr => "fr",
+        Unit::Percent => "%",
+    }
+}
+
+fn binary_symbol(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::And => "and",
+        BinOp::Or => "or",
+        BinOp::Eq => "==",
+        BinOp::Neq => "!=",
+        BinOp::Lt => "<",
+        BinOp::Leq => "<=",
+        BinOp::Gt => ">",
+        BinOp::Geq => ">=",
+        BinOp::Assign => "=",
+        BinOp::In => "in",
+        BinOp::NotIn => "not in",
+        BinOp::AddAssign => "+=",
+        BinOp::SubAssign => "-=",
+        BinOp::MulAssign => "*=",
+        BinOp::DivAssign => "/=",
+    }
+}
+
+/// The precedence of anything that never needs parentheses.
+const ATOM_PRECEDENCE: u8 = u8::MAX;
+
+/// The precedence of the assignment operators, the only right-associative
+/// ones.
+const ASSIGN_PRECEDENCE: u8 = 1;
+
+/// Precedences as in Typst's parser.
+fn binary_precedence(op: BinOp) -> u8 {
+    match op {
+        BinOp::Mul | BinOp::Div => 6,
+        BinOp::Add | BinOp::Sub => 5,
+        BinOp::Eq
+        | BinOp::Neq
+        | BinOp::Lt
+        | BinOp::Leq
+        | BinOp::Gt
+        | BinOp::Geq
+        | BinOp::In
+        | BinOp::NotIn => 4,
+        BinOp::And => 3,
+        BinOp::Or => 2,
+        BinOp::Assign
+        | BinOp::AddAssign
+        | BinOp::SubAssign
+        | BinOp::MulAssign
+        | BinOp::DivAssign => ASSIGN_PRECEDENCE,
+    }
+}
+
+fn unary_precedence(op: UnOp) -> u8 {
+    match op {
+        UnOp::Pos | UnOp::Neg => 7,
+        UnOp::Not => 4,
+    }
+}
+
+fn precedence(expr: &AstExpr) -> u8 {
+    match expr {
+        AstExpr::Binary { op, .. } => binary_precedence(*op),
+        AstExpr::Unary { op, .. } => unary_precedence(*op),
+        // Closures and bindings extend as far to the right as they can.
+        AstExpr::Closure { .. } | AstExpr::LetBinding { .. } => 0,
+        _ => ATOM_PRECEDENCE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::golden::{CORPUS, assert_round_trip};
+    use crate::ast::{make_ast_result, walk_mut};
+    use crate::parse_mode::ParseMode;
+    use crate::parse_options::ParseOptions;
+
+    fn parse(text: &str, mode: ParseMode) -> Vec<AstExpr> {
+        let root = mode.parse(text);
+        make_ast_result(&root, &mode, &ParseOptions::default())
+            .unwrap()
+            .root
+    }
+
+    fn unparse_all(text: &str, mode: ParseMode) -> Result<String, String> {
+        let prefix = if mode == ParseMode::Markup { "#" } else { "" };
+        let lines = parse(text, mode)
+            .iter()
+            .map(|expr| Ok(format!("{prefix}{}", unparse(expr)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(lines.join("\n"))
+    }
+
+    #[test]
+    fn round_trips_code() {
+        for text in [
+            "#(1 + 2 * 3)",
+            "#let x = (a: 1, b: 2)",
+            "#let f(x, y: 2, ..rest) = -x.len() + y",
+            "#let (a, (b, _), ..c) = (1, (2,), (:), \"q\\\"\\n\")",
+            "#(not a in b and c != 2.5em or 1e3 < -50%)",
+        ] {
+            assert_round_trip(text, ParseMode::Markup, |text| {
+                unparse_all(text, ParseMode::Markup).unwrap()
+            });
+        }
+
+        // Code snippets of the corpus that only use supported nodes.
+        let mut supported = 0;
+        for &(mode, text) in CORPUS {
+            if mode == ParseMode::Code && unparse_all(text, mode).is_ok() {
+                assert_round_trip(text, mode, |text| unparse_all(text, mode).unwrap());
+                supported += 1;
+            }
+        }
+        assert!(supported > 0);
+    }
+
+    #[test]
+    fn parenthesizes_by_precedence() {
+        let mut exprs = parse("(1 + 2) * 3 - (4 - 5) + -(a.b)", ParseMode::Code);
+        for expr in &mut exprs {
+            walk_mut(expr, &mut |expr| {
+                if let AstExpr::Parenthesized { expr: inner, .. } = expr {
+                    *expr = (**inner).clone();
+                }
+            });
+        }
+        assert_eq!(unparse(&exprs[0]).unwrap(), "(1 + 2) * 3 - (4 - 5) + -a.b");
+    }
+
+    #[test]
+    fn rejects_unsupported_nodes() {
+        let exprs = parse("*strong* #if x [a]", ParseMode::Markup);
+        assert_eq!(
+            unparse(&exprs[0]).unwrap_err(),
+            "cannot unparse `strong` nodes yet"
+        );
+        assert!(unparse(&exprs[2]).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use super::expr::AstExpr;
+
+/// Calls `f` on `expr` and then on every node below it, in source order.
+/// This reaches expressions inside arguments, parameters, patterns, and
+/// collection items too.
+pub fn walk<'a>(expr: &'a AstExpr, f: &mut dyn FnMut(&'a AstExpr)) {
+    f(expr);
+    for child in expr.children() {
+        walk(child, f);
+    }
+}
+
+/// Like [`walk`], but allows modifying each node. Nodes are visited before
+/// their children, so the children seen are those of the modified node.
+pub fn walk_mut(expr: &mut AstExpr, f: &mut dyn FnMut(&mut AstExpr)) {
+    f(expr);
+    for child in expr.children_mut() {
+        walk_mut(child, f);
+    }
+}
+
+/// Keeps the nodes of `exprs` whose kind is one of `kinds`, replacing every
+/// other node by the outermost matching nodes below it, in source order. A
+/// kept node stays whole, so it still holds nodes of other kinds, including
+/// nested matches.
+pub fn retain_kinds(exprs: Vec<AstExpr>, kinds: &[String]) -> Vec<AstExpr> {
+    let mut kept = vec![];
+    for expr in exprs {
+        collect_kinds(expr, kinds, &mut kept);
+    }
+    kept
+}
+
+fn collect_kinds(mut expr: AstExpr, kinds: &[String], kept: &mut Vec<AstExpr>) {
+    if kinds.iter().any(|kind| kind == expr.kind()) {
+        kept.push(expr);
+        return;
+    }
+    for child in expr.children_mut() {
+        // The node is dropped, so its children can be moved out of it.
+        let child = std::mem::replace(
+            child,
+            AstExpr::None {
+                range: None,
+                meta: Default::default(),
+            },
+        );
+        collect_kinds(child, kinds, kept);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParseOptions;
+    use crate::ast::make_ast_result;
+    use crate::parse_mode::ParseMode;
+
+    const SAMPLE: &str = "#let f(x, (a, b), y: 1, ..rest) = x + a * b\n\
+                          = Hi *there* _you_\n\
+                          #f(1, ..(2, 3), k: (a: 4))";
+
+    fn sample() -> Vec<AstExpr> {
+        let root = ParseMode::Markup.parse(SAMPLE);
+        make_ast_result(&root, &ParseMode::Markup, &ParseOptions::default())
+            .unwrap()
+            .root
+    }
+
+    #[test]
+    fn visits_every_node() {
+        let mut kinds = vec![];
+        for expr in &sample() {
+            walk(expr, &mut |expr| kinds.push(expr.kind()));
+        }
+        assert_eq!(kinds.len(), 29);
+        // Parameters, destructuring patterns, spread and named arguments, and
+        // collection items are all reached.
+        let count = |kind| kinds.iter().filter(|&&k| k == kind).count();
+        assert_eq!(count("ident"), 8);
+        assert_eq!(count("int"), 5);
+    }
+
+    #[test]
+    fn retains_outermost_nodes_of_kinds() {
+        let root = ParseMode::Markup.parse("*#f(g(1))* and #h(x)");
+        let exprs = make_ast_result(&root, &ParseMode::Markup, &ParseOptions::default())
+            .unwrap()
+            .root;
+        let kept = retain_kinds(exprs, &["funcCall".into()]);
+        let callees: Vec<_> = kept
+            .iter()
+            .map(|expr| match expr {
+                AstExpr::FuncCall { callee, .. } => callee.as_ref(),
+                _ => panic!("expected only calls"),
+            })
+            .collect();
+        assert!(
+            matches!(callees[..], [AstExpr::Ident { name: f, .. }, AstExpr::Ident { name: h, .. }] if f == "f" && h == "h")
+        );
+        // The nested call stays in the arguments of the outer one.
+        let mut calls = 0;
+        walk(&kept[0], &mut |expr| {
+            calls += usize::from(expr.kind() == "funcCall")
+        });
+        assert_eq!(calls, 2);
+    }
+
+    #[test]
+    fn modifies_every_node() {
+        let mut exprs = sample();
+        for expr in &mut exprs {
+            walk_mut(expr, &mut |expr| {
+                if let AstExpr::Int { value, .. } = expr {
+                    *value *= 10;
+                }
+            });
+        }
+        let mut values = vec![];
+        for expr in &exprs {
+            walk(expr, &mut |expr| {
+                if let AstExpr::Int { value, .. } = expr {
+                    values.push(*value);
+                }
+            });
+        }
+        assert_eq!(values, [10, 10, 20, 30, 40]);
+    }
+}
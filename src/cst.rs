@@ -1,66 +1,1214 @@
+use std::collections::HashSet;
+
 use serde::Serialize;
-use typst_syntax::LinkedNode;
+use typst_syntax::ast::{self, AstNode};
+use typst_syntax::{LinkedChildren, LinkedNode, Side, SyntaxKind, SyntaxNode};
 
-#[derive(Serialize)]
+use crate::newlines::{LineEnding, line_ending};
+use crate::parse_mode::ParseMode;
+use crate::parse_options::ParseOptions;
+use crate::range_mapper::RangeMapper;
+
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
 pub struct ParseError {
     pub message: String,
     pub range: [usize; 2],
+    /// Suggestions the parser attached to the error.
+    pub hints: Vec<String>,
+    pub severity: Severity,
+}
+
+/// How serious a diagnostic is. `typst-syntax` currently only reports
+/// errors; the variant exists so warnings can be told apart once it does.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Serialize)]
+pub struct CstNode {
+    pub kind: String,
+    /// `None` only for placeholders.
+    pub range: Option<[usize; 2]>,
+    /// The length of the node's text in bytes, only with
+    /// `ParseOptions::include_len`. Set for placeholders too.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub len: Option<usize>,
+    pub text: Option<String>,
+    pub children: Vec<CstNode>,
+    /// Whether the node stands in for a missing part of its parent, only
+    /// with `ParseOptions::include_placeholders`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub placeholder: bool,
+}
+
+impl Drop for CstNode {
+    /// Drops the descendants with an explicit stack instead of one nested
+    /// drop call per level, so deep trees cannot overflow the native stack.
+    fn drop(&mut self) {
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
+        }
+    }
 }
 
 #[derive(Serialize)]
-struct CstNode {
-    kind: String,
-    range: [usize; 2],
-    text: Option<String>,
-    children: Vec<CstNode>,
+#[serde(rename_all = "camelCase")]
+pub struct CstParseResult {
+    pub root: CstNode,
+    /// Whether the text parsed without errors, so that `errors` is empty.
+    pub ok: bool,
+    pub errors: Vec<ParseError>,
+    pub line_ending: LineEnding,
+    /// The mode the text was parsed in, only with `ParseMode::Auto`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detected_mode: Option<ParseMode>,
 }
 
+/// A CST node in a flat list, see [`CstParseResult::into_flat`].
 #[derive(Serialize)]
-struct CstParseResult {
-    root: CstNode,
-    errors: Vec<ParseError>,
+pub struct FlatCstNode {
+    /// The index of the node in the list.
+    pub id: usize,
+    /// The index of the enclosing node, or -1 for the root.
+    pub parent: isize,
+    pub kind: String,
+    pub range: Option<[usize; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub len: Option<usize>,
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub placeholder: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlatCstParseResult {
+    /// The nodes in pre-order, so every node comes after its parent and
+    /// siblings keep their order.
+    pub nodes: Vec<FlatCstNode>,
+    pub ok: bool,
+    pub errors: Vec<ParseError>,
+    pub line_ending: LineEnding,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detected_mode: Option<ParseMode>,
+}
+
+impl CstParseResult {
+    /// Lists the nodes of the tree in pre-order, each pointing to its parent
+    /// by index, for consumers that would rather not walk a nested tree.
+    pub fn into_flat(self) -> FlatCstParseResult {
+        let mut nodes = vec![];
+        // An explicit stack, as the tree can be up to `max_depth` deep.
+        let mut stack = vec![(self.root, -1)];
+        while let Some((mut node, parent)) = stack.pop() {
+            let id = nodes.len();
+            nodes.push(FlatCstNode {
+                id,
+                parent,
+                kind: std::mem::take(&mut node.kind),
+                range: node.range,
+                len: node.len,
+                text: node.text.take(),
+                placeholder: node.placeholder,
+            });
+            let children = std::mem::take(&mut node.children).into_iter().rev();
+            stack.extend(children.map(|child| (child, id as isize)));
+        }
+        FlatCstParseResult {
+            nodes,
+            ok: self.ok,
+            errors: self.errors,
+            line_ending: self.line_ending,
+            detected_mode: self.detected_mode,
+        }
+    }
+}
+
+struct CstBuilder<'a> {
+    options: &'a ParseOptions,
+    mapper: RangeMapper,
+}
+
+impl CstBuilder<'_> {
+    /// Converts `node` and its descendants, dropping the children of nodes
+    /// `max_depth` levels below it. Uses an explicit stack instead of
+    /// recursion, so deep trees cannot overflow the native stack.
+    fn node_to_cst(&self, node: &LinkedNode) -> CstNode {
+        // Each frame holds a node converted so far and the children of it
+        // still to visit.
+        let mut stack = vec![self.frame(node, 0)];
+        loop {
+            let frame = stack.last_mut().unwrap();
+            if frame.depth < self.options.max_depth()
+                && let Some(child) = frame.children.next()
+            {
+                let depth = frame.depth + 1;
+                stack.push(self.frame(&child, depth));
+                continue;
+            }
+            let Frame {
+                mut cst,
+                node,
+                depth,
+                ..
+            } = stack.pop().unwrap();
+            if self.options.include_placeholders && depth < self.options.max_depth() {
+                let placeholders = missing_parts(node)
+                    .into_iter()
+                    .map(|node| placeholder_to_cst(node, self.options));
+                cst.children
+                    .extend(placeholders.filter(|cst| self.keep(cst)));
+            }
+            let done = cst;
+            let Some(parent) = stack.last_mut() else {
+                return done;
+            };
+            if self.keep(&done) {
+                parent.cst.children.push(done);
+            }
+        }
+    }
+
+    fn frame<'a>(&self, node: &LinkedNode<'a>, depth: usize) -> Frame<'a> {
+        let children = node.children();
+        let mut cst = self.shallow_node_to_cst(node);
+        if depth < self.options.max_depth() {
+            cst.children.reserve_exact(children.len());
+        }
+        Frame {
+            cst,
+            node: node.get(),
+            children,
+            depth,
+        }
+    }
+
+    /// Converts `node` without its children.
+    fn shallow_node_to_cst(&self, node: &LinkedNode) -> CstNode {
+        let text = node.get().text();
+        let omit_text = text.is_empty()
+            || (self.options.omit_whitespace_text && text.chars().all(char::is_whitespace));
+        CstNode {
+            kind: format!("{:?}", node.get().kind()),
+            range: Some(
+                self.mapper
+                    .map([node.offset(), node.offset() + node.get().len()]),
+            ),
+            len: self.options.include_len.then(|| node.get().len()),
+            text: if omit_text {
+                None
+            } else {
+                Some(text.to_string())
+            },
+            children: vec![],
+            placeholder: false,
+        }
+    }
+
+    /// Writes `node` and its descendants one per line, indented by depth.
+    fn write_pretty(&self, node: &LinkedNode, out: &mut String) {
+        let mut stack = vec![(node.clone(), 0)];
+        while let Some((node, depth)) = stack.pop() {
+            let cst = self.shallow_node_to_cst(&node);
+            let [start, end] = cst.range.unwrap_or_default();
+            out.push_str(&format!(
+                "{:indent$}{} [{start}..{end}]",
+                "",
+                cst.kind,
+                indent = 2 * depth
+            ));
+            if let Some(text) = &cst.text {
+                out.push_str(&format!(" {:?}", truncate(text, PRETTY_TEXT_LIMIT)));
+            }
+            out.push('\n');
+            if depth < self.options.max_depth() {
+                stack.extend(node.children().rev().map(|child| (child, depth + 1)));
+            }
+        }
+    }
+
+    /// Whether a converted node stays in the tree: always, unless
+    /// `cst_kinds` is set and neither the node nor any of its remaining
+    /// descendants has one of the listed kinds.
+    fn keep(&self, cst: &CstNode) -> bool {
+        match &self.options.cst_kinds {
+            Some(kinds) => !cst.children.is_empty() || kinds.contains(&cst.kind),
+            None => true,
+        }
+    }
+}
+
+struct Frame<'a> {
+    cst: CstNode,
+    node: &'a SyntaxNode,
+    children: LinkedChildren<'a>,
+    depth: usize,
 }
 
-fn node_to_cst(node: &LinkedNode) -> CstNode {
-    let text = node.get().text();
+fn placeholder_to_cst(node: &SyntaxNode, options: &ParseOptions) -> CstNode {
     CstNode {
-        kind: format!("{:?}", node.get().kind()),
-        range: [node.offset(), node.offset() + node.get().len()],
-        text: if text.is_empty() {
-            None
-        } else {
-            Some(text.to_string())
-        },
-        children: node.children().map(|c| node_to_cst(&c)).collect(),
+        kind: format!("{:?}", node.kind()),
+        range: None,
+        len: options.include_len.then(|| node.len()),
+        text: None,
+        children: vec![],
+        placeholder: true,
     }
 }
 
+/// The placeholders `typst-syntax` hands out for required parts of `node`
+/// that are missing from its children, like the transform of `#show:`, in
+/// source order. They go after the node's own children.
+fn missing_parts(node: &SyntaxNode) -> Vec<&SyntaxNode> {
+    let parts: Vec<&SyntaxNode> = match node.kind() {
+        SyntaxKind::Strong => parts(node.cast::<ast::Strong>(), |v| vec![v.body().to_untyped()]),
+        SyntaxKind::Emph => parts(node.cast::<ast::Emph>(), |v| vec![v.body().to_untyped()]),
+        SyntaxKind::Heading => parts(node.cast::<ast::Heading>(), |v| vec![v.body().to_untyped()]),
+        SyntaxKind::ListItem => parts(node.cast::<ast::ListItem>(), |v| {
+            vec![v.body().to_untyped()]
+        }),
+        SyntaxKind::EnumItem => parts(node.cast::<ast::EnumItem>(), |v| {
+            vec![v.body().to_untyped()]
+        }),
+        SyntaxKind::TermItem => parts(node.cast::<ast::TermItem>(), |v| {
+            vec![v.term().to_untyped(), v.description().to_untyped()]
+        }),
+        SyntaxKind::MathAttach => parts(node.cast::<ast::MathAttach>(), |v| {
+            vec![v.base().to_untyped()]
+        }),
+        SyntaxKind::MathFrac => parts(node.cast::<ast::MathFrac>(), |v| {
+            vec![v.num().to_untyped(), v.denom().to_untyped()]
+        }),
+        SyntaxKind::MathRoot => parts(node.cast::<ast::MathRoot>(), |v| {
+            vec![v.radicand().to_untyped()]
+        }),
+        SyntaxKind::Parenthesized => parts(node.cast::<ast::Parenthesized>(), |v| {
+            vec![v.expr().to_untyped()]
+        }),
+        SyntaxKind::Named => parts(node.cast::<ast::Named>(), |v| {
+            vec![v.name().to_untyped(), v.expr().to_untyped()]
+        }),
+        SyntaxKind::Keyed => parts(node.cast::<ast::Keyed>(), |v| {
+            vec![v.key().to_untyped(), v.expr().to_untyped()]
+        }),
+        SyntaxKind::Spread => parts(node.cast::<ast::Spread>(), |v| vec![v.expr().to_untyped()]),
+        SyntaxKind::Unary => parts(node.cast::<ast::Unary>(), |v| vec![v.expr().to_untyped()]),
+        SyntaxKind::Binary => parts(node.cast::<ast::Binary>(), |v| {
+            vec![v.lhs().to_untyped(), v.rhs().to_untyped()]
+        }),
+        SyntaxKind::FieldAccess => parts(node.cast::<ast::FieldAccess>(), |v| {
+            vec![v.target().to_untyped(), v.field().to_untyped()]
+        }),
+        SyntaxKind::FuncCall => parts(node.cast::<ast::FuncCall>(), |v| {
+            vec![v.callee().to_untyped(), v.args().to_untyped()]
+        }),
+        SyntaxKind::Closure => parts(node.cast::<ast::Closure>(), |v| {
+            vec![v.params().to_untyped(), v.body().to_untyped()]
+        }),
+        SyntaxKind::LetBinding => parts(node.cast::<ast::LetBinding>(), |v| match v.kind() {
+            ast::LetBindingKind::Normal(pattern) => vec![pattern.to_untyped()],
+            ast::LetBindingKind::Closure(_) => vec![],
+        }),
+        SyntaxKind::DestructAssignment => parts(node.cast::<ast::DestructAssignment>(), |v| {
+            vec![v.pattern().to_untyped(), v.value().to_untyped()]
+        }),
+        SyntaxKind::SetRule => parts(node.cast::<ast::SetRule>(), |v| {
+            vec![v.target().to_untyped(), v.args().to_untyped()]
+        }),
+        SyntaxKind::ShowRule => parts(node.cast::<ast::ShowRule>(), |v| {
+            vec![v.transform().to_untyped()]
+        }),
+        SyntaxKind::Contextual => parts(node.cast::<ast::Contextual>(), |v| {
+            vec![v.body().to_untyped()]
+        }),
+        SyntaxKind::Conditional => parts(node.cast::<ast::Conditional>(), |v| {
+            vec![v.condition().to_untyped(), v.if_body().to_untyped()]
+        }),
+        SyntaxKind::WhileLoop => parts(node.cast::<ast::WhileLoop>(), |v| {
+            vec![v.condition().to_untyped(), v.body().to_untyped()]
+        }),
+        SyntaxKind::ForLoop => parts(node.cast::<ast::ForLoop>(), |v| {
+            vec![
+                v.pattern().to_untyped(),
+                v.iterable().to_untyped(),
+                v.body().to_untyped(),
+            ]
+        }),
+        SyntaxKind::ModuleImport => parts(node.cast::<ast::ModuleImport>(), |v| {
+            vec![v.source().to_untyped()]
+        }),
+        SyntaxKind::ModuleInclude => parts(node.cast::<ast::ModuleInclude>(), |v| {
+            vec![v.source().to_untyped()]
+        }),
+        _ => vec![],
+    };
+    parts
+        .into_iter()
+        .filter(|part| !node.children().any(|child| std::ptr::eq(child, *part)))
+        .collect()
+}
+
+fn parts<'a, T>(node: Option<T>, f: impl FnOnce(T) -> Vec<&'a SyntaxNode>) -> Vec<&'a SyntaxNode> {
+    node.map(f).unwrap_or_default()
+}
+
+/// The syntax errors below `node`, sorted by range, without repeating an
+/// error with the same message and range.
 pub fn collect_errors(node: &LinkedNode) -> Vec<ParseError> {
-    if !node.get().erroneous() {
-        return vec![];
+    let mut errors = vec![];
+    visit_errors(node, &mut |error| errors.push(error));
+    sort_and_dedup(&mut errors);
+    errors
+}
+
+pub fn sort_and_dedup(errors: &mut Vec<ParseError>) {
+    errors.sort_by_key(|error| error.range);
+    let mut seen = HashSet::new();
+    errors.retain(|error| seen.insert((error.range, error.message.clone())));
+}
+
+/// Parses `text` and passes each syntax error to `on_error` as soon as it is
+/// found, in the same order as the batch-returning APIs list them. The tree
+/// is visited in source order, so no sorting is needed to get there.
+pub fn report_errors(text: &str, mode: ParseMode, on_error: &mut dyn FnMut(&ParseError)) {
+    let root = mode.parse(text);
+    let mut seen = HashSet::new();
+    visit_errors(&LinkedNode::new(&root), &mut |error| {
+        if seen.insert((error.range, error.message.clone())) {
+            on_error(&error);
+        }
+    });
+}
+
+fn visit_errors(node: &LinkedNode, f: &mut dyn FnMut(ParseError)) {
+    // An explicit stack, as erroneous trees can be arbitrarily deep.
+    let mut stack = vec![node.clone()];
+    while let Some(node) = stack.pop() {
+        if !node.get().erroneous() {
+            continue;
+        }
+        if node.get().kind() == typst_syntax::SyntaxKind::Error {
+            for e in node.get().errors() {
+                f(ParseError {
+                    message: e.message.to_string(),
+                    range: [node.offset(), node.offset() + node.get().len()],
+                    hints: e.hints.iter().map(|hint| hint.to_string()).collect(),
+                    severity: Severity::Error,
+                });
+            }
+            continue;
+        }
+        stack.extend(node.children().rev());
     }
-    if node.get().kind() == typst_syntax::SyntaxKind::Error {
-        return node
-            .get()
-            .errors()
-            .into_iter()
-            .map(|e| ParseError {
-                message: e.message.to_string(),
-                range: [node.offset(), node.offset() + node.get().len()],
-            })
-            .collect();
+}
+
+/// The range of the first node nested more than `max_depth` levels below
+/// `root`, if any. Runs without recursion, so it is safe on any tree.
+pub fn find_too_deep(root: &SyntaxNode, max_depth: usize) -> Option<[usize; 2]> {
+    let mut stack = vec![(root, 0, 0)];
+    while let Some((node, offset, depth)) = stack.pop() {
+        if depth > max_depth {
+            return Some([offset, offset + node.len()]);
+        }
+        let mut child_offset = offset + node.len();
+        for child in node.children().rev() {
+            child_offset -= child.len();
+            stack.push((child, child_offset, depth + 1));
+        }
+    }
+    None
+}
+
+/// The text of `node`, like `SyntaxNode::into_text`, which recurses, but
+/// collected with an explicit stack, so it is safe on any tree.
+pub fn node_text(node: &SyntaxNode) -> String {
+    let mut text = String::with_capacity(node.len());
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        text.push_str(node.text());
+        stack.extend(node.children().rev());
+    }
+    text
+}
+
+/// The error reported for a tree nested deeper than `max_depth` at `range`.
+pub fn too_deep_error(max_depth: usize, range: [usize; 2]) -> ParseError {
+    ParseError {
+        message: format!("maximum nesting depth of {max_depth} exceeded"),
+        range,
+        hints: vec!["deeper nodes were left out".into()],
+        severity: Severity::Error,
+    }
+}
+
+/// Whether `text` parses without any syntax errors. This agrees with
+/// [`collect_errors`] being empty but only checks the root, which caches
+/// whether any descendant is erroneous.
+pub fn is_valid(text: &str, mode: ParseMode) -> bool {
+    !mode.parse(text).erroneous()
+}
+
+/// The chain of CST nodes from the root down to the leaf at byte `offset`,
+/// each without its children. Empty when `offset` is past the end of `text`.
+pub fn ancestors_at(text: &str, mode: ParseMode, offset: usize) -> Vec<CstNode> {
+    let root = mode.parse(text);
+    let options = ParseOptions::default();
+    let builder = CstBuilder {
+        options: &options,
+        mapper: RangeMapper::new(&root, &options),
+    };
+    let mut chain = vec![];
+    let mut current = LinkedNode::new(&root).leaf_at(offset, Side::After);
+    while let Some(node) = current {
+        chain.push(builder.shallow_node_to_cst(&node));
+        current = node.parent().cloned();
+    }
+    chain.reverse();
+    chain
+}
+
+pub fn map_error_ranges(errors: &mut [ParseError], mapper: &RangeMapper) {
+    for error in errors {
+        error.range = mapper.map(error.range);
+    }
+}
+
+pub fn build_cst(root: &typst_syntax::SyntaxNode, options: &ParseOptions) -> CstParseResult {
+    build_mapped_cst(root, options, RangeMapper::new(root, options))
+}
+
+/// Like [`build_cst`], with ranges translated by `mapper`.
+pub(crate) fn build_mapped_cst(
+    root: &typst_syntax::SyntaxNode,
+    options: &ParseOptions,
+    mapper: RangeMapper,
+) -> CstParseResult {
+    let linked = LinkedNode::new(root);
+    let builder = CstBuilder { options, mapper };
+    let mut errors = collect_errors(&linked);
+    let too_deep = find_too_deep(root, options.max_depth());
+    if let Some(range) = too_deep {
+        errors.push(too_deep_error(options.max_depth(), range));
+        sort_and_dedup(&mut errors);
+    }
+    map_error_ranges(&mut errors, &builder.mapper);
+    CstParseResult {
+        root: builder.node_to_cst(&linked),
+        ok: !root.erroneous() && too_deep.is_none(),
+        errors,
+        line_ending: line_ending(root, builder.mapper.removed_crs()),
+        detected_mode: (options.mode == Some(ParseMode::Auto)).then(|| ParseMode::of_root(root)),
+    }
+}
+
+/// Parses the bytes `start..end` of `text` on their own, as an editor may do
+/// for a fragment, with every range pointing into `text`. Fails unless both
+/// ends are character boundaries of `text`.
+///
+/// ```
+/// use typst_ast::{ParseOptions, parse_range};
+///
+/// let text = "= Title\n*bold*";
+/// let result = parse_range(text, 8, 14, &ParseOptions::default()).unwrap();
+/// assert_eq!(result.root.children[0].kind, "Strong");
+/// assert_eq!(result.root.children[0].range, Some([8, 14]));
+/// ```
+pub fn parse_range(
+    text: &str,
+    start: usize,
+    end: usize,
+    options: &ParseOptions,
+) -> Result<CstParseResult, String> {
+    let options = options.for_range(text, start, end)?;
+    let (text, removed_crs) = options.normalize(&text[start..end]);
+    let root = options.mode.unwrap_or_default().parse(&text);
+    let mapper = RangeMapper::new(&root, &options).with_removed_crs(removed_crs);
+    Ok(build_mapped_cst(&root, &options, mapper))
+}
+
+/// Renders the CST of `text` on one line as nested `(Kind start..end child…)`
+/// lists, with the quoted text of each leaf. Syntax errors, if any, follow on
+/// one line each as `(error start..end "message")`.
+pub fn parse_sexpr(text: &str, options: &ParseOptions) -> String {
+    let root = options.mode.unwrap_or_default().parse(text);
+    let result = build_cst(&root, options);
+    let mut out = String::new();
+    write_sexpr(&result.root, &mut out);
+    for error in &result.errors {
+        let [start, end] = error.range;
+        out.push_str(&format!("\n(error {start}..{end} {:?})", error.message));
+    }
+    out
+}
+
+fn write_sexpr(root: &CstNode, out: &mut String) {
+    // An explicit stack, as the tree can be up to `max_depth` deep. Each
+    // node is visited twice, to open and, with `true`, to close its list.
+    let mut stack = vec![(root, false)];
+    while let Some((node, close)) = stack.pop() {
+        if close {
+            out.push(')');
+            continue;
+        }
+        if !std::ptr::eq(node, root) {
+            out.push(' ');
+        }
+        match node.range {
+            Some([start, end]) => out.push_str(&format!("({} {start}..{end}", node.kind)),
+            None => out.push_str(&format!("({} placeholder", node.kind)),
+        }
+        if let Some(text) = &node.text {
+            out.push_str(&format!(" {text:?}"));
+        }
+        stack.push((node, true));
+        stack.extend(node.children.iter().rev().map(|child| (child, false)));
+    }
+}
+
+/// Whether concatenating the text of the CST's leaves reproduces `text`.
+pub fn verify_roundtrip(text: &str, options: &ParseOptions) -> bool {
+    roundtrip_mismatch(text, options).is_none()
+}
+
+/// The offset of the first character where the concatenated leaf text of the
+/// CST differs from `text`, mapped like any other offset, or `None` if the two
+/// agree. Options that drop text by design, `cst_kinds` and
+/// `omit_whitespace_text`, are ignored.
+pub fn roundtrip_mismatch(text: &str, options: &ParseOptions) -> Option<usize> {
+    let root = options.mode.unwrap_or_default().parse(text);
+    let lossless = ParseOptions {
+        cst_kinds: None,
+        omit_whitespace_text: false,
+        ..options.clone()
+    };
+    let mut rebuilt = String::with_capacity(text.len());
+    push_leaf_text(&build_cst(&root, &lossless).root, &mut rebuilt);
+    let offset = first_difference(text, &rebuilt)?;
+    Some(RangeMapper::new(&root, options).map([offset, offset])[0])
+}
+
+fn push_leaf_text(root: &CstNode, out: &mut String) {
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if let Some(text) = &node.text {
+            out.push_str(text);
+        }
+        stack.extend(node.children.iter().rev());
+    }
+}
+
+/// The byte offset of the first character at which `a` and `b` differ.
+fn first_difference(a: &str, b: &str) -> Option<usize> {
+    let mut offset = a.bytes().zip(b.bytes()).position(|(x, y)| x != y);
+    if offset.is_none() && a.len() != b.len() {
+        offset = Some(a.len().min(b.len()));
     }
-    node.children().flat_map(|c| collect_errors(&c)).collect()
+    offset.map(|mut offset| {
+        while !a.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    })
+}
+
+/// The number of characters of a leaf's text [`parse_pretty`] shows.
+const PRETTY_TEXT_LIMIT: usize = 40;
+
+/// Renders the CST of `text` as an indented tree for reading, one node per
+/// line as `Kind [start..end] "text"`, indented by two spaces per level.
+/// Long leaf text is truncated. `cst_kinds` is ignored.
+pub fn parse_pretty(text: &str, options: &ParseOptions) -> String {
+    let root = options.mode.unwrap_or_default().parse(text);
+    let builder = CstBuilder {
+        options,
+        mapper: RangeMapper::new(&root, options),
+    };
+    let mut out = String::new();
+    builder.write_pretty(&LinkedNode::new(&root), &mut out);
+    out
+}
+
+fn truncate(text: &str, limit: usize) -> String {
+    match text.char_indices().nth(limit) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text.to_string(),
+    }
+}
+
+/// The errors a parse fails with under `options`: all of them if it is
+/// `strict`, none otherwise.
+pub fn strict_errors<'a>(
+    errors: &'a [ParseError],
+    options: &ParseOptions,
+) -> Option<&'a [ParseError]> {
+    (options.strict && !errors.is_empty()).then_some(errors)
 }
 
 pub fn make_cst_result(
     root: typst_syntax::SyntaxNode,
+    options: &ParseOptions,
+    mapper: RangeMapper,
 ) -> Result<wasm_bindgen::JsValue, wasm_bindgen::JsValue> {
-    let linked = LinkedNode::new(&root);
-    let errors = collect_errors(&linked);
-    let out = CstParseResult {
-        root: node_to_cst(&linked),
-        errors,
-    };
-    serde_wasm_bindgen::to_value(&out).map_err(|e| wasm_bindgen::JsValue::from_str(&e.to_string()))
+    let out = build_mapped_cst(&root, options, mapper);
+    if let Some(errors) = strict_errors(&out.errors, options) {
+        return Err(crate::errors_to_value(errors));
+    }
+    if options.flat {
+        serde_wasm_bindgen::to_value(&out.into_flat())
+    } else {
+        serde_wasm_bindgen::to_value(&out)
+    }
+    .map_err(|e| wasm_bindgen::JsValue::from_str(&e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::newlines::LineEnding;
+    use crate::range_mapper::OffsetEncoding;
+
+    fn leaves(node: &CstNode, out: &mut Vec<(String, Option<String>)>) {
+        if node.children.is_empty() {
+            out.push((node.kind.clone(), node.text.clone()));
+        }
+        for child in &node.children {
+            leaves(child, out);
+        }
+    }
+
+    #[test]
+    fn tells_whether_the_parse_is_ok() {
+        let options = ParseOptions::default();
+        for (text, ok) in [
+            ("*a* #f(x)", true),
+            ("", true),
+            ("[*", false),
+            ("#f(", false),
+        ] {
+            let result = build_cst(&typst_syntax::parse(text), &options);
+            assert_eq!(result.ok, ok, "{text:?}");
+            assert_eq!(result.ok, result.errors.is_empty(), "{text:?}");
+        }
+        let shallow = ParseOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        assert!(!build_cst(&typst_syntax::parse("*_a_*"), &shallow).ok);
+    }
+
+    #[test]
+    fn fails_on_errors_when_strict() {
+        let root = typst_syntax::parse("[*");
+        let lenient = ParseOptions::default();
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let result = build_cst(&root, &lenient);
+        assert!(!result.errors.is_empty());
+        assert!(strict_errors(&result.errors, &lenient).is_none());
+        let errors = strict_errors(&result.errors, &strict).unwrap();
+        assert_eq!(errors.len(), result.errors.len());
+        let clean = build_cst(&typst_syntax::parse("[*a*]"), &strict);
+        assert!(strict_errors(&clean.errors, &strict).is_none());
+    }
+
+    #[test]
+    fn parses_ranges_in_document_coordinates() {
+        let text = "= Ünïcode\n*bold* and #f(x)\n";
+        let (start, end) = (text.find('*').unwrap(), text.len() - 1);
+        for offset_encoding in [OffsetEncoding::Utf8, OffsetEncoding::Utf16] {
+            let options = ParseOptions {
+                offset_encoding,
+                ..Default::default()
+            };
+            let full = build_cst(&typst_syntax::parse(text), &options);
+            let [from, to] = [start, end].map(|i| offset_encoding.text_len(&text[..i]));
+            let expected: Vec<_> = full
+                .root
+                .children
+                .iter()
+                .filter(|child| child.range.is_some_and(|[s, e]| from <= s && e <= to))
+                .map(|child| (child.kind.clone(), child.range))
+                .collect();
+            let fragment = parse_range(text, start, end, &options).unwrap();
+            assert_eq!(fragment.root.range, Some([from, to]));
+            let actual: Vec<_> = fragment
+                .root
+                .children
+                .iter()
+                .map(|child| (child.kind.clone(), child.range))
+                .collect();
+            assert_eq!(actual, expected);
+        }
+        let options = ParseOptions::default();
+        assert!(parse_range(text, 3, 4, &options).is_err());
+        assert!(parse_range(text, 2, text.len() + 1, &options).is_err());
+    }
+
+    #[test]
+    fn includes_placeholders_when_requested() {
+        let root = typst_syntax::parse("#show: x => x\n#show:");
+        let show_rules = |options: &ParseOptions| -> Vec<Vec<(String, bool)>> {
+            build_cst(&root, options)
+                .root
+                .children
+                .iter()
+                .filter(|node| node.kind == "ShowRule")
+                .map(|node| {
+                    let children = node.children.iter();
+                    children
+                        .map(|child| (child.kind.clone(), child.range.is_none()))
+                        .collect()
+                })
+                .collect()
+        };
+        let options = ParseOptions {
+            include_placeholders: true,
+            ..Default::default()
+        };
+        let rules = show_rules(&options);
+        assert_eq!(rules.len(), 2);
+        assert!(rules[0].iter().all(|(_, placeholder)| !placeholder));
+        assert_eq!(rules[1].last(), Some(&("None".to_string(), true)));
+        assert!(
+            show_rules(&ParseOptions::default())[1]
+                .iter()
+                .all(|(_, p)| !p)
+        );
+
+        let result = build_cst(&root, &options);
+        let placeholder = result.root.children[4].children.last().unwrap();
+        assert!(placeholder.placeholder);
+        let json = serde_json::to_value(placeholder).unwrap();
+        assert_eq!(json["range"], serde_json::Value::Null);
+        assert_eq!(json["placeholder"], true);
+        assert!(verify_roundtrip("#show:", &options));
+    }
+
+    #[test]
+    fn reports_line_endings_and_normalizes_newlines() {
+        let text = "é\r\n*b*\nc\r\n";
+        let leaves = |options: &ParseOptions| {
+            let result = parse_range(text, 0, text.len(), options).unwrap();
+            let mut out = vec![];
+            let mut stack = vec![&result.root];
+            while let Some(node) = stack.pop() {
+                if let Some(text) = &node.text {
+                    out.push((text.clone(), node.range.unwrap()));
+                }
+                stack.extend(node.children.iter().rev());
+            }
+            (result.line_ending, out)
+        };
+        let leaf = |text: &str, range| (text.to_string(), range);
+
+        let (ending, kept) = leaves(&ParseOptions::default());
+        assert_eq!(ending, LineEnding::Mixed);
+        assert_eq!(
+            kept,
+            [
+                leaf("é", [0, 2]),
+                leaf("\r\n", [2, 4]),
+                leaf("*", [4, 5]),
+                leaf("b", [5, 6]),
+                leaf("*", [6, 7]),
+                leaf("\n", [7, 8]),
+                leaf("c", [8, 9]),
+                leaf("\r\n", [9, 11]),
+            ]
+        );
+
+        let options = ParseOptions {
+            normalize_newlines: true,
+            offset_encoding: OffsetEncoding::Utf16,
+            ..Default::default()
+        };
+        let (ending, normalized) = leaves(&options);
+        assert_eq!(ending, LineEnding::Mixed);
+        assert_eq!(
+            normalized,
+            [
+                leaf("é", [0, 1]),
+                leaf("\n", [1, 3]),
+                leaf("*", [3, 4]),
+                leaf("b", [4, 5]),
+                leaf("*", [5, 6]),
+                leaf("\n", [6, 7]),
+                leaf("c", [7, 8]),
+                leaf("\n", [8, 10]),
+            ]
+        );
+        let (ending, _) = leaves(&ParseOptions {
+            normalize_newlines: true,
+            ..Default::default()
+        });
+        assert_eq!(ending, LineEnding::Mixed);
+        let crlf = typst_syntax::parse("a\r\nb");
+        assert_eq!(
+            build_cst(&crlf, &ParseOptions::default()).line_ending,
+            LineEnding::Crlf
+        );
+    }
+
+    #[test]
+    fn flattens_the_tree_in_pre_order() {
+        #[derive(Debug, PartialEq)]
+        struct Shape(String, Option<[usize; 2]>, Option<String>, Vec<Shape>);
+
+        fn shape(node: &CstNode) -> Shape {
+            let children = node.children.iter().map(shape).collect();
+            Shape(node.kind.clone(), node.range, node.text.clone(), children)
+        }
+
+        fn rebuild(nodes: &[FlatCstNode], id: usize) -> Shape {
+            let node = &nodes[id];
+            let children = nodes
+                .iter()
+                .filter(|child| child.parent == id as isize)
+                .map(|child| rebuild(nodes, child.id))
+                .collect();
+            Shape(node.kind.clone(), node.range, node.text.clone(), children)
+        }
+
+        let options = ParseOptions {
+            include_placeholders: true,
+            ..Default::default()
+        };
+        let root = typst_syntax::parse("= Hi *there*\n#let f(x) = x + 1\n#show:");
+        let nested = shape(&build_cst(&root, &options).root);
+        let flat = build_cst(&root, &options).into_flat();
+        assert_eq!(flat.nodes[0].parent, -1);
+        assert!(flat.nodes.iter().enumerate().all(|(i, node)| node.id == i));
+        assert!(
+            flat.nodes[1..]
+                .iter()
+                .all(|node| (0..node.id as isize).contains(&node.parent))
+        );
+        assert!(flat.nodes.iter().any(|node| node.placeholder));
+        assert_eq!(rebuild(&flat.nodes, 0), nested);
+    }
+
+    #[test]
+    fn includes_lengths_when_requested() {
+        let root = typst_syntax::parse("é #show:");
+        let options = ParseOptions {
+            include_placeholders: true,
+            include_len: true,
+            offset_encoding: OffsetEncoding::Utf16,
+            ..Default::default()
+        };
+        let result = build_cst(&root, &options);
+        assert_eq!(result.root.len, Some(9));
+        assert_eq!(result.root.range, Some([0, 8]));
+        let show = result.root.children.last().unwrap();
+        assert_eq!(show.kind, "ShowRule");
+        let placeholder = show.children.last().unwrap();
+        assert!(placeholder.placeholder);
+        assert_eq!(placeholder.len, Some(0));
+        let json = serde_json::to_value(placeholder).unwrap();
+        assert_eq!(json["range"], serde_json::Value::Null);
+        assert_eq!(json["len"], 0);
+
+        let plain = build_cst(&root, &ParseOptions::default());
+        assert_eq!(plain.root.len, None);
+        assert!(
+            serde_json::to_value(&plain.root)
+                .unwrap()
+                .get("len")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn omits_whitespace_text_when_requested() {
+        let root = typst_syntax::parse("*a* b\n\nc");
+        let text_of = |options: &ParseOptions, kind: &str| {
+            let mut out = vec![];
+            leaves(&build_cst(&root, options).root, &mut out);
+            out.into_iter().find(|(k, _)| k == kind).unwrap().1
+        };
+
+        let lossless = ParseOptions::default();
+        assert_eq!(text_of(&lossless, "Space").as_deref(), Some(" "));
+        assert_eq!(text_of(&lossless, "Parbreak").as_deref(), Some("\n\n"));
+
+        let options = ParseOptions {
+            omit_whitespace_text: true,
+            ..Default::default()
+        };
+        assert_eq!(text_of(&options, "Space"), None);
+        assert_eq!(text_of(&options, "Parbreak"), None);
+        assert_eq!(text_of(&options, "Text").as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn prunes_to_allowed_kinds() {
+        let root = typst_syntax::parse("= Title\n\nSome *bold* text");
+        let options = ParseOptions {
+            cst_kinds: Some(vec!["Strong".into(), "HeadingMarker".into()]),
+            ..Default::default()
+        };
+        let cst = build_cst(&root, &options).root;
+        let kinds: Vec<_> = cst.children.iter().map(|c| c.kind.as_str()).collect();
+        assert_eq!(kinds, ["Heading", "Strong"]);
+        assert_eq!(cst.children[0].children[0].kind, "HeadingMarker");
+        // Descendants of an allowed node are pruned too unless listed.
+        assert!(cst.children[1].children.is_empty());
+    }
+
+    #[test]
+    fn is_valid_agrees_with_collected_errors() {
+        for (text, mode) in [
+            ("= Hi *there*", ParseMode::Markup),
+            ("#let x = ", ParseMode::Markup),
+            ("f(1, 2)", ParseMode::Code),
+            ("f(1, ", ParseMode::Code),
+            ("x^2", ParseMode::Math),
+            ("x_", ParseMode::Math),
+        ] {
+            let root = mode.parse(text);
+            assert_eq!(
+                is_valid(text, mode),
+                collect_errors(&LinkedNode::new(&root)).is_empty(),
+                "{text:?}"
+            );
+        }
+        assert!(!is_valid("#let x = ", ParseMode::Markup));
+    }
+
+    #[test]
+    fn ancestors_form_a_contiguous_chain() {
+        let text = "= Hi *there*\n#f(x + 1)";
+        let chain = ancestors_at(text, ParseMode::Markup, text.find('x').unwrap());
+        let kinds: Vec<_> = chain.iter().map(|n| n.kind.as_str()).collect();
+        assert_eq!(kinds, ["Markup", "FuncCall", "Args", "Binary", "Ident"]);
+        assert_eq!(chain[0].range, Some([0, text.len()]));
+        assert_eq!(chain.last().unwrap().text.as_deref(), Some("x"));
+        for pair in chain.windows(2) {
+            let [outer, inner] = [pair[0].range.unwrap(), pair[1].range.unwrap()];
+            assert!(outer[0] <= inner[0] && inner[1] <= outer[1]);
+        }
+
+        assert!(ancestors_at(text, ParseMode::Markup, text.len() + 1).is_empty());
+    }
+
+    #[test]
+    fn reports_the_same_errors_as_collected() {
+        let text = "#let = \n#f(\n$a^$ *b";
+        let mut reported = vec![];
+        report_errors(text, ParseMode::Markup, &mut |e| {
+            reported.push((e.message.clone(), e.range))
+        });
+        let root = ParseMode::Markup.parse(text);
+        let collected: Vec<_> = collect_errors(&LinkedNode::new(&root))
+            .into_iter()
+            .map(|e| (e.message, e.range))
+            .collect();
+        assert!(collected.len() > 1);
+        assert_eq!(reported, collected);
+    }
+
+    #[test]
+    fn renders_s_expressions() {
+        assert_eq!(
+            parse_sexpr("= Hi", &ParseOptions::default()),
+            "(Markup 0..4 (Heading 0..4 (HeadingMarker 0..1 \"=\") (Space 1..2 \" \") \
+             (Markup 2..4 (Text 2..4 \"Hi\"))))"
+        );
+        let options = ParseOptions {
+            mode: Some(ParseMode::Code),
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_sexpr("(1,", &options),
+            "(Code 0..3 (Array 0..3 (Error 0..1 \"(\") (Int 1..2 \"1\") (Comma 2..3 \",\")))\n\
+             (error 0..1 \"unclosed delimiter\")"
+        );
+    }
+
+    #[test]
+    fn renders_an_indented_tree() {
+        let text = format!("= Hi\n{}", "long ".repeat(10));
+        let pretty = parse_pretty(&text, &ParseOptions::default());
+        let expected = [
+            "Markup [0..55]",
+            "  Heading [0..4]",
+            "    HeadingMarker [0..1] \"=\"",
+            "    Space [1..2] \" \"",
+            "    Markup [2..4]",
+            "      Text [2..4] \"Hi\"",
+            "  Space [4..5] \"\\n\"",
+            "  Text [5..54] \"long long long long long long long long …\"",
+            "  Space [54..55] \" \"",
+        ];
+        assert_eq!(pretty.lines().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn reconstructs_the_source() {
+        let options = ParseOptions {
+            omit_whitespace_text: true,
+            ..Default::default()
+        };
+        for text in ["= Hi *there*\n\n#f(x)[y] $a^2$", "[*", "#let = \n#f("] {
+            assert!(verify_roundtrip(text, &options), "{text:?}");
+            assert_eq!(roundtrip_mismatch(text, &options), None);
+        }
+    }
+
+    #[test]
+    fn locates_the_first_difference() {
+        assert_eq!(first_difference("abc", "abc"), None);
+        assert_eq!(first_difference("abc", "abd"), Some(2));
+        assert_eq!(first_difference("abc", "ab"), Some(2));
+        assert_eq!(first_difference("ab", "abc"), Some(2));
+        // `ä` and `ö` share their first byte.
+        assert_eq!(first_difference("aä", "aö"), Some(1));
+    }
+
+    #[test]
+    fn surfaces_hints() {
+        let root = ParseMode::Markup.parse("a ] b");
+        let errors = collect_errors(&LinkedNode::new(&root));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "unexpected closing bracket");
+        assert_eq!(errors[0].hints, ["try using a backslash escape: \\]"]);
+        assert_eq!(errors[0].severity, Severity::Error);
+
+        let json = serde_json::to_value(&errors[0]).unwrap();
+        assert_eq!(json["severity"], "error");
+        assert_eq!(json["range"], serde_json::json!([2, 3]));
+    }
+
+    #[test]
+    fn sorts_and_deduplicates_errors() {
+        let error = |message: &str, range| ParseError {
+            message: message.into(),
+            range,
+            hints: vec![],
+            severity: Severity::Error,
+        };
+        let mut errors = vec![
+            error("b", [4, 6]),
+            error("a", [2, 3]),
+            error("b", [4, 6]),
+            error("c", [4, 6]),
+            error("a", [2, 2]),
+            error("a", [2, 3]),
+        ];
+        sort_and_dedup(&mut errors);
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|e| (e.message.as_str(), e.range))
+            .collect();
+        assert_eq!(
+            errors,
+            [("a", [2, 2]), ("a", [2, 3]), ("b", [4, 6]), ("c", [4, 6])]
+        );
+
+        let text = "#f([#g((1, ]), )\n$a^(b$ #{ (1, [*x ] }";
+        let root = ParseMode::Markup.parse(text);
+        let errors = collect_errors(&LinkedNode::new(&root));
+        assert!(errors.len() > 2);
+        assert!(errors.windows(2).all(|pair| pair[0].range <= pair[1].range));
+        let unique: HashSet<_> = errors.iter().map(|e| (&e.message, e.range)).collect();
+        assert_eq!(unique.len(), errors.len());
+    }
+
+    #[test]
+    fn converts_deep_trees_without_recursion() {
+        // typst-syntax's parser recurses, so parse and drop the syntax tree
+        // on a thread with a large stack; converting, writing, and dropping
+        // the CST run on the test thread.
+        let on_big_stack = |f: Box<dyn FnOnce() -> Option<SyntaxNode> + Send>| {
+            std::thread::Builder::new()
+                .stack_size(256 << 20)
+                .spawn(f)
+                .unwrap()
+                .join()
+                .unwrap()
+        };
+        let text = format!("#{}1{}", "(".repeat(10_000), ")".repeat(10_000));
+        let root = on_big_stack(Box::new(move || Some(ParseMode::Markup.parse(&text)))).unwrap();
+
+        let options = ParseOptions {
+            max_depth: Some(usize::MAX),
+            ..Default::default()
+        };
+        let result = build_cst(&root, &options);
+        assert!(result.errors.is_empty());
+        let mut depth = 0;
+        let mut node = &result.root;
+        while let Some(child) = node.children.iter().find(|c| !c.children.is_empty()) {
+            node = child;
+            depth += 1;
+        }
+        assert_eq!(depth, 10_000);
+        assert_eq!(node.children[1].text.as_deref(), Some("1"));
+
+        let mut sexpr = String::new();
+        write_sexpr(&result.root, &mut sexpr);
+        assert_eq!(sexpr.matches("(Parenthesized ").count(), 10_000);
+        assert!(sexpr.ends_with("(RightParen 20001..20002 \")\")))"));
+        let mut xml = String::new();
+        crate::xml::write_node(&result.root, &mut xml);
+        assert_eq!(xml.matches("</Parenthesized>").count(), 10_000);
+        assert!(xml.ends_with("</RightParen></Parenthesized></Markup>"));
+        let mut leaves = String::new();
+        push_leaf_text(&result.root, &mut leaves);
+        assert_eq!(leaves.len(), 20_002);
+        drop(result);
+
+        on_big_stack(Box::new(move || {
+            drop(root);
+            None
+        }));
+    }
+
+    #[test]
+    fn matches_the_recursive_conversion() {
+        fn recursive(builder: &CstBuilder, node: &LinkedNode, depth: usize) -> CstNode {
+            let mut cst = builder.shallow_node_to_cst(node);
+            if depth < builder.options.max_depth() {
+                cst.children = node
+                    .children()
+                    .map(|child| recursive(builder, &child, depth + 1))
+                    .filter(|child| builder.keep(child))
+                    .collect();
+            }
+            cst
+        }
+
+        let text = "= Hi *there* <l>\n#let f(x) = [#x and $x^2$]\n- `raw`\n#f(1, ";
+        for options in [
+            ParseOptions::default(),
+            ParseOptions {
+                cst_kinds: Some(vec!["Strong".into(), "Ident".into()]),
+                omit_whitespace_text: true,
+                ..Default::default()
+            },
+            ParseOptions {
+                max_depth: Some(3),
+                ..Default::default()
+            },
+        ] {
+            let root = ParseMode::Markup.parse(text);
+            let builder = CstBuilder {
+                options: &options,
+                mapper: RangeMapper::new(&root, &options),
+            };
+            let linked = LinkedNode::new(&root);
+            assert_eq!(
+                serde_json::to_value(builder.node_to_cst(&linked)).unwrap(),
+                serde_json::to_value(recursive(&builder, &linked, 0)).unwrap()
+            );
+        }
+    }
 }
@@ -0,0 +1,159 @@
+use std::fmt::Write;
+
+use crate::cst::ParseError;
+
+const RED_BOLD: &str = "\x1b[1;31m";
+const BLUE_BOLD: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Renders errors in the style of rustc: the message, the line and column of
+/// the error, and the offending source line with the range underlined,
+/// followed by a `= hint:` line for each of the error's hints.
+///
+/// Columns count characters, not bytes, so carets line up under multi-byte
+/// text. A range spanning several lines is underlined up to the end of its
+/// first line.
+///
+/// Ranges are byte offsets into `text`, as [`build_cst`](crate::build_cst)
+/// produces them by default. Ranges from a result with an `offsetEncoding` or
+/// `baseOffset` must be mapped back first; an offset that falls inside a
+/// character is moved back to the character's start instead of panicking.
+pub fn format_diagnostics(text: &str, errors: &[ParseError]) -> String {
+    render(text, errors, false)
+}
+
+/// Like [`format_diagnostics`], but colored with ANSI escape codes for
+/// terminal output.
+pub fn format_diagnostics_colored(text: &str, errors: &[ParseError]) -> String {
+    render(text, errors, true)
+}
+
+fn render(text: &str, errors: &[ParseError], color: bool) -> String {
+    let paint = |style: &'static str| if color { style } else { "" };
+    let reset = paint(RESET);
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let gutter = line_starts.len().to_string().len();
+
+    let mut out = String::new();
+    for error in errors {
+        let start = floor_char_boundary(text, error.range[0]);
+        let end = floor_char_boundary(text, error.range[1]).max(start);
+        let line = line_starts.partition_point(|&s| s <= start) - 1;
+        let line_start = line_starts[line];
+        let line_text = text[line_start..]
+            .split('\n')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches('\r');
+        let line_end = line_start + line_text.len();
+
+        let column = text[line_start..start].chars().count();
+        let width = text[start..end.min(line_end).max(start)]
+            .chars()
+            .count()
+            .max(1);
+
+        let _ = writeln!(
+            out,
+            "{}error{reset}{}: {}{reset}",
+            paint(RED_BOLD),
+            paint(BOLD),
+            error.message
+        );
+        let blue = paint(BLUE_BOLD);
+        let _ = writeln!(
+            out,
+            "{:gutter$}{blue}-->{reset} {}:{}",
+            "",
+            line + 1,
+            column + 1
+        );
+        let _ = writeln!(out, "{:gutter$} {blue}|{reset}", "");
+        let _ = writeln!(out, "{blue}{:>gutter$} |{reset} {line_text}", line + 1);
+        let _ = writeln!(
+            out,
+            "{:gutter$} {blue}|{reset} {:column$}{}{}{reset}",
+            "",
+            "",
+            paint(RED_BOLD),
+            "^".repeat(width)
+        );
+        for hint in &error.hints {
+            let _ = writeln!(
+                out,
+                "{:gutter$} {blue}={reset} {}hint{reset}: {hint}",
+                "",
+                paint(BOLD)
+            );
+        }
+    }
+    out
+}
+
+fn floor_char_boundary(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_mode::ParseMode;
+    use typst_syntax::LinkedNode;
+
+    fn errors(text: &str) -> Vec<ParseError> {
+        crate::cst::collect_errors(&LinkedNode::new(&ParseMode::Markup.parse(text)))
+    }
+
+    #[test]
+    fn aligns_carets_on_multi_byte_lines() {
+        let text = "= Überschrift\n#let café = (1, 2";
+        let rendered = format_diagnostics(text, &errors(text));
+        assert_eq!(
+            rendered,
+            "error: unclosed delimiter\n \
+             --> 2:13\n  \
+             |\n\
+             2 | #let café = (1, 2\n  \
+             |             ^\n"
+        );
+    }
+
+    #[test]
+    fn renders_hints_below_the_carets() {
+        let text = "a]b";
+        let rendered = format_diagnostics(text, &errors(text));
+        assert_eq!(
+            rendered,
+            "error: unexpected closing bracket\n \
+             --> 1:2\n  \
+             |\n\
+             1 | a]b\n  \
+             |  ^\n  \
+             = hint: try using a backslash escape: \\]\n"
+        );
+    }
+
+    #[test]
+    fn tolerates_ranges_inside_characters() {
+        let text = "中中]";
+        let mut errors = errors(text);
+        // The UTF-16 range of the bracket.
+        errors[0].range = [2, 3];
+        let rendered = format_diagnostics(text, &errors);
+        assert!(rendered.contains(" --> 1:1\n"), "{rendered}");
+    }
+
+    #[test]
+    fn colors_only_when_requested() {
+        let text = "#let x = ";
+        assert!(!format_diagnostics(text, &errors(text)).contains('\x1b'));
+        assert!(format_diagnostics_colored(text, &errors(text)).contains(RED_BOLD));
+    }
+}
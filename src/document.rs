@@ -0,0 +1,212 @@
+use std::ops::Range;
+
+use typst_syntax::{LinkedNode, Source, SyntaxNode};
+use wasm_bindgen::prelude::*;
+
+use crate::cst::{self, ParseError};
+use crate::parse_mode::ParseMode;
+use crate::parse_options::ParseOptions;
+use crate::range_mapper::RangeMapper;
+
+/// A parsed document that can be edited in place.
+///
+/// Markup documents are backed by a [`Source`], so edits are reparsed
+/// incrementally. `typst-syntax` only supports incremental reparsing for
+/// markup, so code and math documents are fully reparsed on every edit.
+///
+/// Offsets, in [`edit`](Self::edit) and [`errors`](Self::errors), are counted
+/// in the `offset_encoding` of the options and start at their `base_offset`.
+#[wasm_bindgen(skip_typescript)]
+pub struct Document {
+    tree: Tree,
+    options: ParseOptions,
+}
+
+enum Tree {
+    Incremental(Source),
+    Full {
+        mode: ParseMode,
+        text: String,
+        root: SyntaxNode,
+    },
+}
+
+impl Document {
+    pub fn new(text: &str, mode: ParseMode) -> Self {
+        Self::build(
+            text,
+            ParseOptions {
+                mode: Some(mode),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// A document parsed in the `mode` of `options`, with offsets in their
+    /// `offset_encoding` and `base_offset`, reporting nesting deeper than
+    /// their `max_depth`. Fails for `normalize_newlines`, as edits would have
+    /// to be mapped through the removed CRs. Other options have no effect.
+    pub fn with_options(text: &str, options: ParseOptions) -> Result<Self, String> {
+        if options.normalize_newlines {
+            return Err("Document does not support normalizeNewlines".into());
+        }
+        Ok(Self::build(text, options))
+    }
+
+    fn build(text: &str, options: ParseOptions) -> Self {
+        let mode = options.mode.unwrap_or_default();
+        let tree = match mode {
+            ParseMode::Markup => Tree::Incremental(Source::detached(text)),
+            _ => Tree::Full {
+                mode,
+                text: text.to_string(),
+                root: mode.parse(text),
+            },
+        };
+        Document { tree, options }
+    }
+
+    pub fn text(&self) -> &str {
+        match &self.tree {
+            Tree::Incremental(source) => source.text(),
+            Tree::Full { text, .. } => text,
+        }
+    }
+
+    pub fn root(&self) -> &SyntaxNode {
+        match &self.tree {
+            Tree::Incremental(source) => source.root(),
+            Tree::Full { root, .. } => root,
+        }
+    }
+
+    /// Replaces the range `replace` with `with` and reparses.
+    pub fn edit(&mut self, replace: Range<usize>, with: &str) -> Result<(), String> {
+        let text = self.text();
+        let to_byte = |offset: usize| {
+            let offset = offset.checked_sub(self.options.base_offset)?;
+            self.options.offset_encoding.byte_offset(text, offset)
+        };
+        let (Some(start), Some(end)) = (to_byte(replace.start), to_byte(replace.end)) else {
+            return Err(format!("Invalid edit range {replace:?}"));
+        };
+        if start > end {
+            return Err(format!("Invalid edit range {replace:?}"));
+        }
+        let replace = start..end;
+
+        match &mut self.tree {
+            Tree::Incremental(source) => {
+                source.edit(replace, with);
+            }
+            Tree::Full { mode, text, root } => {
+                text.replace_range(replace, with);
+                *root = mode.parse(text);
+            }
+        }
+        Ok(())
+    }
+
+    /// The current diagnostics, without converting or serializing the tree.
+    pub fn errors(&self) -> Vec<ParseError> {
+        let root = self.root();
+        let mut errors = cst::collect_errors(&LinkedNode::new(root));
+        let max_depth = self.options.max_depth();
+        if let Some(range) = cst::find_too_deep(root, max_depth) {
+            errors.push(cst::too_deep_error(max_depth, range));
+            cst::sort_and_dedup(&mut errors);
+        }
+        cst::map_error_ranges(&mut errors, &RangeMapper::new(root, &self.options));
+        errors
+    }
+}
+
+#[wasm_bindgen]
+impl Document {
+    #[wasm_bindgen(constructor, skip_typescript)]
+    pub fn new_js(text: &str, options: JsValue) -> Result<Document, JsValue> {
+        let opts = crate::read_options(options)?;
+        Document::with_options(text, opts).map_err(|e| JsValue::from_str(&e))
+    }
+
+    #[wasm_bindgen(js_name = "edit", skip_typescript)]
+    pub fn edit_js(&mut self, start: usize, end: usize, with: &str) -> Result<(), JsValue> {
+        self.edit(start..end, with)
+            .map_err(|e| JsValue::from_str(&e))
+    }
+
+    #[wasm_bindgen(js_name = "errors", skip_typescript)]
+    pub fn errors_js(&self) -> Result<JsValue, JsValue> {
+        serde_wasm_bindgen::to_value(&self.errors()).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    #[wasm_bindgen(js_name = "text", skip_typescript)]
+    pub fn text_js(&self) -> String {
+        self.text().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recomputes_errors_after_edits() {
+        for (text, mode) in [
+            ("#let x = 1", ParseMode::Markup),
+            ("let x = 1", ParseMode::Code),
+        ] {
+            let mut doc = Document::new(text, mode);
+            assert!(doc.errors().is_empty());
+
+            let end = doc.text().len();
+            doc.edit(end - 1..end, "").unwrap();
+            assert!(!doc.errors().is_empty(), "{mode:?}: expected an error");
+
+            let end = doc.text().len();
+            doc.edit(end..end, "2").unwrap();
+            assert!(doc.errors().is_empty(), "{mode:?}: expected no errors");
+            assert!(doc.text().ends_with("= 2"));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_edit_ranges() {
+        let mut doc = Document::new("ä", ParseMode::Markup);
+        assert!(doc.edit(0..5, "").is_err());
+        assert!(doc.edit(1..2, "").is_err());
+    }
+
+    #[test]
+    fn counts_offsets_in_the_requested_encoding() {
+        let options = ParseOptions {
+            offset_encoding: crate::OffsetEncoding::Utf16,
+            base_offset: 10,
+            ..Default::default()
+        };
+        let mut doc = Document::with_options("中中]", options).unwrap();
+        assert_eq!(doc.errors()[0].range, [12, 13]);
+
+        doc.edit(11..12, "😀").unwrap();
+        assert_eq!(doc.text(), "中😀]");
+        assert_eq!(doc.errors()[0].range, [13, 14]);
+        assert!(doc.edit(12..13, "").is_err());
+        assert!(doc.edit(0..1, "").is_err());
+    }
+
+    #[test]
+    fn reports_nesting_deeper_than_max_depth() {
+        let options = ParseOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        let doc = Document::with_options("#((1))", options).unwrap();
+        assert_eq!(doc.errors().len(), 1);
+
+        let options = ParseOptions {
+            normalize_newlines: true,
+            ..Default::default()
+        };
+        assert!(Document::with_options("a\r\nb", options).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use typst_syntax::{SyntaxKind, SyntaxNode};
+
+use crate::parse_mode::ParseMode;
+
+const INDENT: &str = "  ";
+
+/// Re-prints `text` with canonical spacing.
+///
+/// The rules are deliberately conservative so that the output always parses
+/// to the same tree:
+///
+/// - binary operators are surrounded by exactly one space (unless the
+///   operator is followed by a line break),
+/// - statements inside code blocks are indented by two spaces relative to the
+///   line that opens the block,
+/// - runs of blank lines collapse into a single blank line.
+///
+/// Formatting is idempotent: `format(format(x)) == format(x)`. Documents with
+/// syntax errors are rejected.
+pub fn format(text: &str, mode: ParseMode) -> Result<String, String> {
+    let root = mode.parse(text);
+    if root.erroneous() {
+        return Err("Cannot format a document with syntax errors".into());
+    }
+
+    let mut formatter = Formatter {
+        out: String::with_capacity(text.len()),
+        block_indents: vec![],
+    };
+    formatter.write_node(&root, None, None);
+    Ok(formatter.out)
+}
+
+struct Formatter {
+    out: String,
+    block_indents: Vec<String>,
+}
+
+impl Formatter {
+    fn write_node(
+        &mut self,
+        node: &SyntaxNode,
+        parent: Option<SyntaxKind>,
+        next: Option<SyntaxKind>,
+    ) {
+        match node.kind() {
+            SyntaxKind::Space if node.text().contains('\n') => {
+                if matches!(parent, Some(SyntaxKind::Code | SyntaxKind::CodeBlock)) {
+                    let indent = self.statement_indent(next == Some(SyntaxKind::RightBrace));
+                    self.write_line_breaks(node.text(), &indent);
+                } else {
+                    self.out.push_str(node.text());
+                }
+            }
+            SyntaxKind::Parbreak => {
+                let tail = node.text().rsplit('\n').next().unwrap_or_default();
+                self.out.push_str("\n\n");
+                self.out.push_str(tail);
+            }
+            SyntaxKind::Binary => self.write_binary(node),
+            SyntaxKind::CodeBlock => {
+                self.block_indents.push(self.current_line_indent());
+                self.write_children(node);
+                self.block_indents.pop();
+            }
+            _ if node.children().len() == 0 => self.out.push_str(node.text()),
+            _ => self.write_children(node),
+        }
+    }
+
+    fn write_children(&mut self, node: &SyntaxNode) {
+        let children: Vec<_> = node.children().collect();
+        for (i, child) in children.iter().enumerate() {
+            let next = children.get(i + 1).map(|c| c.kind());
+            self.write_node(child, Some(node.kind()), next);
+        }
+    }
+
+    fn write_binary(&mut self, node: &SyntaxNode) {
+        let mut prev_was_space = true;
+        for child in node.children() {
+            if child.kind() == SyntaxKind::Space {
+                if child.text().contains('\n') {
+                    self.out.push_str(child.text());
+                } else {
+                    self.out.push(' ');
+                }
+                prev_was_space = true;
+                continue;
+            }
+            if !prev_was_space {
+                self.out.push(' ');
+            }
+            self.write_node(child, Some(SyntaxKind::Binary), None);
+            prev_was_space = false;
+        }
+    }
+
+    fn write_line_breaks(&mut self, space: &str, indent: &str) {
+        let newlines = space.chars().filter(|&c| c == '\n').count().min(2);
+        for _ in 0..newlines {
+            self.out.push('\n');
+        }
+        self.out.push_str(indent);
+    }
+
+    fn statement_indent(&self, closing: bool) -> String {
+        match self.block_indents.last() {
+            Some(base) if closing => base.clone(),
+            Some(base) => format!("{base}{INDENT}"),
+            None => String::new(),
+        }
+    }
+
+    fn current_line_indent(&self) -> String {
+        let line_start = self.out.rfind('\n').map_or(0, |i| i + 1);
+        self.out[line_start..]
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "= Title\n\n\n\nSome *text* here.  \n\n#let add(a,b) = a+b\n#{\nlet x = 1\n\n\n      if x>0 {\n  add(x,2)\n        }\n    }\n\n\n\n- item #(1+2)\n";
+
+    #[test]
+    fn normalizes_sample_document() {
+        let formatted = format(SAMPLE, ParseMode::Markup).unwrap();
+        assert_eq!(
+            formatted,
+            "= Title\n\nSome *text* here.\n\n#let add(a,b) = a + b\n#{\n  let x = 1\n\n  if x > 0 {\n    add(x,2)\n  }\n}\n\n- item #(1 + 2)\n"
+        );
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        for (text, mode) in [
+            (SAMPLE, ParseMode::Markup),
+            ("let x = 1\n\n\n   x not   in y\nx+-1", ParseMode::Code),
+            ("x + y", ParseMode::Math),
+        ] {
+            let once = format(text, mode).unwrap();
+            let twice = format(&once, mode).unwrap();
+            assert_eq!(once, twice);
+        }
+    }
+
+    #[test]
+    fn rejects_erroneous_input() {
+        assert!(format("#let x =", ParseMode::Markup).is_err());
+    }
+}
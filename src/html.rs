@@ -0,0 +1,287 @@
+use crate::ast::{AstExpr, make_ast_result};
+use crate::parse_mode::ParseMode;
+use crate::parse_options::ParseOptions;
+
+/// Renders the structural subset of a markup document as HTML for quick
+/// previews and search snippets.
+///
+/// Headings, paragraphs, strong/emphasis, lists, links, references, and raw
+/// text map to their HTML counterparts. This is not a renderer: embedded code
+/// and math become `typst-code`/`typst-math` placeholders holding their
+/// escaped source, and `set`/`show` rules have no effect. Only `http`,
+/// `https`, `mailto`, and relative URLs become links; others, such as
+/// `javascript:`, are rendered as their text.
+pub fn markup_to_html(text: &str) -> Result<String, String> {
+    let mode = ParseMode::Markup;
+    let root = mode.parse(text);
+    let options = ParseOptions {
+        element_calls: true,
+        ..Default::default()
+    };
+    let result = make_ast_result(&root, &mode, &options)?;
+    let mut renderer = HtmlRenderer {
+        source: text,
+        out: String::new(),
+    };
+    renderer.write_blocks(&result.root, true);
+    Ok(renderer.out)
+}
+
+struct HtmlRenderer<'a> {
+    source: &'a str,
+    out: String,
+}
+
+impl HtmlRenderer<'_> {
+    /// Writes block-level content. Runs of inline content become paragraphs,
+    /// wrapped in `<p>` only if `paragraphs` is set (tight list items don't).
+    fn write_blocks(&mut self, exprs: &[AstExpr], paragraphs: bool) {
+        let mut inline: Vec<&AstExpr> = vec![];
+        let mut i = 0;
+        while i < exprs.len() {
+            let expr = &exprs[i];
+            i += 1;
+            if !is_block(expr) {
+                inline.push(expr);
+                continue;
+            }
+            self.flush_paragraph(&mut inline, paragraphs);
+            match expr {
+                AstExpr::Heading { depth, body, .. } => {
+                    let level = (*depth).min(6);
+                    self.out.push_str(&format!("<h{level}>"));
+                    self.write_inline(body.iter());
+                    self.out.push_str(&format!("</h{level}>\n"));
+                }
+                AstExpr::ListItem { .. } | AstExpr::EnumItem { .. } | AstExpr::TermItem { .. } => {
+                    let start = i - 1;
+                    while i < exprs.len() {
+                        match &exprs[i] {
+                            AstExpr::Space { .. } => i += 1,
+                            next if next.kind() == expr.kind() => i += 1,
+                            _ => break,
+                        }
+                    }
+                    self.write_list(expr.kind(), &exprs[start..i]);
+                }
+                AstExpr::Raw { lines, lang, .. } => {
+                    match lang {
+                        Some(lang) => self
+                            .out
+                            .push_str(&format!("<pre><code class=\"language-{}\">", escape(lang))),
+                        None => self.out.push_str("<pre><code>"),
+                    }
+                    self.out.push_str(&escape(&lines.join("\n")));
+                    self.out.push_str("</code></pre>\n");
+                }
+                AstExpr::Equation { .. } => {
+                    self.write_placeholder("div", "typst-math", expr);
+                    self.out.push('\n');
+                }
+                _ => {}
+            }
+        }
+        self.flush_paragraph(&mut inline, paragraphs);
+    }
+
+    fn write_list(&mut self, kind: &str, items: &[AstExpr]) {
+        let tag = match kind {
+            "listItem" => "ul",
+            "enumItem" => "ol",
+            _ => "dl",
+        };
+        self.out.push_str(&format!("<{tag}>\n"));
+        for item in items {
+            match item {
+                AstExpr::ListItem { body, .. }
+                | AstExpr::EnumItem {
+                    number: None, body, ..
+                } => {
+                    self.out.push_str("<li>");
+                    self.write_blocks(body, false);
+                    self.out.push_str("</li>\n");
+                }
+                AstExpr::EnumItem {
+                    number: Some(number),
+                    body,
+                    ..
+                } => {
+                    self.out.push_str(&format!("<li value=\"{number}\">"));
+                    self.write_blocks(body, false);
+                    self.out.push_str("</li>\n");
+                }
+                AstExpr::TermItem {
+                    term, description, ..
+                } => {
+                    self.out.push_str("<dt>");
+                    self.write_inline(term.iter());
+                    self.out.push_str("</dt><dd>");
+                    self.write_blocks(description, false);
+                    self.out.push_str("</dd>\n");
+                }
+                _ => {}
+            }
+        }
+        self.out.push_str(&format!("</{tag}>\n"));
+    }
+
+    fn flush_paragraph(&mut self, inline: &mut Vec<&AstExpr>, paragraphs: bool) {
+        let mut renderer = HtmlRenderer {
+            source: self.source,
+            out: String::new(),
+        };
+        renderer.write_inline(inline.drain(..));
+        let content = renderer.out.trim();
+        if content.is_empty() {
+            return;
+        }
+        if paragraphs {
+            self.out.push_str(&format!("<p>{content}</p>\n"));
+        } else {
+            self.out.push_str(content);
+        }
+    }
+
+    fn write_inline<'e>(&mut self, exprs: impl Iterator<Item = &'e AstExpr>) {
+        for expr in exprs {
+            match expr {
+                AstExpr::Text { text, .. } => self.out.push_str(&escape(text)),
+                AstExpr::Space { .. } => self.out.push(' '),
+                AstExpr::Linebreak { .. } => self.out.push_str("<br>"),
+                AstExpr::Escape { character, .. } | AstExpr::Shorthand { character, .. } => {
+                    self.out.push_str(&escape(&character.to_string()))
+                }
+                AstExpr::SmartQuote { double, .. } => {
+                    self.out.push_str(if *double { "&quot;" } else { "&#39;" })
+                }
+                AstExpr::Strong { body, .. } => {
+                    self.out.push_str("<strong>");
+                    self.write_inline(body.iter());
+                    self.out.push_str("</strong>");
+                }
+                AstExpr::Emph { body, .. } => {
+                    self.out.push_str("<em>");
+                    self.write_inline(body.iter());
+                    self.out.push_str("</em>");
+                }
+                AstExpr::Raw { lines, .. } => {
+                    self.out.push_str("<code>");
+                    self.out.push_str(&escape(&lines.join("\n")));
+                    self.out.push_str("</code>");
+                }
+                AstExpr::Link { url, body, .. } => {
+                    let safe = is_safe_url(url);
+                    if safe {
+                        self.out.push_str(&format!("<a href=\"{}\">", escape(url)));
+                    }
+                    match body {
+                        Some(body) => self.write_inline(body.iter()),
+                        None => self.out.push_str(&escape(url)),
+                    }
+                    if safe {
+                        self.out.push_str("</a>");
+                    }
+                }
+                AstExpr::Ref { target, .. } => {
+                    let target = escape(target);
+                    self.out
+                        .push_str(&format!("<a href=\"#{target}\">@{target}</a>"));
+                }
+                AstExpr::ContentBlock { body, .. } => self.write_inline(body.iter()),
+                AstExpr::Label { .. } | AstExpr::Parbreak { .. } => {}
+                AstExpr::Equation { .. } => self.write_placeholder("span", "typst-math", expr),
+                _ => self.write_placeholder("code", "typst-code", expr),
+            }
+        }
+    }
+
+    fn write_placeholder(&mut self, tag: &str, class: &str, expr: &AstExpr) {
+        let source = expr
+            .range()
+            .and_then(|[start, end]| self.source.get(start..end))
+            .unwrap_or_default();
+        self.out.push_str(&format!(
+            "<{tag} class=\"{class}\">{}</{tag}>",
+            escape(source)
+        ));
+    }
+}
+
+fn is_block(expr: &AstExpr) -> bool {
+    matches!(
+        expr,
+        AstExpr::Heading { .. }
+            | AstExpr::ListItem { .. }
+            | AstExpr::EnumItem { .. }
+            | AstExpr::TermItem { .. }
+            | AstExpr::Parbreak { .. }
+            | AstExpr::Raw { block: true, .. }
+            | AstExpr::Equation { block: true, .. }
+    )
+}
+
+/// Whether `url` may become a link: an `http`, `https`, or `mailto` URL, or a
+/// relative or fragment URL without a scheme. Anything else, like a
+/// `javascript:` URL, is rendered as plain text.
+fn is_safe_url(url: &str) -> bool {
+    match url.find([':', '/', '?', '#']) {
+        Some(i) if url[i..].starts_with(':') => {
+            matches!(
+                url[..i].to_ascii_lowercase().as_str(),
+                "http" | "https" | "mailto"
+            )
+        }
+        _ => true,
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_structural_markup() {
+        let text = "= Intro <intro>\n\nSome *bold* and _emph_ text with `a<b` and #f(x).\nSee @intro and https://typst.app.\n\n- one\n- two\n  + nested\n\n```rs\nfn main() {}\n```\n\n$ x^2 $";
+        assert_eq!(
+            markup_to_html(text).unwrap(),
+            "<h1>Intro</h1>\n\
+             <p>Some <strong>bold</strong> and <em>emph</em> text with <code>a&lt;b</code> and <code class=\"typst-code\">f(x)</code>. See <a href=\"#intro\">@intro</a> and <a href=\"https://typst.app\">https://typst.app</a>.</p>\n\
+             <ul>\n<li>one</li>\n<li>two<ol>\n<li>nested</li>\n</ol>\n</li>\n</ul>\n\
+             <pre><code class=\"language-rs\">fn main() {}</code></pre>\n\
+             <div class=\"typst-math\">$ x^2 $</div>\n"
+        );
+    }
+
+    #[test]
+    fn renders_unsafe_links_as_text() {
+        let html = markup_to_html(
+            "#link(\"javascript:alert(1)\")[x] #link(\"JavaScript:alert(1)\")[y] #link(\"data:text/html,z\")[z]",
+        )
+        .unwrap();
+        assert_eq!(html, "<p>x y z</p>\n");
+
+        let html = markup_to_html(
+            "#link(\"mailto:a@b.c\")[a] #link(\"/docs?q=1:2\")[b] #link(\"#top\")[c] http://x.y",
+        )
+        .unwrap();
+        assert_eq!(
+            html,
+            "<p><a href=\"mailto:a@b.c\">a</a> <a href=\"/docs?q=1:2\">b</a> <a href=\"#top\">c</a> <a href=\"http://x.y\">http://x.y</a></p>\n"
+        );
+    }
+}
@@ -1,11 +1,48 @@
-mod ast;
+mod analysis;
+pub mod ast;
 mod cst;
+mod diagnostics;
+mod document;
+mod format;
+mod html;
+mod lint;
+mod newlines;
+mod output;
 mod parse_mode;
+mod parse_options;
+mod range_mapper;
+mod tokens;
+mod xml;
 
-use serde::Deserialize;
+use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
-use parse_mode::ParseMode;
+use crate::range_mapper::RangeMapper;
+
+pub use analysis::{
+    Ancestor, CallEntry, ImportEntry, ImportSource, ImportedName, ImportedNames, Island,
+    IslandKind, LabelEntry, LabelsAndRefs, NodeAtOffset, OutlineEntry, RefEntry, RuleEntry,
+    SelectorKind, WordCount, ancestors_at_offset, collect_imports, collect_labels_and_refs,
+    collect_rules, duplicate_subtrees, find_calls, fingerprint, islands, node_at_offset, outline,
+    query, raw_languages, referenced_idents, word_count,
+};
+pub use cst::{
+    CstNode, CstParseResult, FlatCstNode, FlatCstParseResult, ParseError, Severity, ancestors_at,
+    build_cst, is_valid, parse_pretty, parse_range, parse_sexpr, report_errors, roundtrip_mismatch,
+    verify_roundtrip,
+};
+pub use diagnostics::{format_diagnostics, format_diagnostics_colored};
+pub use document::Document;
+pub use format::format;
+pub use html::markup_to_html;
+pub use lint::{Warning, trailing_whitespace};
+pub use newlines::LineEnding;
+pub use output::kind_table;
+pub use parse_mode::ParseMode;
+pub use parse_options::{OutputFormat, ParseOptions, TextMode};
+pub use range_mapper::OffsetEncoding;
+pub use tokens::{HighlightedToken, Token, highlight, parse_tokens};
+pub use xml::parse_xml;
 
 #[wasm_bindgen(typescript_custom_section)]
 const TS_TYPES: &str = include_str!("types.ts");
@@ -13,43 +50,239 @@ const TS_TYPES: &str = include_str!("types.ts");
 #[wasm_bindgen(start)]
 pub fn start() {}
 
-#[derive(Deserialize, Default)]
-struct ParseOptions {
-    mode: Option<ParseMode>,
+pub(crate) fn read_options(options: JsValue) -> Result<ParseOptions, JsValue> {
+    if options.is_undefined() || options.is_null() {
+        Ok(ParseOptions::default())
+    } else {
+        serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
 }
 
 #[wasm_bindgen(skip_typescript)]
 pub fn parse(text: &str, options: JsValue) -> Result<JsValue, JsValue> {
-    let opts: ParseOptions = if options.is_undefined() || options.is_null() {
-        ParseOptions::default()
-    } else {
-        serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&e.to_string()))?
-    };
+    let opts = read_options(options)?;
+    parse_with(text, &opts)
+}
+
+#[wasm_bindgen(js_name = "parseRange", skip_typescript)]
+pub fn parse_range_js(
+    text: &str,
+    start: usize,
+    end: usize,
+    options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let opts = read_options(options)?
+        .for_range(text, start, end)
+        .map_err(|e| JsValue::from_str(&e))?;
+    parse_with(&text[start..end], &opts)
+}
+
+fn parse_with(text: &str, opts: &ParseOptions) -> Result<JsValue, JsValue> {
+    let (text, removed_crs) = opts.normalize(text);
+    let mode = opts.mode.unwrap_or_default();
+    let root = mode.parse(&text);
+    let mapper = RangeMapper::new(&root, opts).with_removed_crs(removed_crs);
+    match opts.format.unwrap_or_default() {
+        OutputFormat::Cst => cst::make_cst_result(root, opts, mapper),
+        OutputFormat::Ast => make_ast_value(&root, &mode, opts, &mapper),
+    }
+}
+
+#[wasm_bindgen(js_name = "parseSexpr", skip_typescript)]
+pub fn parse_sexpr_js(text: &str, options: JsValue) -> Result<String, JsValue> {
+    let opts = read_options(options)?;
+    Ok(parse_sexpr(text, &opts))
+}
+
+#[wasm_bindgen(js_name = "parsePretty", skip_typescript)]
+pub fn parse_pretty_js(text: &str, options: JsValue) -> Result<String, JsValue> {
+    let opts = read_options(options)?;
+    Ok(parse_pretty(text, &opts))
+}
+
+#[wasm_bindgen(js_name = "parseXml", skip_typescript)]
+pub fn parse_xml_js(text: &str, options: JsValue) -> Result<String, JsValue> {
+    let opts = read_options(options)?;
+    Ok(parse_xml(text, &opts))
+}
 
-    let root = match opts.mode.unwrap_or_default() {
-        ParseMode::Markup => typst_syntax::parse(text),
-        ParseMode::Code => typst_syntax::parse_code(text),
-        ParseMode::Math => typst_syntax::parse_math(text),
-    };
-    cst::make_cst_result(root)
+#[wasm_bindgen(js_name = "parseTokens", skip_typescript)]
+pub fn parse_tokens_js(text: &str, options: JsValue) -> Result<JsValue, JsValue> {
+    let opts = read_options(options)?;
+    serde_wasm_bindgen::to_value(&parse_tokens(text, &opts))
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen(js_name = "highlight", skip_typescript)]
+pub fn highlight_js(text: &str, options: JsValue) -> Result<JsValue, JsValue> {
+    let opts = read_options(options)?;
+    serde_wasm_bindgen::to_value(&highlight(text, &opts))
+        .map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
 #[wasm_bindgen(js_name = "parseAst", skip_typescript)]
 pub fn parse_ast(text: &str, options: JsValue) -> Result<JsValue, JsValue> {
-    let opts: ParseOptions = if options.is_undefined() || options.is_null() {
-        ParseOptions::default()
+    let opts = read_options(options)?;
+    let (text, removed_crs) = opts.normalize(text);
+
+    let mode = opts.mode.unwrap_or_default();
+    let root = mode.parse(&text);
+    let mapper = RangeMapper::new(&root, &opts).with_removed_crs(removed_crs);
+    make_ast_value(&root, &mode, &opts, &mapper)
+}
+
+fn make_ast_value(
+    root: &typst_syntax::SyntaxNode,
+    mode: &ParseMode,
+    opts: &ParseOptions,
+    mapper: &RangeMapper,
+) -> Result<JsValue, JsValue> {
+    let convert = || ast::make_mapped_ast_result(root, mode, opts, mapper);
+    let result = if opts.safe {
+        ast::catch_panic(convert)
+    } else {
+        convert()
+    }
+    .map_err(|e| JsValue::from_str(&e))?;
+    if let Some(errors) = cst::strict_errors(&result.errors, opts) {
+        return Err(errors_to_value(errors));
+    }
+    ast_to_value(&result, opts)
+}
+
+/// The value a strict parse throws, or the message if even that fails.
+pub(crate) fn errors_to_value(errors: &[ParseError]) -> JsValue {
+    serde_wasm_bindgen::to_value(errors).unwrap_or_else(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Serializes a value holding AST nodes with the output adjustments of `opts`.
+fn ast_to_value(value: &impl Serialize, opts: &ParseOptions) -> Result<JsValue, JsValue> {
+    // Node metadata is flattened into each node, which serde emits as a map.
+    let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+    let options = output::OutputOptions::new(opts);
+    if options.is_plain() {
+        value.serialize(&serializer)
     } else {
-        serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&e.to_string()))?
-    };
+        value.serialize(output::OutputSerializer {
+            inner: &serializer,
+            options,
+        })
+    }
+    .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen(js_name = "nodeAtOffset", skip_typescript)]
+pub fn node_at_offset_js(text: &str, offset: usize, options: JsValue) -> Result<JsValue, JsValue> {
+    let opts = read_options(options)?;
+    match node_at_offset(text, offset, &opts).map_err(|e| JsValue::from_str(&e))? {
+        Some(found) => ast_to_value(&found, &opts),
+        None => Ok(JsValue::NULL),
+    }
+}
+
+#[wasm_bindgen(js_name = "ancestorsAtOffset", skip_typescript)]
+pub fn ancestors_at_offset_js(
+    text: &str,
+    offset: usize,
+    options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let opts = read_options(options)?;
+    let path = ancestors_at_offset(text, offset, &opts).map_err(|e| JsValue::from_str(&e))?;
+    ast_to_value(&path, &opts)
+}
+
+#[wasm_bindgen(js_name = "toPlaintext", skip_typescript)]
+pub fn to_plaintext(text: &str, options: JsValue) -> Result<String, JsValue> {
+    let opts = read_options(options)?;
+    ast::to_plaintext(text, &opts).map_err(|e| JsValue::from_str(&e))
+}
+
+#[wasm_bindgen(js_name = "parseWithRecoveryReport", skip_typescript)]
+pub fn parse_with_recovery_report(text: &str, options: JsValue) -> Result<JsValue, JsValue> {
+    let opts = read_options(options)?;
 
     let mode = opts.mode.unwrap_or_default();
-    let root = match &mode {
-        ParseMode::Markup => typst_syntax::parse(text),
-        ParseMode::Code => typst_syntax::parse_code(text),
-        ParseMode::Math => typst_syntax::parse_math(text),
-    };
+    let root = mode.parse(text);
+
+    let report =
+        ast::parse_with_recovery_report(&root, &mode, &opts).map_err(|e| JsValue::from_str(&e))?;
+    let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+    report
+        .serialize(&serializer)
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen(js_name = "outline", skip_typescript)]
+pub fn outline_js(text: &str, options: JsValue) -> Result<JsValue, JsValue> {
+    let opts = read_options(options)?;
+    let outline = outline(text, &opts).map_err(|e| JsValue::from_str(&e))?;
+    serde_wasm_bindgen::to_value(&outline).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen(js_name = "wordCount", skip_typescript)]
+pub fn word_count_js(text: &str, options: JsValue) -> Result<JsValue, JsValue> {
+    let opts = read_options(options)?;
+    let count = word_count(text, &opts).map_err(|e| JsValue::from_str(&e))?;
+    serde_wasm_bindgen::to_value(&count).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen(js_name = "collectLabelsAndRefs", skip_typescript)]
+pub fn collect_labels_and_refs_js(text: &str, options: JsValue) -> Result<JsValue, JsValue> {
+    let opts = read_options(options)?;
+    let found = collect_labels_and_refs(text, &opts).map_err(|e| JsValue::from_str(&e))?;
+    serde_wasm_bindgen::to_value(&found).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen(js_name = "collectImports", skip_typescript)]
+pub fn collect_imports_js(text: &str, options: JsValue) -> Result<JsValue, JsValue> {
+    let opts = read_options(options)?;
+    let imports = collect_imports(text, &opts).map_err(|e| JsValue::from_str(&e))?;
+    serde_wasm_bindgen::to_value(&imports).map_err(|e| JsValue::from_str(&e.to_string()))
+}
 
-    let result = ast::make_ast_result(&root, &mode).map_err(|e| JsValue::from_str(&e))?;
+#[wasm_bindgen(js_name = "kindTable", skip_typescript)]
+pub fn kind_table_js() -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(kind_table()).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen(js_name = "collectRules", skip_typescript)]
+pub fn collect_rules_js(text: &str, options: JsValue) -> Result<JsValue, JsValue> {
+    let opts = read_options(options)?;
+    let rules = collect_rules(text, &opts).map_err(|e| JsValue::from_str(&e))?;
+    serde_wasm_bindgen::to_value(&rules).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen(js_name = "findCalls", skip_typescript)]
+pub fn find_calls_js(text: &str, name: &str, options: JsValue) -> Result<JsValue, JsValue> {
+    let opts = read_options(options)?;
+    let calls = find_calls(text, name, &opts).map_err(|e| JsValue::from_str(&e))?;
+    serde_wasm_bindgen::to_value(&calls).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen(js_name = "isValid", skip_typescript)]
+pub fn is_valid_js(text: &str, options: JsValue) -> Result<bool, JsValue> {
+    let opts = read_options(options)?;
+    Ok(is_valid(text, opts.mode.unwrap_or_default()))
+}
+
+#[wasm_bindgen(js_name = "verifyRoundtrip", skip_typescript)]
+pub fn verify_roundtrip_js(text: &str, options: JsValue) -> Result<bool, JsValue> {
+    let opts = read_options(options)?;
+    Ok(verify_roundtrip(text, &opts))
+}
+
+#[wasm_bindgen(js_name = "roundtripMismatch", skip_typescript)]
+pub fn roundtrip_mismatch_js(text: &str, options: JsValue) -> Result<Option<usize>, JsValue> {
+    let opts = read_options(options)?;
+    Ok(roundtrip_mismatch(text, &opts))
+}
+
+#[wasm_bindgen(js_name = "countAst", skip_typescript)]
+pub fn count_ast(text: &str, options: JsValue) -> Result<usize, JsValue> {
+    let opts = read_options(options)?;
+
+    let mode = opts.mode.unwrap_or_default();
+    let root = mode.parse(text);
 
-    serde_wasm_bindgen::to_value(&result).map_err(|e| JsValue::from_str(&e.to_string()))
+    ast::count_root_exprs(&root, &mode).map_err(|e| JsValue::from_str(&e))
 }
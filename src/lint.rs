@@ -0,0 +1,197 @@
+use serde::Serialize;
+use typst_syntax::{LinkedNode, SyntaxKind, ast};
+
+use crate::cst::node_text;
+use crate::range_mapper::RangeMapper;
+
+#[derive(Serialize, Clone)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+pub struct Warning {
+    pub message: String,
+    pub range: [usize; 2],
+}
+
+pub fn map_warning_ranges(warnings: &mut [Warning], mapper: &RangeMapper) {
+    for warning in warnings {
+        warning.range = mapper.map(warning.range);
+    }
+}
+
+/// Flags indentation that mixes tabs and spaces inside raw blocks and code
+/// blocks. The first indented line of each block decides its style; every
+/// line whose indentation contains the other character is reported.
+pub fn mixed_indentation(root: &LinkedNode) -> Vec<Warning> {
+    let mut warnings = vec![];
+    // An explicit stack, as erroneous trees can be arbitrarily deep.
+    let mut stack = vec![root.clone()];
+    while let Some(node) = stack.pop() {
+        let is_raw_block = node.cast::<ast::Raw>().is_some_and(|raw| raw.block());
+        if is_raw_block || node.kind() == SyntaxKind::CodeBlock {
+            let text = node_text(node.get());
+            warnings.extend(check_indentation(&text, node.offset()));
+            continue;
+        }
+        stack.extend(node.children().rev());
+    }
+    warnings
+}
+
+/// Flags `return` expressions that are not inside any closure or function
+/// definition, which the parser accepts but evaluation rejects.
+pub fn return_outside_closure(root: &LinkedNode) -> Vec<Warning> {
+    let mut warnings = vec![];
+    let mut stack = vec![root.clone()];
+    while let Some(node) = stack.pop() {
+        match node.kind() {
+            SyntaxKind::Closure => {}
+            SyntaxKind::FuncReturn => warnings.push(Warning {
+                message: "`return` outside of a function".into(),
+                range: [node.offset(), node.offset() + node.len()],
+            }),
+            _ => stack.extend(node.children().rev()),
+        }
+    }
+    warnings
+}
+
+/// Flags strong emphasis, emphasis, and headings whose body is empty or only
+/// whitespace, which the parser accepts but is almost always a typo.
+pub fn empty_markup(root: &LinkedNode) -> Vec<Warning> {
+    let mut warnings = vec![];
+    let mut stack = vec![root.clone()];
+    while let Some(node) = stack.pop() {
+        let what = match node.kind() {
+            SyntaxKind::Strong => Some("strong emphasis"),
+            SyntaxKind::Emph => Some("emphasis"),
+            SyntaxKind::Heading => Some("heading"),
+            _ => None,
+        };
+        let blank = node
+            .children()
+            .find(|c| c.kind() == SyntaxKind::Markup)
+            .is_some_and(|body| {
+                body.children()
+                    .all(|c| matches!(c.kind(), SyntaxKind::Space | SyntaxKind::Parbreak))
+            });
+        if let Some(what) = what.filter(|_| blank) {
+            warnings.push(Warning {
+                message: format!("empty {what}"),
+                range: [node.offset(), node.offset() + node.len()],
+            });
+        }
+        stack.extend(node.children().rev());
+    }
+    warnings
+}
+
+/// Finds spaces and tabs at the end of each line, including the last line
+/// when the text does not end with a newline. This is a plain source scan, so
+/// whitespace inside raw blocks and strings is reported too.
+pub fn trailing_whitespace(text: &str) -> Vec<[usize; 2]> {
+    let mut ranges = vec![];
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim_end_matches([' ', '\t']);
+        if trimmed.len() < content.len() {
+            ranges.push([line_start + trimmed.len(), line_start + content.len()]);
+        }
+        line_start += line.len();
+    }
+    ranges
+}
+
+fn check_indentation(text: &str, offset: usize) -> Vec<Warning> {
+    let mut style = None;
+    let mut warnings = vec![];
+    let mut line_start = 0;
+    // The first line starts at the block's opening delimiter, so it has no
+    // indentation of its own.
+    for line in text.split_inclusive('\n').skip(1) {
+        line_start += text[line_start..].find('\n').map_or(0, |i| i + 1);
+        let indent_len = line.len() - line.trim_start_matches([' ', '\t']).len();
+        let indent = &line[..indent_len];
+        let Some(first) = indent.chars().next() else {
+            continue;
+        };
+        let style = *style.get_or_insert(first);
+        if indent.chars().any(|c| c != style) {
+            warnings.push(Warning {
+                message: "indentation mixes tabs and spaces".into(),
+                range: [offset + line_start, offset + line_start + indent_len],
+            });
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(text: &str) -> Vec<[usize; 2]> {
+        let root = typst_syntax::parse(text);
+        mixed_indentation(&LinkedNode::new(&root))
+            .into_iter()
+            .map(|w| w.range)
+            .collect()
+    }
+
+    #[test]
+    fn reports_mixed_indentation_in_code_blocks() {
+        let text = "#{\n  let x = 1\n\tlet y = 2\n \tlet z = 3\n}";
+        assert_eq!(lint(text), [[15, 16], [26, 28]]);
+    }
+
+    #[test]
+    fn reports_mixed_indentation_in_raw_blocks() {
+        let text = "```py\n\tif x:\n\t\tpass\n\t  pass\n```";
+        assert_eq!(lint(text), [[20, 23]]);
+    }
+
+    #[test]
+    fn accepts_consistent_indentation() {
+        assert!(lint("#{\n\tlet x = 1\n\t\tlet y = 2\n}").is_empty());
+        assert!(lint("`\t inline`").is_empty());
+    }
+
+    #[test]
+    fn reports_return_outside_closure() {
+        let text = "#let f(x) = { return x }\n#let g = () => { if true { return } }\n#{ return 1 }";
+        let root = typst_syntax::parse(text);
+        let ranges: Vec<_> = return_outside_closure(&LinkedNode::new(&root))
+            .into_iter()
+            .map(|w| w.range)
+            .collect();
+        let start = text.rfind("return").unwrap();
+        assert_eq!(ranges, [[start, start + "return 1".len()]]);
+    }
+
+    #[test]
+    fn reports_trailing_whitespace() {
+        assert_eq!(
+            trailing_whitespace("a  \nb\t\r\n\n  \nc \t"),
+            [[1, 3], [5, 6], [9, 11], [13, 15]]
+        );
+        assert!(trailing_whitespace("a\nb").is_empty());
+    }
+
+    #[test]
+    fn reports_empty_markup() {
+        let text = "** and _ _ in\n= \n== Title *x*\n=";
+        let root = typst_syntax::parse(text);
+        let warnings: Vec<_> = empty_markup(&LinkedNode::new(&root))
+            .into_iter()
+            .map(|w| (w.message, w.range))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                ("empty strong emphasis".to_string(), [0, 2]),
+                ("empty emphasis".to_string(), [7, 10]),
+                ("empty heading".to_string(), [14, 15]),
+                ("empty heading".to_string(), [30, 31]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use std::borrow::Cow;
+
+use serde::Serialize;
+use typst_syntax::SyntaxNode;
+
+/// The line ending a text uses.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(test, derive(ts_rs::TS))]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    /// Only `\n`, or no line breaks at all.
+    Lf,
+    /// Only `\r\n`.
+    Crlf,
+    /// More than one kind, counting a lone `\r` as one of its own.
+    Mixed,
+}
+
+/// The line ending of the text `root` was parsed from, taking the `\r`s
+/// removed by normalization (see `ParseOptions::normalize`) into account.
+pub fn line_ending(root: &SyntaxNode, removed_crs: Option<&[usize]>) -> LineEnding {
+    let mut counts = [0usize; 3];
+    let mut after_cr = false;
+    // An explicit stack, as erroneous trees can be arbitrarily deep.
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        for c in node.text().chars() {
+            match c {
+                '\n' if after_cr => counts[1] += 1,
+                '\n' => counts[0] += 1,
+                _ if after_cr => counts[2] += 1,
+                _ => {}
+            }
+            after_cr = c == '\r';
+        }
+        stack.extend(node.children().rev());
+    }
+    if after_cr {
+        counts[2] += 1;
+    }
+    // Normalization turned these CRLFs into the LFs counted above.
+    let removed = removed_crs.map_or(0, <[usize]>::len);
+    counts[0] = counts[0].saturating_sub(removed);
+    counts[1] += removed;
+    match counts {
+        [_, 0, 0] => LineEnding::Lf,
+        [0, _, 0] => LineEnding::Crlf,
+        _ => LineEnding::Mixed,
+    }
+}
+
+/// Replaces every `\r\n` of `text` by `\n`, returning the new text and the
+/// byte offset in it of each `\n` that lost its `\r`.
+pub fn normalize_newlines(text: &str) -> (Cow<'_, str>, Vec<usize>) {
+    if !text.contains("\r\n") {
+        return (Cow::Borrowed(text), vec![]);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut removed = vec![];
+    let mut rest = text;
+    while let Some(i) = rest.find("\r\n") {
+        out.push_str(&rest[..i]);
+        removed.push(out.len());
+        out.push('\n');
+        rest = &rest[i + 2..];
+    }
+    out.push_str(rest);
+    (Cow::Owned(out), removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_line_endings() {
+        let ending = |text| line_ending(&typst_syntax::parse(text), None);
+        assert_eq!(ending("a\nb\n"), LineEnding::Lf);
+        assert_eq!(ending("no breaks"), LineEnding::Lf);
+        assert_eq!(ending("= a\r\n```\r\nb\r\n```\r\n"), LineEnding::Crlf);
+        assert_eq!(ending("a\r\nb\nc"), LineEnding::Mixed);
+        assert_eq!(ending("a\rb"), LineEnding::Mixed);
+
+        let (text, removed) = normalize_newlines("a\r\nb\r\n");
+        assert_eq!((text.as_ref(), removed.as_slice()), ("a\nb\n", &[1, 3][..]));
+        let root = typst_syntax::parse(&text);
+        assert_eq!(line_ending(&root, Some(&removed)), LineEnding::Crlf);
+        let (text, removed) = normalize_newlines("a\r\nb\n");
+        let root = typst_syntax::parse(&text);
+        assert_eq!(line_ending(&root, Some(&removed)), LineEnding::Mixed);
+    }
+}
@@ -0,0 +1,975 @@
+//! Output adjustments applied while serializing an AST: integer `kind`
+//! discriminators, numbers a JavaScript number cannot hold exactly, and
+//! leaving out fields without a value.
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use serde::ser::{self, Impossible, Serialize, Serializer};
+
+use crate::parse_options::ParseOptions;
+
+/// Every `kind` name the AST can emit; a kind's id is its index. Kinds are
+/// only ever appended, so ids stay stable across releases. Nested enums
+/// (arguments, patterns, imports, ...) share ids with same-named kinds.
+pub const KIND_TABLE: &[&str] = &[
+    "text",
+    "space",
+    "linebreak",
+    "parbreak",
+    "escape",
+    "shorthand",
+    "smartQuote",
+    "strong",
+    "emph",
+    "raw",
+    "link",
+    "label",
+    "ref",
+    "heading",
+    "listItem",
+    "enumItem",
+    "termItem",
+    "equation",
+    "math",
+    "mathText",
+    "mathIdent",
+    "mathShorthand",
+    "mathAlignPoint",
+    "mathDelimited",
+    "mathAttach",
+    "mathPrimes",
+    "mathFrac",
+    "mathRoot",
+    "ident",
+    "none",
+    "auto",
+    "bool",
+    "int",
+    "float",
+    "numeric",
+    "str",
+    "codeBlock",
+    "contentBlock",
+    "parenthesized",
+    "array",
+    "dict",
+    "unary",
+    "binary",
+    "fieldAccess",
+    "funcCall",
+    "closure",
+    "letBinding",
+    "destructAssignment",
+    "setRule",
+    "showRule",
+    "contextual",
+    "conditional",
+    "whileLoop",
+    "forLoop",
+    "moduleImport",
+    "moduleInclude",
+    "loopBreak",
+    "loopContinue",
+    "funcReturn",
+    "unknown",
+    "character",
+    "number",
+    "pos",
+    "named",
+    "keyed",
+    "spread",
+    "normal",
+    "placeholder",
+    "destructuring",
+    "pattern",
+    "wildcard",
+    "items",
+    "simple",
+    "renamed",
+    "truncated",
+];
+
+/// The name of every kind id, see [`KIND_TABLE`].
+pub fn kind_table() -> &'static [&'static str] {
+    KIND_TABLE
+}
+
+pub fn kind_id(kind: &str) -> Option<usize> {
+    static IDS: LazyLock<HashMap<&str, usize>> = LazyLock::new(|| {
+        KIND_TABLE
+            .iter()
+            .enumerate()
+            .map(|(id, &kind)| (kind, id))
+            .collect()
+    });
+    IDS.get(kind).copied()
+}
+
+/// The largest integer a JavaScript number represents exactly.
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// Which adjustments [`OutputSerializer`] applies.
+#[derive(Clone, Copy, Default)]
+pub struct OutputOptions {
+    /// Add a `kindId` field with its id next to every `kind` field whose
+    /// value is found in [`KIND_TABLE`].
+    pub numeric_kinds: bool,
+    /// Replace the value of every such `kind` field by its id instead.
+    pub kinds_only_numeric: bool,
+    /// Emit integers outside the safe integer range as decimal strings.
+    pub big_int_as_string: bool,
+    /// Emit non-finite floats as `"inf"`, `"-inf"`, or `"nan"`.
+    pub safe_floats: bool,
+    /// Leave out fields of AST nodes, maps and structs with a `kind` field,
+    /// that are `null` or an empty array.
+    pub compact: bool,
+}
+
+impl OutputOptions {
+    pub fn new(options: &ParseOptions) -> Self {
+        OutputOptions {
+            numeric_kinds: options.numeric_kinds,
+            kinds_only_numeric: options.kinds_only_numeric,
+            big_int_as_string: options.big_int_as_string,
+            safe_floats: options.safe_floats,
+            compact: options.compact,
+        }
+    }
+
+    /// Whether no adjustment is enabled, so serializing can skip the wrapper.
+    pub fn is_plain(&self) -> bool {
+        !self.numeric_kinds
+            && !self.kinds_only_numeric
+            && !self.big_int_as_string
+            && !self.safe_floats
+            && !self.compact
+    }
+
+    fn wrap<'a, T: ?Sized>(self, value: &'a T) -> Wrap<'a, T> {
+        Wrap {
+            value,
+            options: self,
+        }
+    }
+
+    /// The id to emit for a `kind` field's value, if it is a known kind.
+    fn numeric_kind<T: Serialize + ?Sized>(self, key: &str, value: &T) -> Option<usize> {
+        if !(self.numeric_kinds || self.kinds_only_numeric) || key != "kind" {
+            return None;
+        }
+        kind_id(&value.serialize(StrValue).ok()?)
+    }
+
+    /// Whether a field of an AST node holding `value` is left out.
+    fn omits<T: Serialize + ?Sized>(self, value: &T) -> bool {
+        self.compact && value.serialize(Emptiness).is_ok()
+    }
+}
+
+/// Wraps a serializer, applying the adjustments enabled in `options`.
+/// Everything else is passed through unchanged.
+pub struct OutputSerializer<S> {
+    pub inner: S,
+    pub options: OutputOptions,
+}
+
+struct Wrap<'a, T: ?Sized> {
+    value: &'a T,
+    options: OutputOptions,
+}
+
+impl<T: Serialize + ?Sized> Serialize for Wrap<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize(OutputSerializer {
+            inner: serializer,
+            options: self.options,
+        })
+    }
+}
+
+macro_rules! forward {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(fn $method(self, $($arg: $ty),*) -> Result<Self::Ok, Self::Error> {
+            self.inner.$method($($arg),*)
+        })*
+    };
+}
+
+impl<S: Serializer> Serializer for OutputSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = Compound<S::SerializeSeq>;
+    type SerializeTuple = Compound<S::SerializeTuple>;
+    type SerializeTupleStruct = Compound<S::SerializeTupleStruct>;
+    type SerializeTupleVariant = Compound<S::SerializeTupleVariant>;
+    type SerializeMap = Compound<S::SerializeMap>;
+    type SerializeStruct = Compound<S::SerializeStruct>;
+    type SerializeStructVariant = Compound<S::SerializeStructVariant>;
+
+    forward! {
+        serialize_bool(v: bool);
+        serialize_i8(v: i8);
+        serialize_i16(v: i16);
+        serialize_i32(v: i32);
+        serialize_i128(v: i128);
+        serialize_u8(v: u8);
+        serialize_u16(v: u16);
+        serialize_u32(v: u32);
+        serialize_u64(v: u64);
+        serialize_u128(v: u128);
+        serialize_f32(v: f32);
+        serialize_char(v: char);
+        serialize_str(v: &str);
+        serialize_bytes(v: &[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str);
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<S::Ok, S::Error> {
+        if self.options.big_int_as_string && !(-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&v) {
+            self.inner.serialize_str(&v.to_string())
+        } else {
+            self.inner.serialize_i64(v)
+        }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<S::Ok, S::Error> {
+        match v {
+            _ if !self.options.safe_floats || v.is_finite() => self.inner.serialize_f64(v),
+            f64::INFINITY => self.inner.serialize_str("inf"),
+            f64::NEG_INFINITY => self.inner.serialize_str("-inf"),
+            _ => self.inner.serialize_str("nan"),
+        }
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_some(&self.options.wrap(value))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner
+            .serialize_newtype_struct(name, &self.options.wrap(value))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner
+            .serialize_newtype_variant(name, index, variant, &self.options.wrap(value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        self.inner
+            .serialize_seq(len)
+            .map(|inner| Compound::new(inner, self.options))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        self.inner
+            .serialize_tuple(len)
+            .map(|inner| Compound::new(inner, self.options))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        self.inner
+            .serialize_tuple_struct(name, len)
+            .map(|inner| Compound::new(inner, self.options))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        self.inner
+            .serialize_tuple_variant(name, index, variant, len)
+            .map(|inner| Compound::new(inner, self.options))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        self.inner
+            .serialize_map(len)
+            .map(|inner| Compound::new(inner, self.options))
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        self.inner
+            .serialize_struct(name, len)
+            .map(|inner| Compound::new(inner, self.options))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        self.inner
+            .serialize_struct_variant(name, index, variant, len)
+            .map(|inner| Compound::new(inner, self.options))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+pub struct Compound<C> {
+    inner: C,
+    options: OutputOptions,
+    /// Whether the map key just written was `kind`.
+    kind_key: bool,
+    /// Whether a `kind` field was written, which makes this an AST node.
+    /// Tags come first, so this is known before any other field.
+    node: bool,
+}
+
+impl<C> Compound<C> {
+    fn new(inner: C, options: OutputOptions) -> Self {
+        Compound {
+            inner,
+            options,
+            kind_key: false,
+            node: false,
+        }
+    }
+}
+
+macro_rules! compound {
+    ($($trait:ident::$method:ident),*) => {
+        $(impl<C: ser::$trait> ser::$trait for Compound<C> {
+            type Ok = C::Ok;
+            type Error = C::Error;
+
+            fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+                self.inner.$method(&self.options.wrap(value))
+            }
+
+            fn end(self) -> Result<C::Ok, C::Error> {
+                self.inner.end()
+            }
+        })*
+    };
+}
+
+compound!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field
+);
+
+impl<C: ser::SerializeMap> ser::SerializeMap for Compound<C> {
+    type Ok = C::Ok;
+    type Error = C::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), C::Error> {
+        self.kind_key = key.serialize(StrValue).is_ok_and(|key| key == "kind");
+        self.node |= self.kind_key;
+        self.inner.serialize_key(&self.options.wrap(key))
+    }
+
+    fn serialize_entry<K: Serialize + ?Sized, V: Serialize + ?Sized>(
+        &mut self,
+        key: &K,
+        value: &V,
+    ) -> Result<(), C::Error> {
+        if self.node && self.options.omits(value) {
+            return Ok(());
+        }
+        self.serialize_key(key)?;
+        self.serialize_value(value)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), C::Error> {
+        let id = if self.kind_key {
+            self.options.numeric_kind("kind", value)
+        } else {
+            None
+        };
+        self.kind_key = false;
+        match id {
+            Some(id) if self.options.kinds_only_numeric => self.inner.serialize_value(&id),
+            Some(id) => {
+                self.inner.serialize_value(&self.options.wrap(value))?;
+                self.inner.serialize_entry("kindId", &id)
+            }
+            None => self.inner.serialize_value(&self.options.wrap(value)),
+        }
+    }
+
+    fn end(self) -> Result<C::Ok, C::Error> {
+        self.inner.end()
+    }
+}
+
+macro_rules! compound_struct {
+    ($($trait:ident),*) => {
+        $(impl<C: ser::$trait> ser::$trait for Compound<C> {
+            type Ok = C::Ok;
+            type Error = C::Error;
+
+            fn serialize_field<T: Serialize + ?Sized>(
+                &mut self,
+                key: &'static str,
+                value: &T,
+            ) -> Result<(), C::Error> {
+                self.node |= key == "kind";
+                if self.node && self.options.omits(value) {
+                    return self.inner.skip_field(key);
+                }
+                match self.options.numeric_kind(key, value) {
+                    Some(id) if self.options.kinds_only_numeric => {
+                        self.inner.serialize_field(key, &id)
+                    }
+                    Some(id) => {
+                        self.inner.serialize_field(key, &self.options.wrap(value))?;
+                        self.inner.serialize_field("kindId", &id)
+                    }
+                    None => self.inner.serialize_field(key, &self.options.wrap(value)),
+                }
+            }
+
+            fn skip_field(&mut self, key: &'static str) -> Result<(), C::Error> {
+                self.inner.skip_field(key)
+            }
+
+            fn end(self) -> Result<C::Ok, C::Error> {
+                self.inner.end()
+            }
+        })*
+    };
+}
+
+compound_struct!(SerializeStruct, SerializeStructVariant);
+
+/// Extracts a string value; fails for anything else.
+struct StrValue;
+
+#[derive(Debug)]
+struct NotAStr;
+
+impl std::fmt::Display for NotAStr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("not a string")
+    }
+}
+
+impl std::error::Error for NotAStr {}
+
+impl ser::Error for NotAStr {
+    fn custom<T: std::fmt::Display>(_: T) -> Self {
+        NotAStr
+    }
+}
+
+macro_rules! reject {
+    ($ok:ty, $err:ident; $($method:ident($($ty:ty),*);)*) => {
+        $(fn $method(self, $(_: $ty),*) -> Result<$ok, $err> {
+            Err($err)
+        })*
+    };
+}
+
+impl Serializer for StrValue {
+    type Ok = String;
+    type Error = NotAStr;
+    type SerializeSeq = Impossible<String, NotAStr>;
+    type SerializeTuple = Impossible<String, NotAStr>;
+    type SerializeTupleStruct = Impossible<String, NotAStr>;
+    type SerializeTupleVariant = Impossible<String, NotAStr>;
+    type SerializeMap = Impossible<String, NotAStr>;
+    type SerializeStruct = Impossible<String, NotAStr>;
+    type SerializeStructVariant = Impossible<String, NotAStr>;
+
+    fn serialize_str(self, v: &str) -> Result<String, NotAStr> {
+        Ok(v.to_string())
+    }
+
+    reject! {
+        String, NotAStr;
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _: &T) -> Result<String, NotAStr> {
+        Err(NotAStr)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: &T,
+    ) -> Result<String, NotAStr> {
+        Err(NotAStr)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<String, NotAStr> {
+        Err(NotAStr)
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, NotAStr> {
+        Err(NotAStr)
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, NotAStr> {
+        Err(NotAStr)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, NotAStr> {
+        Err(NotAStr)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, NotAStr> {
+        Err(NotAStr)
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, NotAStr> {
+        Err(NotAStr)
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, NotAStr> {
+        Err(NotAStr)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, NotAStr> {
+        Err(NotAStr)
+    }
+}
+
+/// Succeeds only for `null` and empty arrays.
+struct Emptiness;
+
+#[derive(Debug)]
+struct NotEmpty;
+
+impl std::fmt::Display for NotEmpty {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("not empty")
+    }
+}
+
+impl std::error::Error for NotEmpty {}
+
+impl ser::Error for NotEmpty {
+    fn custom<T: std::fmt::Display>(_: T) -> Self {
+        NotEmpty
+    }
+}
+
+/// A sequence that turns out to have elements is not empty.
+struct EmptySeq;
+
+impl ser::SerializeSeq for EmptySeq {
+    type Ok = ();
+    type Error = NotEmpty;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, _: &T) -> Result<(), NotEmpty> {
+        Err(NotEmpty)
+    }
+
+    fn end(self) -> Result<(), NotEmpty> {
+        Ok(())
+    }
+}
+
+impl Serializer for Emptiness {
+    type Ok = ();
+    type Error = NotEmpty;
+    type SerializeSeq = EmptySeq;
+    type SerializeTuple = Impossible<(), NotEmpty>;
+    type SerializeTupleStruct = Impossible<(), NotEmpty>;
+    type SerializeTupleVariant = Impossible<(), NotEmpty>;
+    type SerializeMap = Impossible<(), NotEmpty>;
+    type SerializeStruct = Impossible<(), NotEmpty>;
+    type SerializeStructVariant = Impossible<(), NotEmpty>;
+
+    fn serialize_none(self) -> Result<(), NotEmpty> {
+        Ok(())
+    }
+
+    fn serialize_unit(self) -> Result<(), NotEmpty> {
+        Ok(())
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<EmptySeq, NotEmpty> {
+        match len {
+            Some(0) | None => Ok(EmptySeq),
+            Some(_) => Err(NotEmpty),
+        }
+    }
+
+    reject! {
+        (), NotEmpty;
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    reject! {
+        Impossible<(), NotEmpty>, NotEmpty;
+        serialize_tuple(usize);
+        serialize_tuple_struct(&'static str, usize);
+        serialize_tuple_variant(&'static str, u32, &'static str, usize);
+        serialize_map(Option<usize>);
+        serialize_struct(&'static str, usize);
+        serialize_struct_variant(&'static str, u32, &'static str, usize);
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _: &T) -> Result<(), NotEmpty> {
+        Err(NotEmpty)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), NotEmpty> {
+        Err(NotEmpty)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), NotEmpty> {
+        Err(NotEmpty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::ParseOptions;
+    use crate::analysis::for_each_expr;
+    use crate::ast::make_ast_result;
+    use crate::parse_mode::ParseMode;
+
+    #[test]
+    fn kind_ids_are_unique() {
+        let unique: HashSet<_> = KIND_TABLE.iter().collect();
+        assert_eq!(unique.len(), KIND_TABLE.len());
+        for (id, kind) in KIND_TABLE.iter().enumerate() {
+            assert_eq!(kind_id(kind), Some(id));
+        }
+        assert_eq!(kind_id("nope"), None);
+    }
+
+    #[test]
+    fn kind_ids_are_only_appended() {
+        // Ids are public: extend this snapshot when appending a kind, but
+        // never reorder or remove an entry.
+        assert_eq!(
+            KIND_TABLE,
+            [
+                "text",
+                "space",
+                "linebreak",
+                "parbreak",
+                "escape",
+                "shorthand",
+                "smartQuote",
+                "strong",
+                "emph",
+                "raw",
+                "link",
+                "label",
+                "ref",
+                "heading",
+                "listItem",
+                "enumItem",
+                "termItem",
+                "equation",
+                "math",
+                "mathText",
+                "mathIdent",
+                "mathShorthand",
+                "mathAlignPoint",
+                "mathDelimited",
+                "mathAttach",
+                "mathPrimes",
+                "mathFrac",
+                "mathRoot",
+                "ident",
+                "none",
+                "auto",
+                "bool",
+                "int",
+                "float",
+                "numeric",
+                "str",
+                "codeBlock",
+                "contentBlock",
+                "parenthesized",
+                "array",
+                "dict",
+                "unary",
+                "binary",
+                "fieldAccess",
+                "funcCall",
+                "closure",
+                "letBinding",
+                "destructAssignment",
+                "setRule",
+                "showRule",
+                "contextual",
+                "conditional",
+                "whileLoop",
+                "forLoop",
+                "moduleImport",
+                "moduleInclude",
+                "loopBreak",
+                "loopContinue",
+                "funcReturn",
+                "unknown",
+                "character",
+                "number",
+                "pos",
+                "named",
+                "keyed",
+                "spread",
+                "normal",
+                "placeholder",
+                "destructuring",
+                "pattern",
+                "wildcard",
+                "items",
+                "simple",
+                "renamed",
+                "truncated",
+            ]
+        );
+    }
+
+    #[test]
+    fn replaces_kinds_by_ids() {
+        let text = "= Hi\n#let f(x, ..rest) = x + 1\n#import \"a.typ\": b as c\n$x^2$";
+        let root = ParseMode::Markup.parse(text);
+        let result = make_ast_result(&root, &ParseMode::Markup, &ParseOptions::default()).unwrap();
+        for_each_expr(&result.root, &mut |expr| {
+            assert!(kind_id(expr.kind()).is_some(), "{}", expr.kind());
+        });
+
+        let plain = serde_json::to_value(&result).unwrap();
+        let numeric = result
+            .serialize(OutputSerializer {
+                inner: serde_json::value::Serializer,
+                options: OutputOptions {
+                    kinds_only_numeric: true,
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+        assert_eq!(numeric["root"][0]["kind"], 13);
+        assert_eq!(numeric["root"][0]["depth"], plain["root"][0]["depth"]);
+
+        // Every kind is replaced, including those of nested enums.
+        fn check(plain: &serde_json::Value, numeric: &serde_json::Value) {
+            match (plain, numeric) {
+                (serde_json::Value::Object(plain), serde_json::Value::Object(numeric)) => {
+                    for (key, value) in plain {
+                        if key == "kind" {
+                            let id = kind_id(value.as_str().unwrap()).unwrap();
+                            assert_eq!(numeric[key], id);
+                        } else {
+                            check(value, &numeric[key]);
+                        }
+                    }
+                }
+                (serde_json::Value::Array(plain), serde_json::Value::Array(numeric)) => {
+                    plain.iter().zip(numeric).for_each(|(p, n)| check(p, n));
+                }
+                _ => assert_eq!(plain, numeric),
+            }
+        }
+        check(&plain, &numeric);
+    }
+
+    #[test]
+    fn adds_kind_ids_next_to_kinds() {
+        let text = "#f(x: 1)";
+        let root = ParseMode::Markup.parse(text);
+        let result = make_ast_result(&root, &ParseMode::Markup, &ParseOptions::default()).unwrap();
+        let value = result
+            .serialize(OutputSerializer {
+                inner: serde_json::value::Serializer,
+                options: OutputOptions {
+                    numeric_kinds: true,
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+        let call = &value["root"][0];
+        assert_eq!(call["kind"], "funcCall");
+        assert_eq!(call["kindId"], kind_id("funcCall").unwrap());
+        let arg = &call["args"][0];
+        assert_eq!(arg["kind"], "named");
+        assert_eq!(
+            kind_table()[arg["kindId"].as_u64().unwrap() as usize],
+            "named"
+        );
+        assert_eq!(arg["expr"]["kindId"], kind_id("int").unwrap());
+    }
+
+    #[test]
+    fn emits_big_ints_as_strings() {
+        let int_value = |text: &str, big_int_as_string| {
+            let root = ParseMode::Code.parse(text);
+            let result =
+                make_ast_result(&root, &ParseMode::Code, &ParseOptions::default()).unwrap();
+            let options = OutputOptions {
+                big_int_as_string,
+                ..Default::default()
+            };
+            let value = result.serialize(OutputSerializer {
+                inner: serde_json::value::Serializer,
+                options,
+            });
+            value.unwrap()["root"][0]["value"].clone()
+        };
+
+        let value = int_value("9007199254740993", true);
+        assert_eq!(value, "9007199254740993");
+        assert_eq!(value.as_str().unwrap().parse(), Ok(9007199254740993i64));
+        assert!(int_value("9007199254740993", false).is_number());
+        assert_eq!(int_value("9007199254740991", true), 9007199254740991i64);
+    }
+
+    #[test]
+    fn emits_non_finite_floats_as_strings() {
+        let values = |text: &str, safe_floats| {
+            let root = ParseMode::Code.parse(text);
+            let result =
+                make_ast_result(&root, &ParseMode::Code, &ParseOptions::default()).unwrap();
+            let options = OutputOptions {
+                safe_floats,
+                ..Default::default()
+            };
+            let value = result.serialize(OutputSerializer {
+                inner: serde_json::value::Serializer,
+                options,
+            });
+            let value = value.unwrap()["root"].clone();
+            value.as_array().unwrap().clone()
+        };
+
+        let floats = values("1e400\n-1e400\n2.5", true);
+        assert_eq!(floats[0]["value"], "inf");
+        assert_eq!(floats[1]["expr"]["value"], "inf");
+        assert_eq!(floats[2]["value"], 2.5);
+        assert!(values("1e400", false)[0]["value"].is_null());
+    }
+
+    #[test]
+    fn omits_empty_fields_when_compact() {
+        let text = "#f()\n#f(..)\n```\nx\n```";
+        let root = ParseMode::Markup.parse(text);
+        let result = make_ast_result(&root, &ParseMode::Markup, &ParseOptions::default()).unwrap();
+        let serialize = |compact| {
+            let options = OutputOptions {
+                compact,
+                ..Default::default()
+            };
+            let value = result.serialize(OutputSerializer {
+                inner: serde_json::value::Serializer,
+                options,
+            });
+            value.unwrap()
+        };
+
+        let plain = serde_json::to_value(&result).unwrap();
+        assert_eq!(serialize(false), plain);
+
+        let compact = serialize(true);
+        // Fields outside of AST nodes are kept.
+        assert_eq!(compact["warnings"], serde_json::json!([]));
+        assert_eq!(compact["errors"][0]["hints"], serde_json::json!([]));
+        assert!(plain["root"][0]["args"].as_array().unwrap().is_empty());
+        assert!(compact["root"][0].get("args").is_none());
+        assert!(compact["root"][0]["callee"].get("range").is_some());
+        // The missing expression after `..` is a placeholder without a range.
+        let spread = &compact["root"][2]["args"][0];
+        assert_eq!(spread["expr"], serde_json::json!({"kind": "none"}));
+        assert!(spread.get("sinkIdent").is_none());
+        let raw = &compact["root"][4];
+        assert!(plain["root"][4]["lang"].is_null());
+        assert!(raw.get("lang").is_none());
+        assert_eq!(raw["lines"], serde_json::json!(["x"]));
+    }
+}
@@ -1,10 +1,133 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use typst_syntax::{SyntaxKind, SyntaxNode};
 
-#[derive(Deserialize, Default)]
+#[derive(Deserialize, Serialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(test, derive(ts_rs::TS))]
 #[serde(rename_all = "lowercase")]
 pub enum ParseMode {
     #[default]
     Markup,
     Code,
     Math,
+    /// Picks one of the other modes from the text, see [`ParseMode::detect`].
+    Auto,
+}
+
+impl ParseMode {
+    pub fn parse(self, text: &str) -> SyntaxNode {
+        match self {
+            ParseMode::Markup => typst_syntax::parse(text),
+            ParseMode::Code => typst_syntax::parse_code(text),
+            ParseMode::Math => typst_syntax::parse_math(text),
+            ParseMode::Auto => {
+                let markup = typst_syntax::parse(text);
+                match detect_in(&markup) {
+                    // Parse what is inside the `$`s or after the `#`, keeping
+                    // those as leaves so offsets and text stay the same.
+                    ParseMode::Math => {
+                        let inner = typst_syntax::parse_math(&text[1..text.len() - 1]);
+                        let mut children = vec![SyntaxNode::leaf(SyntaxKind::Dollar, "$")];
+                        children.extend(inner.children().cloned());
+                        children.push(SyntaxNode::leaf(SyntaxKind::Dollar, "$"));
+                        SyntaxNode::inner(SyntaxKind::Math, children)
+                    }
+                    ParseMode::Code => {
+                        let inner = typst_syntax::parse_code(&text[1..]);
+                        let mut children = vec![SyntaxNode::leaf(SyntaxKind::Hash, "#")];
+                        children.extend(inner.children().cloned());
+                        SyntaxNode::inner(SyntaxKind::Code, children)
+                    }
+                    _ => markup,
+                }
+            }
+        }
+    }
+
+    /// The mode [`ParseMode::Auto`] picks for `text`; other modes return
+    /// themselves. The text is math if it is a single equation from the first
+    /// to the last character, like `$x^2$`, and code if it is a single
+    /// expression behind a `#`, like `#f(x)`, followed by nothing but
+    /// whitespace. Anything else is markup.
+    pub fn detect(self, text: &str) -> ParseMode {
+        match self {
+            ParseMode::Auto => detect_in(&typst_syntax::parse(text)),
+            mode => mode,
+        }
+    }
+
+    /// The mode `root` was parsed in, as a result of [`ParseMode::parse`].
+    pub fn of_root(root: &SyntaxNode) -> ParseMode {
+        match root.kind() {
+            SyntaxKind::Code => ParseMode::Code,
+            SyntaxKind::Math => ParseMode::Math,
+            _ => ParseMode::Markup,
+        }
+    }
+}
+
+fn detect_in(markup: &SyntaxNode) -> ParseMode {
+    match markup.children().as_slice() {
+        [equation] if equation.kind() == SyntaxKind::Equation && is_closed(equation) => {
+            ParseMode::Math
+        }
+        [hash, expr, rest @ ..]
+            if hash.kind() == SyntaxKind::Hash
+                && expr.is::<typst_syntax::ast::Expr>()
+                && rest.iter().all(|node| node.kind() == SyntaxKind::Space) =>
+        {
+            ParseMode::Code
+        }
+        _ => ParseMode::Markup,
+    }
+}
+
+/// Whether `equation` ends with a `$` of its own, unlike an unclosed one.
+fn is_closed(equation: &SyntaxNode) -> bool {
+    matches!(
+        equation.children().as_slice(),
+        [open, .., close] if open.kind() == SyntaxKind::Dollar && close.kind() == SyntaxKind::Dollar
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cst::{build_cst, verify_roundtrip};
+    use crate::parse_options::ParseOptions;
+
+    #[test]
+    fn detects_the_mode() {
+        let detect = |text| ParseMode::Auto.detect(text);
+        assert_eq!(detect("$x^2$"), ParseMode::Math);
+        assert_eq!(detect("$ a + b $"), ParseMode::Math);
+        assert_eq!(detect("#f(x, [y])\n"), ParseMode::Code);
+        assert_eq!(detect("#let x = 1"), ParseMode::Code);
+        assert_eq!(detect("$a$ and $b$"), ParseMode::Markup);
+        assert_eq!(detect("$"), ParseMode::Markup);
+        assert_eq!(detect("$x"), ParseMode::Markup);
+        assert_eq!(detect("#f(x) text"), ParseMode::Markup);
+        assert_eq!(detect("= Heading"), ParseMode::Markup);
+        assert_eq!(detect(""), ParseMode::Markup);
+        assert_eq!(ParseMode::Code.detect("= Heading"), ParseMode::Code);
+    }
+
+    #[test]
+    fn keeps_delimiters_in_the_tree() {
+        for (text, mode) in [
+            ("$x^2$", ParseMode::Math),
+            ("#f(x)", ParseMode::Code),
+            ("*hi*", ParseMode::Markup),
+        ] {
+            let root = ParseMode::Auto.parse(text);
+            assert_eq!(ParseMode::of_root(&root), mode);
+            assert_eq!(root.clone().into_text(), text);
+            assert!(!root.erroneous(), "{text:?} should parse without errors");
+            let options = ParseOptions {
+                mode: Some(ParseMode::Auto),
+                ..Default::default()
+            };
+            assert!(verify_roundtrip(text, &options));
+            assert_eq!(build_cst(&root, &options).detected_mode, Some(mode));
+        }
+    }
 }
@@ -0,0 +1,194 @@
+use std::borrow::Cow;
+
+use serde::Deserialize;
+
+use crate::newlines::normalize_newlines;
+use crate::parse_mode::ParseMode;
+use crate::range_mapper::OffsetEncoding;
+#[cfg(doc)]
+use crate::range_mapper::RangeMapper;
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ParseOptions {
+    pub mode: Option<ParseMode>,
+    /// Trim leading/trailing whitespace from markup `Text` nodes, shrinking
+    /// their ranges to match. Non-breaking spaces (U+00A0, U+2007, U+202F)
+    /// are kept. Off by default since it alters content.
+    pub trim_text: bool,
+    /// Added to every emitted range, for fragments cut out of a larger file.
+    pub base_offset: usize,
+    /// Unit of every emitted offset. `base_offset` is counted in the same unit.
+    /// Also accepted as `rangeEncoding`.
+    #[serde(alias = "rangeEncoding")]
+    pub offset_encoding: OffsetEncoding,
+    /// Convert CRLF line endings to LF before parsing, with ranges still
+    /// pointing into the original text. Applied where this crate parses the
+    /// text itself, like [`parse_range`](crate::parse_range).
+    pub normalize_newlines: bool,
+    /// Warn about indentation mixing tabs and spaces in raw and code blocks.
+    pub lint_indentation: bool,
+    /// Omit `text` on whitespace-only CST nodes to shrink the payload. This
+    /// makes the CST lossy: the source can no longer be rebuilt from it.
+    pub omit_whitespace_text: bool,
+    /// Report a panic during AST conversion as an error instead of aborting.
+    /// Only effective where unwinding is supported.
+    pub safe: bool,
+    /// Fail `parse` and `parseAst` with the syntax errors, if there are any,
+    /// instead of returning a result with the errors attached.
+    pub strict: bool,
+    /// Fold arithmetic over numeric literals of the same type, e.g. `1pt + 2pt`
+    /// becomes `3pt`, keeping the range of the whole expression.
+    pub fold_constants: bool,
+    /// Prune the CST to nodes of these kinds and the ancestors needed to reach
+    /// them. A pruned CST is no longer lossless.
+    pub cst_kinds: Option<Vec<String>>,
+    /// Add to the CST a node without a range for each required part the
+    /// parser found missing, like the transform of `#show:`, marked as a
+    /// placeholder. Such a CST no longer mirrors the syntax tree exactly.
+    pub include_placeholders: bool,
+    /// Add the length of its text in bytes to each CST node, placeholders
+    /// included, so it does not have to be derived from the range.
+    pub include_len: bool,
+    /// Return the CST from `parse` as a flat list of nodes in pre-order that
+    /// point to their parents, see `CstParseResult::into_flat`.
+    pub flat: bool,
+    /// Keep only the outermost AST nodes of these kinds, listed at the top
+    /// level in place of the nodes enclosing them. Kept nodes stay whole.
+    pub kinds: Option<Vec<String>>,
+    /// Extend the range of a `let` binding to start at the comments directly
+    /// above it, so it covers its documentation.
+    pub include_comments_in_ranges: bool,
+    /// Attach a `stableId` to every AST node that survives unrelated edits.
+    pub stable_ids: bool,
+    /// Parse the content of `typ`, `typst`, and `typc` raw blocks into a
+    /// nested AST, a few levels deep at most.
+    pub parse_nested_typst: bool,
+    /// Emit next to every `kind` of the AST its integer id as `kindId`, with
+    /// `kind_map` listing the kind for each id. Ids are stable across
+    /// releases.
+    pub numeric_kinds: bool,
+    /// Like `numeric_kinds`, but emit the id in place of `kind` instead.
+    pub kinds_only_numeric: bool,
+    /// Emit `int` values outside JavaScript's safe integer range as decimal
+    /// strings, so they survive the conversion exactly.
+    pub big_int_as_string: bool,
+    /// Emit non-finite floats, such as an overflowing `1e400`, as the strings
+    /// `"inf"`, `"-inf"`, and `"nan"` instead of `null`.
+    pub safe_floats: bool,
+    /// Leave out AST fields that are `null` or an empty array, such as the
+    /// range of a placeholder or the arguments of `f()`.
+    pub compact: bool,
+    /// Report the distance between each binary operator and its operands as
+    /// `lhs_gap`/`rhs_gap`, so formatters can tell `a+b` from `a + b`.
+    pub operator_gaps: bool,
+    /// Attach to every AST node the number of nodes enclosing it, as
+    /// `node_depth` (0 for top-level nodes).
+    pub node_depth: bool,
+    /// Attach to every AST node the text it covers, as `source`.
+    pub include_source: bool,
+    /// Attach to every string literal its spelling in the source, as `raw`.
+    pub string_raw: bool,
+    /// Attach to every int, float, and numeric literal its spelling in the
+    /// source, as `literal`.
+    pub keep_literal_text: bool,
+    /// Represent `link("url")[body]` calls as `Link` nodes with a body and
+    /// `heading[body]` calls as `Heading` nodes, instead of as function calls.
+    pub element_calls: bool,
+    /// Whether markup text keeps its source structure or is resolved into
+    /// reader-facing strings.
+    pub text_mode: TextMode,
+    /// The tree `parse` returns; the CST when omitted.
+    pub format: Option<OutputFormat>,
+    /// How deeply syntax nodes may nest before conversion leaves them out, to
+    /// avoid overflowing the stack; [`DEFAULT_MAX_DEPTH`] when omitted. This
+    /// cannot protect `typst_syntax::parse`, which recurses on its own. The CST is
+    /// built and dropped without recursion, but serializing a nested
+    /// [`CstNode`](crate::CstNode) recurses once per level.
+    pub max_depth: Option<usize>,
+}
+
+/// The nesting depth allowed when `max_depth` is omitted, far beyond what
+/// handwritten documents reach.
+pub const DEFAULT_MAX_DEPTH: usize = 512;
+
+impl ParseOptions {
+    pub fn max_depth(&self) -> usize {
+        self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH)
+    }
+
+    /// The options for parsing the bytes `start..end` of `text` on their own
+    /// with ranges that point into `text`, by moving `base_offset` past the
+    /// text before `start`. Fails unless both ends are character boundaries
+    /// of `text`.
+    pub fn for_range(&self, text: &str, start: usize, end: usize) -> Result<ParseOptions, String> {
+        if start > end || end > text.len() {
+            return Err(format!(
+                "range {start}..{end} is out of bounds for a text of {} bytes",
+                text.len()
+            ));
+        }
+        if let Some(offset) = [start, end]
+            .into_iter()
+            .find(|&i| !text.is_char_boundary(i))
+        {
+            return Err(format!("byte offset {offset} is not a character boundary"));
+        }
+        Ok(ParseOptions {
+            base_offset: self.base_offset + self.offset_encoding.text_len(&text[..start]),
+            ..self.clone()
+        })
+    }
+
+    /// The text to parse in place of `text`, with CRLF converted to LF if
+    /// `normalize_newlines` is set, and the byte offsets in it of the LFs
+    /// that lost their CR, for [`RangeMapper::with_removed_crs`].
+    pub(crate) fn normalize<'a>(&self, text: &'a str) -> (Cow<'a, str>, Vec<usize>) {
+        if !self.normalize_newlines {
+            return (Cow::Borrowed(text), vec![]);
+        }
+        normalize_newlines(text)
+    }
+}
+
+/// How markup `Text` nodes are emitted.
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TextMode {
+    /// Source text, with escapes, shorthands, and smart quotes as separate
+    /// nodes.
+    #[default]
+    Raw,
+    /// Runs of text, spaces, escapes, shorthands, and smart quotes merged
+    /// into one `Text` node with the characters they produce.
+    Resolved,
+}
+
+/// The tree `parse` returns.
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// The lossless concrete syntax tree, as before this option existed.
+    #[default]
+    Cst,
+    /// The same result as `parseAst`.
+    Ast,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_range_encoding_as_alias() {
+        let options: ParseOptions =
+            serde_json::from_str(r#"{ "rangeEncoding": "utf16" }"#).unwrap();
+        assert_eq!(options.offset_encoding, OffsetEncoding::Utf16);
+    }
+
+    #[test]
+    fn can_be_shared_across_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ParseOptions>();
+    }
+}
@@ -0,0 +1,163 @@
+use std::rc::Rc;
+
+use serde::Deserialize;
+use typst_syntax::SyntaxNode;
+
+use crate::cst::node_text;
+use crate::parse_options::ParseOptions;
+
+/// The unit in which emitted offsets are counted.
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OffsetEncoding {
+    /// UTF-8 bytes, as used by Rust strings.
+    #[default]
+    Utf8,
+    /// UTF-16 code units, as used by JavaScript strings.
+    Utf16,
+    /// Unicode scalar values (code points), as used by Python strings.
+    Utf32,
+}
+
+impl OffsetEncoding {
+    /// The length of `text` in this unit.
+    pub fn text_len(self, text: &str) -> usize {
+        text.chars().map(|c| self.len(c)).sum()
+    }
+
+    /// The byte offset in `text` at which `offset` units of this encoding
+    /// end, or `None` if that is inside a character or past the end.
+    pub fn byte_offset(self, text: &str, offset: usize) -> Option<usize> {
+        let mut units = 0;
+        for (i, c) in text.char_indices() {
+            if units >= offset {
+                return (units == offset).then_some(i);
+            }
+            units += self.len(c);
+        }
+        (units == offset).then_some(text.len())
+    }
+
+    fn len(self, c: char) -> usize {
+        match self {
+            OffsetEncoding::Utf8 => c.len_utf8(),
+            OffsetEncoding::Utf16 => c.len_utf16(),
+            OffsetEncoding::Utf32 => 1,
+        }
+    }
+}
+
+/// Translates byte ranges of the parsed text into the coordinates requested
+/// by the caller. Every emitted range goes through here.
+pub struct RangeMapper {
+    base_offset: usize,
+    /// Added to byte offsets first, for text parsed out of a larger text.
+    shift: usize,
+    /// Encoded offset for every byte offset of the text (plus the end), or
+    /// `None` when offsets stay in bytes.
+    table: Option<Rc<[usize]>>,
+    /// The byte offsets in the parsed text of the LFs that lost their CR to
+    /// `ParseOptions::normalize_newlines`. A CR is one unit in every
+    /// encoding, so each one before an offset adds one to it.
+    removed_crs: Option<Rc<[usize]>>,
+}
+
+impl RangeMapper {
+    pub fn new(root: &SyntaxNode, options: &ParseOptions) -> Self {
+        let table = (options.offset_encoding != OffsetEncoding::Utf8)
+            .then(|| offset_table(&node_text(root), options.offset_encoding).into());
+        RangeMapper {
+            base_offset: options.base_offset,
+            shift: 0,
+            table,
+            removed_crs: None,
+        }
+    }
+
+    /// This mapper, counting a CR before each LF at the byte offsets in
+    /// `removed_crs`, as returned by `ParseOptions::normalize`.
+    pub fn with_removed_crs(self, removed_crs: Vec<usize>) -> Self {
+        RangeMapper {
+            removed_crs: (!removed_crs.is_empty()).then(|| removed_crs.into()),
+            ..self
+        }
+    }
+
+    pub fn removed_crs(&self) -> Option<&[usize]> {
+        self.removed_crs.as_deref()
+    }
+
+    /// A mapper for a substring starting at byte `offset` of this mapper's
+    /// text, so that ranges within the substring map into the full text.
+    pub fn nested(&self, offset: usize) -> Self {
+        RangeMapper {
+            base_offset: self.base_offset,
+            shift: self.shift + offset,
+            table: self.table.clone(),
+            removed_crs: self.removed_crs.clone(),
+        }
+    }
+
+    pub fn map(&self, [start, end]: [usize; 2]) -> [usize; 2] {
+        let encode = |offset: usize| {
+            let encoded = match &self.table {
+                Some(table) => table[offset.min(table.len() - 1)],
+                None => offset,
+            };
+            // An offset at an LF that lost its CR maps to the CR, keeping it
+            // in the range of the line break.
+            let crs = self
+                .removed_crs
+                .as_ref()
+                .map_or(0, |removed| removed.partition_point(|&at| at < offset));
+            encoded + crs
+        };
+        [
+            self.base_offset + encode(self.shift + start),
+            self.base_offset + encode(self.shift + end),
+        ]
+    }
+}
+
+/// Computes the remapped offset of every byte in one pass over the text.
+fn offset_table(text: &str, encoding: OffsetEncoding) -> Vec<usize> {
+    let mut table = Vec::with_capacity(text.len() + 1);
+    let mut offset = 0;
+    for c in text.chars() {
+        table.extend(std::iter::repeat_n(offset, c.len_utf8()));
+        offset += encoding.len(c);
+    }
+    table.push(offset);
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remaps_astral_plane_offsets_per_encoding() {
+        let text = "a😀é b";
+        let root = typst_syntax::parse(text);
+        let b = text.find('b').unwrap();
+        let map = |offset_encoding| {
+            let options = ParseOptions {
+                offset_encoding,
+                ..Default::default()
+            };
+            RangeMapper::new(&root, &options).map([b, b + 1])
+        };
+
+        assert_eq!(map(OffsetEncoding::Utf8), [8, 9]);
+        assert_eq!(map(OffsetEncoding::Utf16), [5, 6]);
+        assert_eq!(map(OffsetEncoding::Utf32), [4, 5]);
+        assert_eq!(
+            text.chars().position(|c| c == 'b'),
+            Some(map(OffsetEncoding::Utf32)[0])
+        );
+        assert_eq!(
+            text[..b].encode_utf16().count(),
+            map(OffsetEncoding::Utf16)[0]
+        );
+    }
+}
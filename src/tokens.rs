@@ -0,0 +1,187 @@
+use serde::Serialize;
+use typst_syntax::{LinkedNode, SyntaxNode, Tag};
+
+use crate::parse_options::ParseOptions;
+use crate::range_mapper::RangeMapper;
+
+/// A leaf of the CST.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Token {
+    pub kind: String,
+    pub range: [usize; 2],
+    pub text: String,
+}
+
+/// Parses `text` and returns its CST leaves in source order, without building
+/// the tree. Their texts add up to `text`.
+///
+/// ```
+/// use typst_ast::{ParseOptions, parse_tokens};
+///
+/// let kinds: Vec<_> = parse_tokens("*hi*", &ParseOptions::default())
+///     .into_iter()
+///     .map(|token| token.kind)
+///     .collect();
+/// assert_eq!(kinds, ["Star", "Text", "Star"]);
+/// ```
+pub fn parse_tokens(text: &str, options: &ParseOptions) -> Vec<Token> {
+    let root = options.mode.unwrap_or_default().parse(text);
+    let mapper = RangeMapper::new(&root, options);
+    let mut tokens = vec![];
+    for_each_leaf(&root, &mut |leaf| {
+        tokens.push(Token {
+            kind: format!("{:?}", leaf.kind()),
+            range: mapper.map([leaf.offset(), leaf.offset() + leaf.len()]),
+            text: leaf.text().to_string(),
+        });
+    });
+    tokens
+}
+
+/// A leaf of the CST with Typst's own highlighting category.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct HighlightedToken {
+    pub range: [usize; 2],
+    pub tag: &'static str,
+}
+
+/// Parses `text` and classifies its CST leaves with Typst's highlighter, in
+/// source order. A leaf without a category of its own takes the one of its
+/// closest categorized ancestor, so the text of `*strong*` is `strong` too.
+/// Leaves without any, like plain markup text, are left out.
+///
+/// ```
+/// use typst_ast::{ParseOptions, highlight};
+///
+/// let tags: Vec<_> = highlight("#f(\"a\")", &ParseOptions::default())
+///     .into_iter()
+///     .map(|token| token.tag)
+///     .collect();
+/// assert_eq!(tags, ["function", "function", "punctuation", "string", "punctuation"]);
+/// ```
+pub fn highlight(text: &str, options: &ParseOptions) -> Vec<HighlightedToken> {
+    let root = options.mode.unwrap_or_default().parse(text);
+    let mapper = RangeMapper::new(&root, options);
+    let mut tokens = vec![];
+    let mut stack = vec![(LinkedNode::new(&root), None)];
+    while let Some((node, inherited)) = stack.pop() {
+        let tag = typst_syntax::highlight(&node).or(inherited);
+        if node.children().len() > 0 {
+            stack.extend(node.children().rev().map(|child| (child, tag)));
+        } else if let Some(tag) = tag {
+            tokens.push(HighlightedToken {
+                range: mapper.map([node.offset(), node.offset() + node.len()]),
+                tag: tag_name(tag),
+            });
+        }
+    }
+    tokens
+}
+
+fn tag_name(tag: Tag) -> &'static str {
+    match tag {
+        Tag::Comment => "comment",
+        Tag::Punctuation => "punctuation",
+        Tag::Escape => "escape",
+        Tag::Strong => "strong",
+        Tag::Emph => "emph",
+        Tag::Link => "link",
+        Tag::Raw => "raw",
+        Tag::Label => "label",
+        Tag::Ref => "ref",
+        Tag::Heading => "heading",
+        Tag::ListMarker => "listMarker",
+        Tag::ListTerm => "listTerm",
+        Tag::MathDelimiter => "mathDelimiter",
+        Tag::MathOperator => "mathOperator",
+        Tag::Keyword => "keyword",
+        Tag::Operator => "operator",
+        Tag::Number => "number",
+        Tag::String => "string",
+        Tag::Function => "function",
+        Tag::Interpolated => "interpolated",
+        Tag::Error => "error",
+    }
+}
+
+fn for_each_leaf(root: &SyntaxNode, f: &mut dyn FnMut(&LinkedNode)) {
+    // An explicit stack, as erroneous trees can be arbitrarily deep.
+    let mut stack = vec![LinkedNode::new(root)];
+    while let Some(node) = stack.pop() {
+        if node.children().len() == 0 {
+            f(&node);
+        }
+        stack.extend(node.children().rev());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_mode::ParseMode;
+
+    fn tokens(text: &str, mode: ParseMode) -> Vec<(String, [usize; 2], String)> {
+        let options = ParseOptions {
+            mode: Some(mode),
+            ..Default::default()
+        };
+        parse_tokens(text, &options)
+            .into_iter()
+            .map(|token| (token.kind, token.range, token.text))
+            .collect()
+    }
+
+    #[test]
+    fn lists_leaves_in_order() {
+        let expected = [
+            ("Hash", [0, 1], "#"),
+            ("Let", [1, 4], "let"),
+            ("Space", [4, 5], " "),
+            ("Ident", [5, 6], "x"),
+            ("Space", [6, 7], " "),
+            ("Eq", [7, 8], "="),
+            ("Space", [8, 9], " "),
+            ("Int", [9, 10], "1"),
+        ];
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(kind, range, text)| (kind.to_string(), range, text.to_string()))
+            .collect();
+        assert_eq!(tokens("#let x = 1", ParseMode::Markup), expected);
+    }
+
+    #[test]
+    fn respects_the_mode() {
+        let kinds = |mode| -> Vec<String> {
+            tokens("a+b", mode)
+                .into_iter()
+                .map(|(kind, ..)| kind)
+                .collect()
+        };
+        assert_eq!(kinds(ParseMode::Markup), ["Text"]);
+        assert_eq!(kinds(ParseMode::Code), ["Ident", "Plus", "Ident"]);
+        assert_eq!(kinds(ParseMode::Math), ["MathText", "MathText", "MathText"]);
+    }
+
+    #[test]
+    fn tags_leaves_by_category() {
+        let text = "#text(\"hi\") *bold* plain";
+        let tags: Vec<_> = highlight(text, &ParseOptions::default())
+            .into_iter()
+            .map(|token| (&text[token.range[0]..token.range[1]], token.tag))
+            .collect();
+        assert_eq!(
+            tags,
+            [
+                ("#", "function"),
+                ("text", "function"),
+                ("(", "punctuation"),
+                ("\"hi\"", "string"),
+                (")", "punctuation"),
+                ("*", "strong"),
+                ("bold", "strong"),
+                ("*", "strong"),
+            ]
+        );
+    }
+}
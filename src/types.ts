@@ -1,95 +1,408 @@
 export interface SyntaxNode {
   kind: string;
-  range: [number, number];
+  /** `null` only for placeholders. */
+  range: [number, number] | null;
+  /** The length of the node's text in UTF-8 bytes, with `includeLen`. */
+  len?: number;
   text?: string;
   children: SyntaxNode[];
+  /** `true` for a missing part of the parent, with `includePlaceholders`. */
+  placeholder?: true;
+}
+
+/** A CST node in the flat list returned with `flat`. */
+export interface FlatSyntaxNode {
+  /** The index of the node in `nodes`. */
+  id: number;
+  /** The index of the enclosing node, or -1 for the root. */
+  parent: number;
+  kind: string;
+  range: [number, number] | null;
+  len?: number;
+  text?: string;
+  placeholder?: true;
 }
 
 export interface ParseError {
   message: string;
   range: [number, number];
+  /** Suggestions the parser attached to the error. */
+  hints: string[];
+  /** Always `"error"` for now; syntax warnings may follow. */
+  severity: "error" | "warning";
+}
+
+/** `ParseResult` with the CST as a flat list of nodes in pre-order. */
+export interface FlatParseResult extends Omit<ParseResult, "root"> {
+  nodes: FlatSyntaxNode[];
 }
 
 export interface ParseResult {
   root: SyntaxNode;
+  /** Whether the text parsed without errors, so that `errors` is empty. */
+  ok: boolean;
   errors: ParseError[];
-}
-
-export type ParseMode = "markup" | "code" | "math";
+  /**
+   * The line ending of the text; `"lf"` also without any line breaks, and
+   * `"mixed"` for more than one kind.
+   */
+  lineEnding: "lf" | "crlf" | "mixed";
+  /** The mode picked for the text, only with `mode: "auto"`. */
+  detectedMode?: Exclude<ParseMode, "auto">;
+}
+
+/**
+ * `"auto"` picks math for a single `$…$` equation spanning the whole text,
+ * code for a single `#` expression followed only by whitespace, and markup
+ * otherwise.
+ */
+export type ParseMode = "markup" | "code" | "math" | "auto";
 
 export interface ParseOptions {
   mode?: ParseMode;
+  /**
+   * Trim leading/trailing whitespace from AST `text` nodes. Non-breaking
+   * spaces (U+00A0, U+2007, U+202F) are kept.
+   */
+  trimText?: boolean;
+  /** Added to every emitted range (including error ranges). */
+  baseOffset?: number;
+  /**
+   * Unit of every emitted offset: UTF-8 bytes, UTF-16 code units (JavaScript
+   * string indices), or code points. `baseOffset` uses the same unit.
+   */
+  offsetEncoding?: "utf8" | "utf16" | "utf32";
+  /** Alias of `offsetEncoding`. */
+  rangeEncoding?: "utf8" | "utf16" | "utf32";
+  /**
+   * Convert CRLF line endings to LF before parsing, with ranges still
+   * pointing into the original text. Applies to `parse`, `parseRange`, and
+   * `parseAst`.
+   */
+  normalizeNewlines?: boolean;
+  /** Warn about indentation mixing tabs and spaces in raw and code blocks. */
+  lintIndentation?: boolean;
+  /** Omit `text` on whitespace-only CST nodes. Makes the CST lossy. */
+  omitWhitespaceText?: boolean;
+  /**
+   * Report a panic during AST conversion as an error instead of aborting.
+   * Only effective where unwinding is supported.
+   */
+  safe?: boolean;
+  /**
+   * Make `parse`, `parseRange`, and `parseAst` throw the array of syntax
+   * errors, if there are any, instead of returning them with the result.
+   */
+  strict?: boolean;
+  /**
+   * Fold arithmetic over numeric literals of the same type, e.g. `1pt + 2pt`
+   * becomes `3pt`, keeping the range of the whole expression.
+   */
+  foldConstants?: boolean;
+  /**
+   * Prune the CST to nodes of these kinds and the ancestors needed to reach
+   * them. A pruned CST is no longer lossless.
+   */
+  cstKinds?: string[];
+  /**
+   * Add a CST node with no range and `placeholder: true` for each required
+   * part the parser found missing, such as the transform of `#show:`.
+   */
+  includePlaceholders?: boolean;
+  /**
+   * Add `len`, the length of its text in UTF-8 bytes, to each CST node,
+   * placeholders included.
+   */
+  includeLen?: boolean;
+  /**
+   * Return the CST from `parse` and `parseRange` as `nodes`, a flat list in
+   * pre-order where each node points to its parent by index, instead of as
+   * a nested `root`.
+   */
+  flat?: boolean;
+  /**
+   * Keep only the outermost AST nodes of these kinds (e.g. `["funcCall"]`),
+   * listed in `root` in place of the nodes enclosing them. Kept nodes stay
+   * whole, including nodes of other kinds and nested matches inside them.
+   */
+  kinds?: string[];
+  /**
+   * Extend the range of a `let` binding to start at the comments directly
+   * above it, so it covers its documentation.
+   */
+  includeCommentsInRanges?: boolean;
+  /**
+   * Attach a `stableId` to every AST node, derived from its content and
+   * ancestry rather than its offset, so it survives unrelated edits. Ids
+   * are the same across builds and platforms.
+   */
+  stableIds?: boolean;
+  /**
+   * Parse the content of `typ`, `typst`, and `typc` raw blocks into a nested
+   * AST (in `nested`), a few levels deep at most.
+   */
+  parseNestedTypst?: boolean;
+  /**
+   * Add `kindId`, the integer id of the node's `kind`, to every AST object
+   * with a `kind`, with `kindMap` giving the name for each id. Ids are
+   * stable across releases.
+   */
+  numericKinds?: boolean;
+  /**
+   * Like `numericKinds`, but emit the id in place of the `kind` string; the
+   * `kind` types below then describe the names, not the ids.
+   */
+  kindsOnlyNumeric?: boolean;
+  /**
+   * Emit `int` values outside the safe integer range as decimal strings
+   * instead of failing, so they survive exactly.
+   */
+  bigIntAsString?: boolean;
+  /**
+   * Emit non-finite floats, such as an overflowing `1e400`, as `"inf"`,
+   * `"-inf"`, or `"nan"` instead of `Infinity` or `NaN`.
+   */
+  safeFloats?: boolean;
+  /**
+   * Leave out AST fields that are `null` or an empty array, such as the
+   * `range` of a placeholder, so they read as `undefined`. Fields outside of
+   * AST nodes, like `errors` and `warnings`, are always present.
+   */
+  compact?: boolean;
+  /**
+   * Report the distance between each binary operator and its operands as
+   * `lhsGap` and `rhsGap`, e.g. to tell `a+b` from `a + b`.
+   */
+  operatorGaps?: boolean;
+  /** Attach `nodeDepth` (0 for top-level nodes) to every AST node. */
+  nodeDepth?: boolean;
+  /** Attach `source`, the exact text it covers, to every AST node. */
+  includeSource?: boolean;
+  /** Attach `raw`, the literal as written, to every `str` node. */
+  stringRaw?: boolean;
+  /** Attach `literal`, the spelling in the source, to every number literal. */
+  keepLiteralText?: boolean;
+  /**
+   * Emit `#link("url")[body]` calls as `link` nodes with a `body`, and
+   * `#heading[body]` calls as `heading` nodes, instead of as `funcCall` nodes.
+   */
+  elementCalls?: boolean;
+  /**
+   * `"raw"` keeps markup text as in the source, with escapes, shorthands, and
+   * smart quotes as separate nodes. `"resolved"` merges such runs into one
+   * `text` node holding the characters they produce. Default: `"raw"`.
+   */
+  textMode?: "raw" | "resolved";
+  /**
+   * The tree `parse` returns: the CST (`"cst"`, the default) or the same
+   * result as `parseAst` (`"ast"`). Ignored by the other functions.
+   */
+  format?: "cst" | "ast";
+  /**
+   * How deeply syntax nodes may nest (default: 512). Beyond it, the CST
+   * leaves out deeper nodes, the AST has `truncated` nodes in their place,
+   * and both report an error, instead of overflowing the stack. Building the
+   * CST needs no recursion, but returning its nested `root` recurses once
+   * per level, so pair a much larger limit with `flat`. The parser itself
+   * recurses, so a deep enough text can still overflow while parsing.
+   */
+  maxDepth?: number;
 }
 
 export declare function parse(
   text: string,
-  options?: ParseOptions,
+  options: ParseOptions & { format?: "cst"; flat: true },
+): FlatParseResult;
+export declare function parse(
+  text: string,
+  options?: ParseOptions & { format?: "cst" },
 ): ParseResult;
+export declare function parse(
+  text: string,
+  options: ParseOptions & { format: "ast" },
+): ParseAstResult;
+
+/**
+ * Parses the UTF-8 bytes `start..end` of `text` like `parse` parses a text of
+ * their own, with ranges pointing into `text`. Throws unless both ends are
+ * character boundaries of `text`.
+ */
+export declare function parseRange(
+  text: string,
+  start: number,
+  end: number,
+  options: ParseOptions & { format?: "cst"; flat: true },
+): FlatParseResult;
+export declare function parseRange(
+  text: string,
+  start: number,
+  end: number,
+  options?: ParseOptions & { format?: "cst" },
+): ParseResult;
+export declare function parseRange(
+  text: string,
+  start: number,
+  end: number,
+  options: ParseOptions & { format: "ast" },
+): ParseAstResult;
+
+/**
+ * The CST on one line as nested `(Kind start..end child…)` lists, followed by
+ * one `(error start..end "message")` line per syntax error.
+ */
+export declare function parseSexpr(
+  text: string,
+  options?: ParseOptions,
+): string;
+
+/**
+ * The CST as an indented tree, one `Kind [start..end] "text"` line per node.
+ * Leaf text longer than 40 characters is truncated. Ignores `cstKinds`.
+ */
+export declare function parsePretty(
+  text: string,
+  options?: ParseOptions,
+): string;
+
+/**
+ * The CST as XML inside a `<cst>` root: one `<Kind start end>` element per
+ * node, then one `<error start end message>` element per syntax error.
+ */
+export declare function parseXml(
+  text: string,
+  options?: ParseOptions,
+): string;
+
+export interface Token {
+  kind: string;
+  range: [number, number];
+  text: string;
+}
+
+/** The leaves of the CST in source order, without building the tree. */
+export declare function parseTokens(
+  text: string,
+  options?: ParseOptions,
+): Token[];
+
+export type HighlightTag =
+  | "comment"
+  | "punctuation"
+  | "escape"
+  | "strong"
+  | "emph"
+  | "link"
+  | "raw"
+  | "label"
+  | "ref"
+  | "heading"
+  | "listMarker"
+  | "listTerm"
+  | "mathDelimiter"
+  | "mathOperator"
+  | "keyword"
+  | "operator"
+  | "number"
+  | "string"
+  | "function"
+  | "interpolated"
+  | "error";
+
+export interface HighlightedToken {
+  range: [number, number];
+  tag: HighlightTag;
+}
+
+/**
+ * The CST leaves Typst's highlighter classifies, in source order. A leaf
+ * takes the tag of its closest tagged ancestor if it has none itself.
+ */
+export declare function highlight(
+  text: string,
+  options?: ParseOptions,
+): HighlightedToken[];
 
 // --- AST types ---
 
 export type Range = [number, number] | null;
 
-export type AstExpr =
-  | AstText
-  | AstSpace
-  | AstLinebreak
-  | AstParbreak
-  | AstEscape
-  | AstShorthand
-  | AstSmartQuote
-  | AstStrong
-  | AstEmph
-  | AstRaw
-  | AstLink
-  | AstLabel
-  | AstRef
-  | AstHeading
-  | AstListItem
-  | AstEnumItem
-  | AstTermItem
-  | AstEquation
-  | AstMath
-  | AstMathText
-  | AstMathIdent
-  | AstMathShorthand
-  | AstMathAlignPoint
-  | AstMathDelimited
-  | AstMathAttach
-  | AstMathPrimes
-  | AstMathFrac
-  | AstMathRoot
-  | AstIdent
-  | AstNone
-  | AstAuto
-  | AstBool
-  | AstInt
-  | AstFloat
-  | AstNumeric
-  | AstStr
-  | AstCodeBlock
-  | AstContentBlock
-  | AstParenthesized
-  | AstArray
-  | AstDict
-  | AstUnary
-  | AstBinary
-  | AstFieldAccess
-  | AstFuncCall
-  | AstClosure
-  | AstLetBinding
-  | AstDestructAssignment
-  | AstSetRule
-  | AstShowRule
-  | AstContextual
-  | AstConditional
-  | AstWhileLoop
-  | AstForLoop
-  | AstModuleImport
-  | AstModuleInclude
-  | AstLoopBreak
-  | AstLoopContinue
-  | AstFuncReturn;
+export type AstExpr = AstNodeMeta &
+  (
+    | AstText
+    | AstSpace
+    | AstLinebreak
+    | AstParbreak
+    | AstEscape
+    | AstShorthand
+    | AstSmartQuote
+    | AstStrong
+    | AstEmph
+    | AstRaw
+    | AstLink
+    | AstLabel
+    | AstRef
+    | AstHeading
+    | AstListItem
+    | AstEnumItem
+    | AstTermItem
+    | AstEquation
+    | AstMath
+    | AstMathText
+    | AstMathIdent
+    | AstMathShorthand
+    | AstMathAlignPoint
+    | AstMathDelimited
+    | AstMathAttach
+    | AstMathPrimes
+    | AstMathFrac
+    | AstMathRoot
+    | AstIdent
+    | AstNone
+    | AstAuto
+    | AstBool
+    | AstInt
+    | AstFloat
+    | AstNumeric
+    | AstStr
+    | AstCodeBlock
+    | AstContentBlock
+    | AstParenthesized
+    | AstArray
+    | AstDict
+    | AstUnary
+    | AstBinary
+    | AstFieldAccess
+    | AstFuncCall
+    | AstClosure
+    | AstLetBinding
+    | AstDestructAssignment
+    | AstSetRule
+    | AstShowRule
+    | AstContextual
+    | AstConditional
+    | AstWhileLoop
+    | AstForLoop
+    | AstModuleImport
+    | AstModuleInclude
+    | AstLoopBreak
+    | AstLoopContinue
+    | AstFuncReturn
+    | AstTruncated
+  );
+
+/** Optional data that any node may carry. */
+export interface AstNodeMeta {
+  /** Present with `stableIds`. */
+  stableId?: string;
+  /** `true` if the node is embedded in markup or math behind a `#`. */
+  hashPrefixed?: true;
+  /** Number of enclosing nodes (0 at the top level), present with `nodeDepth`. */
+  nodeDepth?: number;
+  /** The text the node covers, present with `includeSource`. */
+  source?: string;
+  /** The id of `kind` in `kindTable()`, present with `numericKinds`. */
+  kindId?: number;
+}
 
 // Markup
 
@@ -112,6 +425,8 @@ export interface AstLinebreak {
 export interface AstParbreak {
   kind: "parbreak";
   range: Range;
+  /** The number of empty lines, e.g. 1 for `\n\n`. */
+  blankLines: number;
 }
 
 export interface AstEscape {
@@ -150,12 +465,23 @@ export interface AstRaw {
   lines: string[];
   lang: string | null;
   block: boolean;
+  /** Whether it is fenced by three or more backticks, allowing a `lang`. */
+  fenced: boolean;
+  /** The number of backticks on either side. */
+  backticks: number;
+  /** The parsed content of a `typ`/`typst`/`typc` block. */
+  nested: ParseAstResult | null;
 }
 
 export interface AstLink {
   kind: "link";
   range: Range;
   url: string;
+  /**
+   * The display content of a `#link("url")[body]` call, only converted to a
+   * link with `elementCalls`; `null` for a bare URL.
+   */
+  body: AstExpr[] | null;
 }
 
 export interface AstLabel {
@@ -175,9 +501,22 @@ export interface AstHeading {
   kind: "heading";
   range: Range;
   depth: number;
+  /**
+   * Best-effort level after the nearest preceding top-level
+   * `#set heading(offset: ..)`, or `null` when the offset isn't a literal.
+   * An explicit `level` argument is absolute and taken as is.
+   */
+  effectiveDepth: number | null;
+  /**
+   * Whether the heading was written as `= Title` or `#heading[Title]`; the
+   * latter only with `elementCalls`.
+   */
+  syntax: AstHeadingSyntax;
   body: AstExpr[];
 }
 
+export type AstHeadingSyntax = "markup" | "func";
+
 export interface AstListItem {
   kind: "listItem";
   range: Range;
@@ -196,6 +535,10 @@ export interface AstTermItem {
   range: Range;
   term: AstExpr[];
   description: AstExpr[];
+  /** `term` flattened to plain text. */
+  termText: string;
+  /** `description` flattened to plain text. */
+  descriptionText: string;
 }
 
 export interface AstEquation {
@@ -203,6 +546,8 @@ export interface AstEquation {
   range: Range;
   body: AstExpr[];
   block: boolean;
+  /** The label attached right after the equation, if any. */
+  label: string | null;
 }
 
 // Math
@@ -227,8 +572,12 @@ export interface AstMathIdent {
   kind: "mathIdent";
   range: Range;
   name: string;
+  /** Best-effort guess from a bundled table of math functions and symbols. */
+  role: AstMathIdentRole;
 }
 
+export type AstMathIdentRole = "function" | "symbol" | "variable";
+
 export interface AstMathShorthand {
   kind: "mathShorthand";
   range: Range;
@@ -243,11 +592,16 @@ export interface AstMathAlignPoint {
 export interface AstMathDelimited {
   kind: "mathDelimited";
   range: Range;
+  delimiter: AstMathDelimiter;
+  /** `true` for the body of an explicit `lr(..)` call, as in `lr((x))`. */
+  sized: boolean;
   open: AstExpr;
   body: AstExpr[];
   close: AstExpr;
 }
 
+export type AstMathDelimiter = "paren" | "bracket" | "brace" | "bar" | "custom";
+
 export interface AstMathAttach {
   kind: "mathAttach";
   range: Range;
@@ -304,13 +658,19 @@ export interface AstBool {
 export interface AstInt {
   kind: "int";
   range: Range;
-  value: number;
+  /** A string only for unsafe integers with `bigIntAsString`. */
+  value: number | string;
+  /** The literal as written, present with `keepLiteralText`. */
+  literal?: string;
 }
 
 export interface AstFloat {
   kind: "float";
   range: Range;
-  value: number;
+  /** A string instead of a non-finite number with `safeFloats`. */
+  value: number | "inf" | "-inf" | "nan";
+  /** The literal as written, present with `keepLiteralText`. */
+  literal?: string;
 }
 
 export type AstUnit =
@@ -327,14 +687,20 @@ export type AstUnit =
 export interface AstNumeric {
   kind: "numeric";
   range: Range;
-  value: number;
+  /** A string instead of a non-finite number with `safeFloats`. */
+  value: number | "inf" | "-inf" | "nan";
   unit: AstUnit;
+  /** The literal as written, present with `keepLiteralText`. */
+  literal?: string;
 }
 
 export interface AstStr {
   kind: "str";
   range: Range;
+  /** The value with escapes resolved. */
   value: string;
+  /** The literal as written, quotes included, present with `stringRaw`. */
+  raw?: string;
 }
 
 // Code structures
@@ -359,23 +725,37 @@ export interface AstParenthesized {
 
 export type AstArrayItem =
   | { kind: "pos"; expr: AstExpr }
-  | { kind: "spread"; expr: AstExpr; sinkIdent: string | null };
+  | {
+      kind: "spread";
+      /** The `..` token. */
+      spreadRange: Range;
+      expr: AstExpr;
+      sinkIdent: string | null;
+    };
 
 export interface AstArray {
   kind: "array";
   range: Range;
   items: AstArrayItem[];
+  trailingComma: boolean;
 }
 
 export type AstDictItem =
   | { kind: "named"; name: string; expr: AstExpr }
   | { kind: "keyed"; key: AstExpr; expr: AstExpr }
-  | { kind: "spread"; expr: AstExpr; sinkIdent: string | null };
+  | {
+      kind: "spread";
+      /** The `..` token. */
+      spreadRange: Range;
+      expr: AstExpr;
+      sinkIdent: string | null;
+    };
 
 export interface AstDict {
   kind: "dict";
   range: Range;
   items: AstDictItem[];
+  trailingComma: boolean;
 }
 
 // Operations
@@ -416,6 +796,10 @@ export interface AstBinary {
   op: AstBinOp;
   lhs: AstExpr;
   rhs: AstExpr;
+  /** Distance from `lhs` to the operator, only with `operatorGaps`. */
+  lhsGap?: number;
+  /** Distance from the operator to `rhs`, only with `operatorGaps`. */
+  rhsGap?: number;
 }
 
 export interface AstFieldAccess {
@@ -428,7 +812,13 @@ export interface AstFieldAccess {
 export type AstArg =
   | { kind: "pos"; expr: AstExpr }
   | { kind: "named"; name: string; expr: AstExpr }
-  | { kind: "spread"; expr: AstExpr; sinkIdent: string | null };
+  | {
+      kind: "spread";
+      /** The `..` token. */
+      spreadRange: Range;
+      expr: AstExpr;
+      sinkIdent: string | null;
+    };
 
 export interface AstFuncCall {
   kind: "funcCall";
@@ -469,8 +859,11 @@ export type AstPattern =
 
 // Bindings
 
+/** The coarse shape of a pattern; a `normal` pattern is reported as `ident`. */
+export type AstPatternKind = "ident" | "destructuring" | "placeholder" | "paren";
+
 export type AstLetBindingKind =
-  | { kind: "normal"; pattern: AstPattern }
+  | { kind: "normal"; pattern: AstPattern; patternKind: AstPatternKind }
   | { kind: "closure"; name: string };
 
 export interface AstLetBinding {
@@ -484,6 +877,7 @@ export interface AstDestructAssignment {
   kind: "destructAssignment";
   range: Range;
   pattern: AstPattern;
+  patternKind: AstPatternKind;
   value: AstExpr;
 }
 
@@ -495,6 +889,8 @@ export interface AstSetRule {
   target: AstExpr;
   args: AstArg[];
   condition: AstExpr | null;
+  isKnownElement: boolean | null;
+  scope: AstRuleScope;
 }
 
 export interface AstShowRule {
@@ -502,8 +898,16 @@ export interface AstShowRule {
   range: Range;
   selector: AstExpr | null;
   transform: AstExpr;
+  isKnownElement: boolean | null;
+  scope: AstRuleScope;
 }
 
+/**
+ * `local` if the rule sits in a content or code block and only affects the
+ * rest of it, `block` if it applies to the rest of the document.
+ */
+export type AstRuleScope = "local" | "block";
+
 export interface AstContextual {
   kind: "contextual";
   range: Range;
@@ -512,12 +916,18 @@ export interface AstContextual {
 
 // Control flow
 
+export type AstBodyKind = "content" | "code" | "expr";
+
 export interface AstConditional {
   kind: "conditional";
   range: Range;
   condition: AstExpr;
   ifBody: AstExpr;
   elseBody: AstExpr | null;
+  ifKeywordRange: Range;
+  elseKeywordRange: Range;
+  ifBodyKind: AstBodyKind;
+  elseBodyKind: AstBodyKind | null;
 }
 
 export interface AstWhileLoop {
@@ -525,14 +935,17 @@ export interface AstWhileLoop {
   range: Range;
   condition: AstExpr;
   body: AstExpr;
+  bodyKind: AstBodyKind;
 }
 
 export interface AstForLoop {
   kind: "forLoop";
   range: Range;
   pattern: AstPattern;
+  patternKind: AstPatternKind;
   iterable: AstExpr;
   body: AstExpr;
+  bodyKind: AstBodyKind;
 }
 
 // Module
@@ -543,7 +956,9 @@ export type AstImportItem =
       kind: "renamed";
       path: string[];
       originalName: string;
+      originalNameRange: Range;
       newName: string;
+      newNameRange: Range;
     };
 
 export type AstImports =
@@ -555,6 +970,7 @@ export interface AstModuleImport {
   range: Range;
   source: AstExpr;
   newName: string | null;
+  newNameRange: Range;
   imports: AstImports | null;
 }
 
@@ -579,17 +995,273 @@ export interface AstLoopContinue {
 export interface AstFuncReturn {
   kind: "funcReturn";
   range: Range;
+  keywordRange: Range;
   body: AstExpr | null;
 }
 
+/**
+ * An expression nested deeper than `maxDepth`, left out like the CST leaves
+ * out its nodes. `syntaxKind` is the kind of its syntax node.
+ */
+export interface AstTruncated {
+  kind: "truncated";
+  range: Range;
+  syntaxKind: string;
+}
+
 // Parse AST result
 
+export interface ParseWarning {
+  message: string;
+  range: [number, number];
+}
+
 export interface ParseAstResult {
   root: AstExpr[];
+  rootCount: number;
+  /** Whether the text parsed without errors, so that `errors` is empty. */
+  ok: boolean;
   errors: ParseError[];
+  warnings: ParseWarning[];
+  /** The kind name for each id, only with `numericKinds` or `kindsOnlyNumeric`. */
+  kindMap?: string[];
+  /**
+   * The line ending of the text; `"lf"` also without any line breaks, and
+   * `"mixed"` for more than one kind.
+   */
+  lineEnding: "lf" | "crlf" | "mixed";
+  /** The mode picked for the text, only with `mode: "auto"`. */
+  detectedMode?: Exclude<ParseMode, "auto">;
 }
 
 export declare function parseAst(
   text: string,
   options?: ParseOptions,
 ): ParseAstResult;
+
+export interface Recovery {
+  /** For a placeholder, the range of the nearest enclosing node with one. */
+  range: Range;
+  description: string;
+}
+
+export interface ParseRecoveryReport extends ParseAstResult {
+  recoveries: Recovery[];
+}
+
+/**
+ * Like `parseAst`, but also lists where the parser skipped input or assumed
+ * missing syntax, and which AST nodes are placeholders for missing parts.
+ */
+export declare function parseWithRecoveryReport(
+  text: string,
+  options?: ParseOptions,
+): ParseRecoveryReport;
+
+export interface NodeAtOffset {
+  node: AstExpr;
+  /** The kinds of the nodes enclosing `node`, outermost first. */
+  ancestors: AstExpr["kind"][];
+}
+
+/**
+ * The innermost AST node whose range contains `offset`, given in the same
+ * units as the ranges `options` produce, or `null` if there is none.
+ */
+export declare function nodeAtOffset(
+  text: string,
+  offset: number,
+  options?: ParseOptions,
+): NodeAtOffset | null;
+
+/**
+ * The nodes from the top level down to the one `nodeAtOffset` finds, e.g.
+ * for breadcrumbs. Empty if there is none.
+ */
+export declare function ancestorsAtOffset(
+  text: string,
+  offset: number,
+  options?: ParseOptions,
+): { kind: AstExpr["kind"]; range: [number, number] }[];
+
+export interface OutlineEntry {
+  level: number;
+  /** The plain text of the heading, without formatting. */
+  title: string;
+  range: [number, number];
+  children: OutlineEntry[];
+}
+
+export interface WordCount {
+  /** Runs of non-whitespace text holding a letter or digit. */
+  words: number;
+  /** Characters of the words, not counting whitespace. */
+  characters: number;
+  headings: number;
+  /** Calls of `figure`. */
+  figures: number;
+  equations: number;
+}
+
+/**
+ * Counts the words of the prose of a markup document, leaving out code, raw
+ * text, and math, along with its headings, figures, and equations. `mode` is
+ * ignored.
+ */
+export declare function wordCount(
+  text: string,
+  options?: ParseOptions,
+): WordCount;
+
+/**
+ * The readable text of the markup for search indexing, separated by single
+ * spaces. Code, math, and raw text are left out.
+ */
+export declare function toPlaintext(
+  text: string,
+  options?: ParseOptions,
+): string;
+
+/**
+ * The headings of a markup document as a tree. `mode` is ignored; a heading
+ * nests under the closest preceding heading with a smaller level.
+ */
+export declare function outline(
+  text: string,
+  options?: ParseOptions,
+): OutlineEntry[];
+
+export interface LabelsAndRefs {
+  labels: { name: string; range: [number, number] }[];
+  refs: { target: string; range: [number, number] }[];
+}
+
+/**
+ * Every `<label>` and `@ref` of a document, in document order, including
+ * those nested in headings, content blocks, and reference supplements.
+ */
+export declare function collectLabelsAndRefs(
+  text: string,
+  options?: ParseOptions,
+): LabelsAndRefs;
+
+export type ImportSource =
+  | { kind: "str"; value: string }
+  /** Any other expression, given by its `kind`. */
+  | { kind: "expr"; exprKind: AstExpr["kind"] };
+
+export type ImportEntry =
+  | {
+      type: "import";
+      range: [number, number];
+      source: ImportSource;
+      newName: string | null;
+      /** `null` for a plain `import "a.typ"` without a colon. */
+      imports:
+        | { kind: "wildcard" }
+        | {
+            kind: "items";
+            /** `path` is the imported item, e.g. `["b"]` for `b as c`. */
+            items: { path: string[]; newName: string | null }[];
+          }
+        | null;
+    }
+  | { type: "include"; range: [number, number]; source: ImportSource };
+
+/** Every `import` and `include` of a document, in document order. */
+export declare function collectImports(
+  text: string,
+  options?: ParseOptions,
+): ImportEntry[];
+
+export type SelectorKind =
+  | "function"
+  | "label"
+  | "text"
+  | "regex"
+  | "other"
+  | "absent";
+
+export type RuleEntry =
+  | {
+      type: "set";
+      range: [number, number];
+      /** The function set, if it is a plain identifier like `text`. */
+      target: string | null;
+      /** Whether the rule only applies `if` a condition holds. */
+      hasCondition: boolean;
+      selectorKind: null;
+    }
+  | {
+      type: "show";
+      range: [number, number];
+      /** The selector, if it is a plain identifier like `heading`. */
+      target: string | null;
+      hasCondition: false;
+      /** `"absent"` for `show: ..` without a selector. */
+      selectorKind: SelectorKind;
+    };
+
+/** Every `set` and `show` rule of a document, in document order. */
+export declare function collectRules(
+  text: string,
+  options?: ParseOptions,
+): RuleEntry[];
+
+export interface CallEntry {
+  range: [number, number];
+  /** Positional, named, and spread arguments, trailing content included. */
+  argCount: number;
+}
+
+/**
+ * Every call whose callee is `name` or a field access ending in it, like
+ * `figure(..)`, `std.figure(..)`, or `x.figure()`, in document order.
+ */
+export declare function findCalls(
+  text: string,
+  name: string,
+  options?: ParseOptions,
+): CallEntry[];
+
+export declare function countAst(text: string, options?: ParseOptions): number;
+
+/** Whether the text of the CST's leaves adds up to `text`. */
+export declare function verifyRoundtrip(
+  text: string,
+  options?: ParseOptions,
+): boolean;
+
+/**
+ * The offset of the first character where the text of the CST's leaves
+ * differs from `text`, or `undefined` if it does not.
+ */
+export declare function roundtripMismatch(
+  text: string,
+  options?: ParseOptions,
+): number | undefined;
+
+/** The kind name for each id emitted with `numericKinds`; never reordered. */
+export declare function kindTable(): string[];
+
+export declare function isValid(text: string, options?: ParseOptions): boolean;
+
+/**
+ * The options a `Document` honors. Its offsets, in `edit` and `errors`, are
+ * counted in `offsetEncoding` starting at `baseOffset`.
+ */
+export type DocumentOptions = Pick<
+  ParseOptions,
+  "mode" | "offsetEncoding" | "rangeEncoding" | "baseOffset" | "maxDepth"
+>;
+
+export declare class Document {
+  /** Throws for `normalizeNewlines`, which it does not support. */
+  constructor(text: string, options?: DocumentOptions);
+  free(): void;
+  /** Replaces the range `[start, end)` and reparses. */
+  edit(start: number, end: number, text: string): void;
+  /** The current diagnostics, without serializing the tree. */
+  errors(): ParseError[];
+  text(): string;
+}
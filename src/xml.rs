@@ -0,0 +1,177 @@
+use crate::cst::{CstNode, build_cst};
+use crate::parse_options::ParseOptions;
+
+/// Renders the CST of `text` as XML. Each node becomes an element named after
+/// its kind, `<Kind start="…" end="…">`, holding its children or, for a leaf,
+/// its text; placeholders carry `placeholder="true"` instead of a range.
+/// Syntax errors follow the tree as `<error start="…" end="…" message="…">`
+/// elements with one `<hint>` per hint, all inside a `<cst>` root.
+///
+/// The output is well-formed for any input. Characters XML cannot represent
+/// at all, like most control characters, become U+FFFD, so only for texts
+/// without them do the leaves add up to `text`.
+///
+/// ```
+/// use typst_ast::{ParseOptions, parse_xml};
+///
+/// let xml = parse_xml("*a&b*", &ParseOptions::default());
+/// assert_eq!(
+///     xml,
+///     "<cst><Markup start=\"0\" end=\"5\"><Strong start=\"0\" end=\"5\">\
+///      <Star start=\"0\" end=\"1\">*</Star><Markup start=\"1\" end=\"4\">\
+///      <Text start=\"1\" end=\"4\">a&amp;b</Text></Markup>\
+///      <Star start=\"4\" end=\"5\">*</Star></Strong></Markup></cst>",
+/// );
+/// ```
+pub fn parse_xml(text: &str, options: &ParseOptions) -> String {
+    let root = options.mode.unwrap_or_default().parse(text);
+    let result = build_cst(&root, options);
+    let mut out = String::from("<cst>");
+    write_node(&result.root, &mut out);
+    for error in &result.errors {
+        let [start, end] = error.range;
+        out.push_str(&format!(
+            "<error start=\"{start}\" end=\"{end}\" message=\"{}\">",
+            escape(&error.message, true)
+        ));
+        for hint in &error.hints {
+            out.push_str(&format!("<hint>{}</hint>", escape(hint, false)));
+        }
+        out.push_str("</error>");
+    }
+    out.push_str("</cst>");
+    out
+}
+
+pub(crate) fn write_node(root: &CstNode, out: &mut String) {
+    // An explicit stack, as the tree can be up to `max_depth` deep. Nodes
+    // with content are visited twice, to open and, with `true`, to close.
+    let mut stack = vec![(root, false)];
+    while let Some((node, close)) = stack.pop() {
+        if close {
+            out.push_str(&format!("</{}>", node.kind));
+            continue;
+        }
+        // Kinds are Rust identifiers, which are valid XML names as they are.
+        match node.range {
+            Some([start, end]) => {
+                out.push_str(&format!("<{} start=\"{start}\" end=\"{end}\"", node.kind))
+            }
+            None => out.push_str(&format!("<{} placeholder=\"true\"", node.kind)),
+        }
+        if node.text.is_none() && node.children.is_empty() {
+            out.push_str("/>");
+            continue;
+        }
+        out.push('>');
+        if let Some(text) = &node.text {
+            out.push_str(&escape(text, false));
+        }
+        stack.push((node, true));
+        stack.extend(node.children.iter().rev().map(|child| (child, false)));
+    }
+}
+
+/// Escapes `text` for character data or, with `attribute`, for a quoted
+/// attribute value, where whitespace other than spaces would be normalized
+/// away unless written as references.
+fn escape(text: &str, attribute: bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' if attribute => out.push_str("&quot;"),
+            '\r' => out.push_str("&#13;"),
+            '\t' | '\n' if attribute => out.push_str(&format!("&#{};", c as u32)),
+            '\t' | '\n' => out.push(c),
+            c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => out.push('\u{FFFD}'),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that every tag is closed in order, that there is a single root,
+    /// and that no `<` or `&` appears outside of markup and references.
+    fn assert_well_formed(xml: &str) {
+        let mut open: Vec<&str> = vec![];
+        let mut roots = 0;
+        let mut rest = xml;
+        while let Some(i) = rest.find(['<', '&']) {
+            let (before, after) = rest.split_at(i);
+            assert!(
+                !open.is_empty() || before.is_empty(),
+                "text outside the root"
+            );
+            if after.starts_with('&') {
+                let end = after.find(';').expect("unterminated reference");
+                let name = &after[1..end];
+                assert!(
+                    ["amp", "lt", "gt", "quot", "#9", "#10", "#13"].contains(&name),
+                    "bad reference {name:?}"
+                );
+                rest = &after[end + 1..];
+                continue;
+            }
+            let end = after.find('>').expect("unterminated tag");
+            let tag = &after[1..end];
+            assert!(!tag.contains('<'), "bad tag {tag:?}");
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name), "mismatched closing tag");
+            } else {
+                let name = tag.split([' ', '/']).next().unwrap();
+                assert!(!name.is_empty(), "empty tag name");
+                if open.is_empty() {
+                    roots += 1;
+                }
+                if !tag.ends_with('/') {
+                    open.push(name);
+                }
+            }
+            rest = &after[end + 1..];
+        }
+        assert!(open.is_empty(), "unclosed tags {open:?}");
+        assert!(rest.is_empty(), "text after the root");
+        assert_eq!(roots, 1);
+    }
+
+    #[test]
+    fn writes_well_formed_xml() {
+        for text in [
+            "= Title\n*bold* & _emph_ <label> @ref",
+            "#let f(x) = \"a<b&c\"\r\n$x^2$",
+            "[*",
+            "#f(\"\u{0}\u{1}\", <a>",
+            "```rs\nfn main() {}\n```",
+            "",
+        ] {
+            let xml = parse_xml(text, &ParseOptions::default());
+            assert_well_formed(&xml);
+        }
+    }
+
+    #[test]
+    fn escapes_attributes_and_keeps_errors() {
+        let xml = parse_xml("#f(\"a\\\"b\"", &ParseOptions::default());
+        assert_well_formed(&xml);
+        assert!(xml.contains("<error start=\"2\" end=\"3\" message=\"unclosed delimiter\">"));
+        assert_eq!(
+            parse_xml("\t\r\n", &ParseOptions::default()),
+            "<cst><Markup start=\"0\" end=\"3\"><Space start=\"0\" end=\"3\">\t&#13;\n</Space></Markup></cst>"
+        );
+        let options = ParseOptions {
+            include_placeholders: true,
+            ..Default::default()
+        };
+        let xml = parse_xml("#show:", &options);
+        assert_well_formed(&xml);
+        assert!(xml.contains("<None placeholder=\"true\"/>"), "{xml}");
+        assert_eq!(escape("\"a\"\n\u{1}", true), "&quot;a&quot;&#10;\u{FFFD}");
+    }
+}